@@ -0,0 +1,218 @@
+//! Pack all the saved data in a single backup file, and unpack it back. The backup is a
+//! text file that starts with the line `rusty-forest-backup 1`. Each saved file follows,
+//! as a line `file NAME LENGTH` and then the LENGTH bytes of the file. The files removed
+//! by `reset` are also chosen here.
+
+use std::fs;
+use std::path::Path;
+use crate::tree::{data_dir, write_atomic};
+
+/// The first line of a backup file.
+const HEADER: &str = "rusty-forest-backup 1";
+
+/// The files from the data directory that are saved in a backup.
+pub const BACKUP_FILES: [&str; 4] = ["trees.conf", "stats.conf", "state.json", "config.conf"];
+
+/// What is removed by `reset`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResetKind {
+    /// All the saved data.
+    All,
+    /// Only the tree collection.
+    Trees,
+    /// Only the grown trees and the counters computed from them.
+    Stats,
+}
+
+/// Get the files of the data directory that are removed by `reset`.
+pub fn reset_files(kind: ResetKind) -> Vec<&'static str> {
+    match kind {
+    ResetKind::All => { vec!["trees.conf", "stats.conf", "stats.db", "state.json", "config.conf"] }
+    ResetKind::Trees => { vec!["trees.conf"] }
+    ResetKind::Stats => { vec!["stats.conf", "stats.db", "state.json"] }
+    }
+}
+
+/// Get the paths of the given files that exist in the directory.
+pub fn existing_files(dir: &str, files: &[&str]) -> Vec<String> {
+    files.iter()
+        .map(|name| Path::new(dir).join(name))
+        .filter(|path| path.exists())
+        .map(|path| path.to_string_lossy().into_owned())
+        .collect()
+}
+
+/// Pack the saved data files into the content of a backup. The files that don't exist are
+/// skipped.
+pub fn create_backup() -> Result<String, String> {
+    create_backup_from(&data_dir()?)
+}
+
+/// Pack the saved data files from the given directory into the content of a backup.
+pub fn create_backup_from(dir: &str) -> Result<String, String> {
+    let mut backup = String::from(HEADER) + "\n";
+
+    for name in BACKUP_FILES {
+        let path = Path::new(dir).join(name).to_string_lossy().into_owned();
+        if !Path::new(&path).exists() {
+            continue;
+        }
+
+        let content = match fs::read_to_string(&path) {
+        Ok(x) => { x }
+        Err(x) => { return Err(format!("Failed to read {}: {}", path, x)); }
+        };
+
+        backup += &format!("file {} {}\n", name, content.len());
+        backup += &content;
+    }
+
+    Ok(backup)
+}
+
+/// Unpack the content of a backup into the names and contents of the saved files.
+pub fn parse_backup(backup: &str) -> Result<Vec<(String, String)>, String> {
+    let mut rest = match backup.strip_prefix(HEADER) {
+    Some(x) => { x.strip_prefix('\n').unwrap_or(x) }
+    None => { return Err("Failed to parse backup: this is not a rusty-forest backup".to_string()); }
+    };
+
+    let mut files: Vec<(String, String)> = Vec::new();
+
+    while !rest.is_empty() {
+        let (line, after) = match rest.split_once('\n') {
+        Some(x) => { x }
+        None => { return Err("Failed to parse backup: unexpected end of file".to_string()); }
+        };
+
+        let tokens: Vec<&str> = line.split(' ').collect();
+        if tokens.len() != 3 || tokens[0] != "file" {
+            return Err(format!("Failed to parse backup: unexpected line {}", line));
+        }
+
+        // Only the known files are restored, so the backup can't write anywhere else
+        if !BACKUP_FILES.contains(&tokens[1]) {
+            return Err(format!("Failed to parse backup: unknown file {}", tokens[1]));
+        }
+
+        let len = match tokens[2].parse::<usize>() {
+        Ok(x) if x <= after.len() && after.is_char_boundary(x) => { x }
+        _ => { return Err(format!("Failed to parse backup: wrong length for {}", tokens[1])); }
+        };
+
+        files.push((tokens[1].to_string(), after[..len].to_string()));
+        rest = &after[len..];
+    }
+
+    Ok(files)
+}
+
+/// Write the files unpacked from a backup in the data directory.
+pub fn restore_backup(files: &[(String, String)]) -> Result<(), String> {
+    restore_backup_to(&data_dir()?, files)
+}
+
+/// Write the files unpacked from a backup in the given directory.
+pub fn restore_backup_to(dir: &str, files: &[(String, String)]) -> Result<(), String> {
+    for (name, content) in files {
+        write_atomic(&Path::new(dir).join(name).to_string_lossy(), content)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::temp_dir;
+
+    /// Make a data directory with all the files that can be saved, and another file.
+    fn full_dir(name: &str) -> String {
+        let dir = temp_dir(name);
+        for file in reset_files(ResetKind::All).into_iter().chain(["notes.txt"]) {
+            fs::write(Path::new(&dir).join(file), file).unwrap();
+        }
+        dir
+    }
+
+    fn remove_all(paths: &[String]) {
+        for path in paths {
+            fs::remove_file(path).unwrap();
+        }
+    }
+
+    fn remaining(dir: &str) -> Vec<String> {
+        let mut names: Vec<String> = fs::read_dir(dir).unwrap()
+            .map(|x| x.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn reset_removes_all_the_data_files() {
+        let dir = full_dir("reset-all");
+        let paths = existing_files(&dir, &reset_files(ResetKind::All));
+        assert_eq!(paths.len(), 5);
+        assert!(paths.iter().all(|x| x.starts_with(&dir)));
+
+        remove_all(&paths);
+        assert_eq!(remaining(&dir), vec!["notes.txt"]);
+        assert!(existing_files(&dir, &reset_files(ResetKind::All)).is_empty());
+    }
+
+    #[test]
+    fn reset_can_remove_only_the_trees_or_the_stats() {
+        let dir = full_dir("reset-trees");
+        remove_all(&existing_files(&dir, &reset_files(ResetKind::Trees)));
+        assert_eq!(remaining(&dir), vec!["config.conf", "notes.txt", "state.json", "stats.conf", "stats.db"]);
+
+        let dir = full_dir("reset-stats");
+        remove_all(&existing_files(&dir, &reset_files(ResetKind::Stats)));
+        assert_eq!(remaining(&dir), vec!["config.conf", "notes.txt", "trees.conf"]);
+    }
+
+    #[test]
+    fn missing_files_are_not_listed() {
+        let dir = temp_dir("reset-missing");
+        fs::write(Path::new(&dir).join("stats.conf"), "").unwrap();
+
+        assert_eq!(existing_files(&dir, &reset_files(ResetKind::All)).len(), 1);
+        assert!(existing_files(&dir, &reset_files(ResetKind::Trees)).is_empty());
+    }
+
+    #[test]
+    fn backup_and_restore_round_trip() {
+        let dir = temp_dir("backup");
+        let path = |name: &str| { Path::new(&dir).join(name) };
+        let trees = format!("{}\n{}\n", crate::tree::format_header(), crate::testing::tree_line("oak"));
+        let stats = format!("{}\n{}\n", crate::tree::format_header(), crate::testing::grown("work", 25, 100));
+        fs::write(path("trees.conf"), &trees).unwrap();
+        fs::write(path("stats.conf"), &stats).unwrap();
+        fs::write(path("config.conf"), "min_session_minutes=10\nlabel=\u{e9}t\u{e9}\n").unwrap();
+
+        let backup = create_backup_from(&dir).unwrap();
+        assert!(backup.starts_with("rusty-forest-backup 1\nfile trees.conf "));
+
+        remove_all(&existing_files(&dir, &reset_files(ResetKind::All)));
+        assert!(remaining(&dir).is_empty());
+
+        let files = parse_backup(&backup).unwrap();
+        assert_eq!(files.iter().map(|x| x.0.as_str()).collect::<Vec<_>>(), vec!["trees.conf", "stats.conf", "config.conf"]);
+        restore_backup_to(&dir, &files).unwrap();
+
+        assert_eq!(fs::read_to_string(path("trees.conf")).unwrap(), trees);
+        assert_eq!(fs::read_to_string(path("stats.conf")).unwrap(), stats);
+        assert_eq!(fs::read_to_string(path("config.conf")).unwrap(), "min_session_minutes=10\nlabel=\u{e9}t\u{e9}\n");
+        assert_eq!(remaining(&dir), vec!["config.conf", "stats.conf", "trees.conf"]);
+    }
+
+    #[test]
+    fn broken_backups_are_rejected() {
+        assert!(parse_backup("not a backup").is_err());
+        assert!(parse_backup("rusty-forest-backup 1\nfile trees.conf 100\nshort").is_err());
+        assert!(parse_backup("rusty-forest-backup 1\nfile ../escape 2\nhi").is_err());
+        assert!(parse_backup("rusty-forest-backup 1\nfile trees.conf").is_err());
+        assert_eq!(parse_backup("rusty-forest-backup 1\n").unwrap(), Vec::new());
+    }
+}
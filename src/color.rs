@@ -0,0 +1,280 @@
+//! Write colors using the color depth chosen by the user. Trees are stored with 24-bit
+//! colors, but some terminals don't display them properly, so the colors can be
+//! converted to the nearest color of the 256 or 16 color palettes before writing them.
+//! The colors can also be left out entirely, drawing the trees only with their symbols.
+
+use std::fmt;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU8, AtomicU32, Ordering};
+
+/// The colors that can be used when writing to the terminal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorMode {
+    /// Write the exact 24-bit colors.
+    TrueColor,
+    /// Write the nearest colors from the 256 color palette.
+    Palette256,
+    /// Write the nearest colors from the 16 basic colors.
+    Palette16,
+    /// Don't write any color, only the symbols.
+    None,
+}
+
+impl FromStr for ColorMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+        "truecolor" => { Ok(ColorMode::TrueColor) }
+        "256" => { Ok(ColorMode::Palette256) }
+        "16" => { Ok(ColorMode::Palette16) }
+        "none" => { Ok(ColorMode::None) }
+        _ => { Err(format!("Unknown color depth {}; it should be truecolor, 256, 16 or none", s)) }
+        }
+    }
+}
+
+/// The color mode used by the whole program, as the index of the variant.
+static MODE: AtomicU8 = AtomicU8::new(0);
+
+/// Set the color mode used by the whole program.
+pub fn set_mode(mode: ColorMode) {
+    let idx = match mode {
+    ColorMode::TrueColor => { 0 }
+    ColorMode::Palette256 => { 1 }
+    ColorMode::Palette16 => { 2 }
+    ColorMode::None => { 3 }
+    };
+
+    MODE.store(idx, Ordering::Relaxed);
+}
+
+/// Get the color mode used by the whole program.
+pub fn mode() -> ColorMode {
+    match MODE.load(Ordering::Relaxed) {
+    1 => { ColorMode::Palette256 }
+    2 => { ColorMode::Palette16 }
+    3 => { ColorMode::None }
+    _ => { ColorMode::TrueColor }
+    }
+}
+
+/// The character drawn instead of the empty cells when no colors are used.
+static FILL: AtomicU32 = AtomicU32::new(' ' as u32);
+
+/// Set the character drawn instead of the empty cells when no colors are used.
+pub fn set_fill(chr: char) {
+    FILL.store(chr as u32, Ordering::Relaxed);
+}
+
+/// Get the symbol that should be written for a cell. Without colors, the empty cells
+/// would be invisible, so they are replaced by the fill character.
+pub fn visible_symbol(symbol: char) -> char {
+    if mode() == ColorMode::None && symbol == ' ' {
+        char::from_u32(FILL.load(Ordering::Relaxed)).unwrap_or(' ')
+    } else {
+        symbol
+    }
+}
+
+/// Guess the color mode supported by the terminal from the `COLORTERM` and `TERM`
+/// environment variables.
+pub fn detect_mode(colorterm: Option<&str>, term: Option<&str>) -> ColorMode {
+    if let Some("truecolor") | Some("24bit") = colorterm {
+        return ColorMode::TrueColor;
+    }
+
+    match term {
+    Some(x) if x.ends_with("-256color") => { ColorMode::Palette256 }
+    _ => { ColorMode::Palette16 }
+    }
+}
+
+/// The 16 basic colors, as they are displayed by xterm.
+const BASIC_COLORS: [(u8, u8, u8); 16] = [
+    (0, 0, 0), (205, 0, 0), (0, 205, 0), (205, 205, 0),
+    (0, 0, 238), (205, 0, 205), (0, 205, 205), (229, 229, 229),
+    (127, 127, 127), (255, 0, 0), (0, 255, 0), (255, 255, 0),
+    (92, 92, 255), (255, 0, 255), (0, 255, 255), (255, 255, 255),
+];
+
+/// The levels of each channel in the color cube of the 256 color palette.
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+/// Get the squared distance between two colors.
+fn distance(a: (u8, u8, u8), b: (u8, u8, u8)) -> u32 {
+    let d = |x: u8, y: u8| { (x as i32 - y as i32).pow(2) as u32 };
+
+    d(a.0, b.0) + d(a.1, b.1) + d(a.2, b.2)
+}
+
+/// Get the index of the nearest color from the 16 basic colors.
+pub fn nearest_16(rgb: (u8, u8, u8)) -> u8 {
+    (0..BASIC_COLORS.len())
+        .min_by_key(|&idx| distance(rgb, BASIC_COLORS[idx]))
+        .unwrap() as u8
+}
+
+/// Get the index of the nearest color from the 256 color palette. Only the color cube
+/// and the grayscale ramp are used, since the first 16 colors depend on the terminal.
+pub fn nearest_256(rgb: (u8, u8, u8)) -> u8 {
+    let level = |x: u8| {
+        (0..CUBE_LEVELS.len())
+            .min_by_key(|&idx| (x as i32 - CUBE_LEVELS[idx] as i32).abs())
+            .unwrap()
+    };
+
+    let (r, g, b) = (level(rgb.0), level(rgb.1), level(rgb.2));
+    let cube = (CUBE_LEVELS[r], CUBE_LEVELS[g], CUBE_LEVELS[b]);
+
+    // The grayscale ramp goes from 8 to 238, in steps of 10
+    let average = (rgb.0 as u32 + rgb.1 as u32 + rgb.2 as u32) / 3;
+    let gray_idx = (average.saturating_sub(3) / 10).min(23) as u8;
+    let gray_level = 8 + 10 * gray_idx;
+    let gray = (gray_level, gray_level, gray_level);
+
+    if distance(rgb, gray) < distance(rgb, cube) {
+        232 + gray_idx
+    } else {
+        16 + 36 * r as u8 + 6 * g as u8 + b as u8
+    }
+}
+
+/// Write the escape of a color, in the given color mode. base is 38 for the foreground
+/// and 48 for the background.
+fn write_color(f: &mut fmt::Formatter, rgb: (u8, u8, u8), base: u8) -> fmt::Result {
+    match mode() {
+    ColorMode::TrueColor => { write!(f, "\x1b[{};2;{};{};{}m", base, rgb.0, rgb.1, rgb.2) }
+    ColorMode::Palette256 => { write!(f, "\x1b[{};5;{}m", base, nearest_256(rgb)) }
+    ColorMode::Palette16 => {
+        // The basic colors have their own escapes: 30-37 and 90-97 for the foreground,
+        // and 10 more for the background
+        let idx = nearest_16(rgb);
+        let offset = if base == 38 { 0 } else { 10 };
+        if idx < 8 {
+            write!(f, "\x1b[{}m", 30 + offset + idx)
+        } else {
+            write!(f, "\x1b[{}m", 90 + offset + idx - 8)
+        }
+    }
+    ColorMode::None => { Ok(()) }
+    }
+}
+
+/// Set the foreground to the given color, using the chosen color mode.
+pub struct Fg(pub (u8, u8, u8));
+
+impl fmt::Display for Fg {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write_color(f, self.0, 38)
+    }
+}
+
+/// Set the background to the given color, using the chosen color mode.
+pub struct Bg(pub (u8, u8, u8));
+
+impl fmt::Display for Bg {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write_color(f, self.0, 48)
+    }
+}
+
+/// Reset the foreground and background to the default colors of the terminal. Nothing
+/// is written when no colors are used.
+pub struct Reset;
+
+impl fmt::Display for Reset {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match mode() {
+        ColorMode::None => { Ok(()) }
+        _ => { write!(f, "{}{}", termion::color::Fg(termion::color::Reset), termion::color::Bg(termion::color::Reset)) }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::color_mode;
+
+    #[test]
+    fn nearest_16_picks_the_basic_colors() {
+        assert_eq!(nearest_16((0, 0, 0)), 0);
+        assert_eq!(nearest_16((255, 255, 255)), 15);
+        assert_eq!(nearest_16((200, 10, 10)), 1);
+        assert_eq!(nearest_16((250, 5, 5)), 9);
+        assert_eq!(nearest_16((30, 200, 20)), 2);
+        assert_eq!(nearest_16((120, 130, 125)), 8);
+    }
+
+    #[test]
+    fn nearest_256_picks_the_cube_or_the_grays() {
+        assert_eq!(nearest_256((0, 0, 0)), 16);
+        assert_eq!(nearest_256((255, 255, 255)), 231);
+        assert_eq!(nearest_256((255, 0, 0)), 196);
+        assert_eq!(nearest_256((0, 135, 255)), 33);
+        assert_eq!(nearest_256((128, 128, 128)), 244);
+        assert_eq!(nearest_256((8, 8, 8)), 232);
+    }
+
+    #[test]
+    fn colors_are_written_for_the_chosen_depth() {
+        let _mode = color_mode(ColorMode::TrueColor);
+        assert_eq!(Fg((1, 2, 3)).to_string(), "\x1b[38;2;1;2;3m");
+        assert_eq!(Bg((1, 2, 3)).to_string(), "\x1b[48;2;1;2;3m");
+
+        set_mode(ColorMode::Palette256);
+        assert_eq!(Fg((255, 0, 0)).to_string(), "\x1b[38;5;196m");
+
+        set_mode(ColorMode::Palette16);
+        assert_eq!(Fg((200, 0, 0)).to_string(), "\x1b[31m");
+        assert_eq!(Bg((255, 255, 255)).to_string(), "\x1b[107m");
+    }
+
+    #[test]
+    fn color_depth_is_parsed() {
+        assert_eq!("truecolor".parse::<ColorMode>(), Ok(ColorMode::TrueColor));
+        assert_eq!("256".parse::<ColorMode>(), Ok(ColorMode::Palette256));
+        assert_eq!("16".parse::<ColorMode>(), Ok(ColorMode::Palette16));
+        assert!("8".parse::<ColorMode>().is_err());
+    }
+
+    #[test]
+    fn color_depth_is_detected_from_the_environment() {
+        assert_eq!(detect_mode(Some("truecolor"), Some("xterm")), ColorMode::TrueColor);
+        assert_eq!(detect_mode(Some("24bit"), None), ColorMode::TrueColor);
+        assert_eq!(detect_mode(None, Some("xterm-256color")), ColorMode::Palette256);
+        assert_eq!(detect_mode(Some("yes"), Some("screen-256color")), ColorMode::Palette256);
+        assert_eq!(detect_mode(None, Some("xterm")), ColorMode::Palette16);
+        assert_eq!(detect_mode(None, Some("linux")), ColorMode::Palette16);
+        assert_eq!(detect_mode(None, None), ColorMode::Palette16);
+    }
+
+    #[test]
+    fn no_colors_writes_only_the_symbols() {
+        let _mode = color_mode(ColorMode::None);
+        set_fill('.');
+
+        let mut tree = crate::testing::tree("oak");
+        tree.cells[0][0].symbol = '*';
+        let mut out = Vec::new();
+        crate::list::write_tree(&mut out, &tree, 1).unwrap();
+        crate::render::write_collage(&mut out, &[tree.clone(), tree], 2).unwrap();
+
+        let text = String::from_utf8(out).unwrap();
+        assert!(!text.contains('\x1b'));
+        assert!(text.starts_with("*....\n"));
+        assert_eq!(Fg((1, 2, 3)).to_string() + &Reset.to_string(), "");
+    }
+
+    #[test]
+    fn fill_replaces_only_the_empty_cells_without_colors() {
+        let _mode = color_mode(ColorMode::None);
+        set_fill('#');
+        assert_eq!(visible_symbol(' '), '#');
+        assert_eq!(visible_symbol('o'), 'o');
+
+        set_mode(ColorMode::TrueColor);
+        assert_eq!(visible_symbol(' '), ' ');
+    }
+}
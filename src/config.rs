@@ -0,0 +1,94 @@
+//! Handle the configuration file, `~/.rusty-forest/config.conf`. The file has a setting
+//! on each line, in `key=value` format. Empty lines and lines starting with `#` are
+//! ignored. The settings are used as defaults for the command line options.
+
+use std::collections::HashMap;
+use std::fs;
+use crate::tree::data_file;
+
+/// Name of the configuration file inside the data directory.
+const CONFIG_FILE: &str = "config.conf";
+
+/// The settings read from the configuration file.
+#[derive(Debug, Default)]
+pub struct Config {
+    values: HashMap<String, String>,
+}
+
+impl Config {
+    /// Load the configuration file. A missing file means no settings were changed.
+    pub fn load() -> Result<Config, String> {
+        let path = data_file(CONFIG_FILE)?;
+
+        let content = match fs::read_to_string(path) {
+        Ok(x) => { x }
+        Err(_) => { return Ok(Config::default()); }
+        };
+
+        Config::parse(&content)
+    }
+
+    /// Parse the content of a configuration file.
+    pub fn parse(content: &str) -> Result<Config, String> {
+        let mut values = HashMap::new();
+
+        for (idx, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            match line.split_once('=') {
+            Some((key, value)) => { values.insert(key.trim().to_string(), value.trim().to_string()); }
+            None => { return Err(format!("Failed to parse config: line {} is not in key=value format", idx + 1)); }
+            }
+        }
+
+        Ok(Config { values })
+    }
+
+    /// Get the value of a setting, if it is set.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.values.get(key).map(|x| x.as_str())
+    }
+
+    /// Get the value of a setting that should be a non-negative number, if it is set.
+    pub fn get_number(&self, key: &str) -> Result<Option<u64>, String> {
+        match self.get(key) {
+        Some(x) => {
+            match x.parse::<u64>() {
+            Ok(x) => { Ok(Some(x)) }
+            Err(_) => { Err(format!("Invalid value for {} in config: it should be a non-negative number", key)) }
+            }
+        }
+        None => { Ok(None) }
+        }
+    }
+
+    /// Get the value of a setting that should be true or false, if it is set.
+    pub fn get_bool(&self, key: &str) -> Result<Option<bool>, String> {
+        match self.get(key) {
+        Some("true") => { Ok(Some(true)) }
+        Some("false") => { Ok(Some(false)) }
+        Some(_) => { Err(format!("Invalid value for {} in config: it should be true or false", key)) }
+        None => { Ok(None) }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn min_session_is_a_number_of_minutes() {
+        let config = Config::parse("# no tiny sessions\nmin_session_minutes = 15\n").unwrap();
+        assert_eq!(config.get_number("min_session_minutes"), Ok(Some(15)));
+        assert_eq!(Config::parse("").unwrap().get_number("min_session_minutes"), Ok(None));
+
+        for value in ["-5", "ten", "1.5", ""] {
+            let config = Config::parse(&format!("min_session_minutes={}", value)).unwrap();
+            assert!(config.get_number("min_session_minutes").is_err(), "{}", value);
+        }
+    }
+}
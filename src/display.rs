@@ -4,6 +4,7 @@ use termion::screen::*;
 use termion::terminal_size;
 use termion::raw::RawTerminal;
 use crate::tree::Cell;
+use crate::color;
 use std::io::{Write, stdout, Stdout};
 
 /// A struct to work with the display of the screen. At the creation, it will
@@ -18,11 +19,12 @@ pub struct Display {
     old_matrix: Vec<Vec<Cell> >,
     /// the buffer; when writing stuff on the screen, this will be modified
     matrix: Vec<Vec<Cell> >,
-    /// a field containing an instance of stdout, wrapped in an AlternateScreen
-    stdout: AlternateScreen<RawTerminal<Stdout> >,
+    /// a field containing an instance of stdout, wrapped in an AlternateScreen; None if
+    /// the display only draws in the buffer
+    stdout: Option<AlternateScreen<RawTerminal<Stdout> > >,
     /// a HideCursor instance that will handle the cursor
     #[allow(dead_code)]
-    hide_cursor: cursor::HideCursor<Stdout>,
+    hide_cursor: Option<cursor::HideCursor<Stdout> >,
 }
 
 impl Display {
@@ -34,8 +36,8 @@ impl Display {
         let matrix = vec![vec![Cell::default(); width as usize]; height as usize];
         let mut screen = AlternateScreen::from(stdout().into_raw_mode().unwrap());
         
-        write!(screen, "{}{}{}", termion::color::Fg(termion::color::Rgb(0, 0, 0)),
-                               termion::color::Bg(termion::color::Rgb(0, 0, 0)),
+        write!(screen, "{}{}{}", color::Fg((0, 0, 0)),
+                               color::Bg((0, 0, 0)),
                                termion::clear::All).expect("Failed to write");
 
         screen.flush().expect("Failed to flush");
@@ -45,11 +47,37 @@ impl Display {
             height: height as usize,
             old_matrix,
             matrix,
-            stdout: screen,
-            hide_cursor: cursor::HideCursor::from(stdout()),
+            stdout: Some(screen),
+            hide_cursor: Some(cursor::HideCursor::from(stdout())),
         }
     }
 
+    /// Create a display of the given size that only draws in the buffer, without a
+    /// terminal, so what is drawn can be checked.
+    #[cfg(test)]
+    pub fn headless(width: usize, height: usize) -> Self {
+        Display {
+            width,
+            height,
+            old_matrix: vec![vec![Cell::default(); width]; height],
+            matrix: vec![vec![Cell::default(); width]; height],
+            stdout: None,
+            hide_cursor: None,
+        }
+    }
+
+    /// Get the cell drawn in the buffer on the l'th line and c'th column.
+    #[cfg(test)]
+    pub fn cell(&self, l: usize, c: usize) -> Cell {
+        self.matrix[l - 1][c - 1]
+    }
+
+    /// Get the symbols drawn in the buffer on the l'th line.
+    #[cfg(test)]
+    pub fn line(&self, l: usize) -> String {
+        self.matrix[l - 1].iter().map(|x| x.symbol).collect()
+    }
+
     /// Draw a pixel on the buffer on the l'th line and c'th column.
     pub fn draw_pixel(&mut self, l: usize, c: usize, cell: Cell) {
         if 1 <= l && l <= self.height && 1 <= c && c <= self.width {
@@ -160,42 +188,48 @@ impl Display {
 
     /// Display all the modifications on the screen and handle the terminal size changes.
     pub fn display(&mut self) {
+        let out = match self.stdout.as_mut() {
+        Some(x) => { x }
+        None => { return; }
+        };
+
         let (width, height) = terminal_size().unwrap();
         let (width, height) = (width as usize, height as usize);
+        // When the terminal is resized, every character is redrawn
+        let resized = self.width != width || self.height != height;
         
         for l in 1..height+1 {
             for c in 1..width+1 {
-                if (self.width != width || self.height != self.height)
-                && l <= self.height && c <= self.width {
+                if resized && l <= self.height && c <= self.width {
                     let (r, g, b) = self.matrix[l - 1][c - 1].fg;
                     let (r2, g2, b2) = self.matrix[l - 1][c - 1].bg;
-                    write!(self.stdout, "{}", termion::cursor::Goto(c as u16, l as u16))
+                    write!(out, "{}", termion::cursor::Goto(c as u16, l as u16))
                         .expect("Failed to write");
-                    write!(self.stdout, "{}{}{}", termion::color::Fg(termion::color::Rgb(r, g, b)),
-                                                  termion::color::Bg(termion::color::Rgb(r2, g2, b2)),
+                    write!(out, "{}{}{}", color::Fg((r, g, b)),
+                                                  color::Bg((r2, g2, b2)),
                                                   self.matrix[l - 1][c - 1].symbol)
                         .expect("Failed to write");
-                } else if self.width != width || self.height != height {
-                    write!(self.stdout, "{}", termion::cursor::Goto(c as u16, l as u16))
+                } else if resized {
+                    write!(out, "{}", termion::cursor::Goto(c as u16, l as u16))
                         .expect("Failed to write");
-                    write!(self.stdout, "{}{} ", termion::color::Fg(termion::color::Rgb(0, 0, 0)),
-                                                 termion::color::Bg(termion::color::Rgb(0, 0, 0)))
+                    write!(out, "{}{} ", color::Fg((0, 0, 0)),
+                                                 color::Bg((0, 0, 0)))
                         .expect("Failed to write");
                 } else if l <= self.height && c <= self.width &&
                    self.old_matrix[l - 1][c - 1] != self.matrix[l - 1][c - 1]{
                     let (r, g, b) = self.matrix[l - 1][c - 1].fg;
                     let (r2, g2, b2) = self.matrix[l - 1][c - 1].bg;
-                    write!(self.stdout, "{}", termion::cursor::Goto(c as u16, l as u16))
+                    write!(out, "{}", termion::cursor::Goto(c as u16, l as u16))
                         .expect("Failed to write");
-                    write!(self.stdout, "{}{}{}", termion::color::Fg(termion::color::Rgb(r, g, b)),
-                                                  termion::color::Bg(termion::color::Rgb(r2, g2, b2)),
+                    write!(out, "{}{}{}", color::Fg((r, g, b)),
+                                                  color::Bg((r2, g2, b2)),
                                                   self.matrix[l - 1][c - 1].symbol)
                         .expect("Failed to write");
                 } else if !(l <= self.height && c <= self.width) {
-                    write!(self.stdout, "{}", termion::cursor::Goto(c as u16, l as u16))
+                    write!(out, "{}", termion::cursor::Goto(c as u16, l as u16))
                         .expect("Failed to write");
-                    write!(self.stdout, "{}{} ", termion::color::Fg(termion::color::Rgb(0, 0, 0)),
-                                                 termion::color::Bg(termion::color::Rgb(0, 0, 0)))
+                    write!(out, "{}{} ", color::Fg((0, 0, 0)),
+                                                 color::Bg((0, 0, 0)))
                         .expect("Failed to write");
                 }
             }
@@ -204,6 +238,8 @@ impl Display {
         self.width = width;
         self.height = height;
 
+        // Vec::resize both grows and shrinks, so every line ends up exactly as wide as
+        // the screen, and the new lines and columns are blank
         self.matrix.resize(self.height, Vec::new());
         for line in 0..height {
             self.matrix[line].resize(self.width, Cell::default());
@@ -211,10 +247,34 @@ impl Display {
         
         self.old_matrix = self.matrix.clone();
         
-        self.stdout.flush()
+        out.flush()
             .expect("Failed to flush");
     }
     
+    /// Display already rendered text on the screen, replacing everything on it. The text 
+    /// may contain color escapes; the lines that don't fit on the screen are skipped.
+    /// This does not use the buffer, so it should not be mixed with draw calls.
+    pub fn display_text(&mut self, text: &str) {
+        let out = match self.stdout.as_mut() {
+        Some(x) => { x }
+        None => { return; }
+        };
+
+        let (_, height) = terminal_size().unwrap();
+
+        write!(out, "{}{}{}", termion::color::Fg(termion::color::Reset),
+                                      termion::color::Bg(termion::color::Reset),
+                                      termion::clear::All).expect("Failed to write");
+
+        for (l, line) in text.lines().take(height as usize).enumerate() {
+            write!(out, "{}{}", termion::cursor::Goto(1, l as u16 + 1), line)
+                .expect("Failed to write");
+        }
+
+        out.flush()
+            .expect("Failed to flush");
+    }
+
     /// Used for debug purposes.
     pub fn screen_shot(&self) {
         eprintln!("DEBUG:\n");
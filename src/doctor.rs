@@ -0,0 +1,404 @@
+//! Look for problems in the saved data, for the doctor subcommand. The checks don't change
+//! anything: each one only reports what it found. The lines that can't be read can then be
+//! removed with a repair, which keeps a backup of the original file.
+
+use std::collections::HashMap;
+use std::ffi::CString;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+use chrono::Local;
+use crate::tree::{Tree, GrownTree, data_dir, is_builtin, write_atomic};
+use crate::lock::{GROW_LOCK, is_stale};
+use crate::status::GrowStatus;
+use crate::session::Session;
+
+/// How serious the result of a check is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Health {
+    Ok,
+    /// Something looks odd, but everything still works.
+    Warning,
+    /// Some data can't be used.
+    Problem,
+}
+
+/// The result of a check.
+#[derive(Debug, Clone)]
+pub struct Check {
+    pub health: Health,
+    /// What was checked, for instance "trees.conf".
+    pub subject: String,
+    pub detail: String,
+}
+
+impl Check {
+    fn new(health: Health, subject: &str, detail: String) -> Check {
+        Check {
+            health,
+            subject: subject.to_string(),
+            detail,
+        }
+    }
+}
+
+/// Returns true if the current user can write in the directory.
+fn is_writable(dir: &str) -> bool {
+    match CString::new(dir) {
+    Ok(path) => { unsafe { libc::access(path.as_ptr(), libc::W_OK) == 0 } }
+    Err(_) => { false }
+    }
+}
+
+/// Check that the data directory exists and can be written.
+fn check_dir(dir: &str) -> Check {
+    if !Path::new(dir).is_dir() {
+        Check::new(Health::Problem, "data", format!("{} does not exist", dir))
+    } else if !is_writable(dir) {
+        Check::new(Health::Problem, "data", format!("{} can't be written", dir))
+    } else {
+        Check::new(Health::Ok, "data", dir.to_string())
+    }
+}
+
+/// Check the lines of the tree collection, and look for trees with the same name or the
+/// same appearance.
+pub fn check_trees(content: &str) -> Vec<Check> {
+    let mut checks = Vec::new();
+    let mut trees: Vec<Tree> = Vec::new();
+    let mut malformed = 0;
+
+    for (line, tree_str) in content.lines().enumerate() {
+        if tree_str.starts_with('#') {
+            continue;
+        }
+
+        match Tree::import_tree(tree_str.to_string()) {
+        Ok(tree) => { trees.push(tree); }
+        Err(x) => {
+            checks.push(Check::new(Health::Problem, "trees.conf", format!("line {}: {}", line + 1, x)));
+            malformed += 1;
+        }
+        }
+    }
+
+    let mut names: HashMap<&str, usize> = HashMap::new();
+    for tree in &trees {
+        *names.entry(&tree.name).or_insert(0) += 1;
+        if is_builtin(&tree.name) {
+            checks.push(Check::new(Health::Warning, "trees.conf", format!("{} is a built-in tree, so it's ignored", tree.name)));
+        }
+    }
+
+    let mut duplicate_names: Vec<&&str> = names.iter().filter(|x| *x.1 > 1).map(|x| x.0).collect();
+    duplicate_names.sort();
+    for name in duplicate_names {
+        checks.push(Check::new(Health::Warning, "trees.conf", format!("{} trees are named {}", names[*name], name)));
+    }
+
+    for (idx, tree) in trees.iter().enumerate() {
+        if let Some(other) = trees[..idx].iter().find(|x| x.same_appearance(tree)) {
+            checks.push(Check::new(Health::Warning, "trees.conf", format!("{} looks the same as {}", tree.name, other.name)));
+        }
+    }
+
+    checks.push(Check::new(if malformed == 0 { Health::Ok } else { Health::Problem }, "trees.conf",
+                           format!("{} trees, {} malformed lines", trees.len(), malformed)));
+    checks
+}
+
+/// Check the lines of the grown trees.
+pub fn check_stats(content: &str) -> Vec<Check> {
+    let mut checks = Vec::new();
+    let (mut valid, mut malformed) = (0, 0);
+
+    for (line, tree_str) in content.lines().enumerate() {
+        if tree_str.starts_with('#') {
+            continue;
+        }
+
+        match GrownTree::from_str(tree_str) {
+        Ok(_) => { valid += 1; }
+        Err(x) => {
+            checks.push(Check::new(Health::Problem, "stats.conf", format!("line {}: {}", line + 1, x)));
+            malformed += 1;
+        }
+        }
+    }
+
+    checks.push(Check::new(if malformed == 0 { Health::Ok } else { Health::Problem }, "stats.conf",
+                           format!("{} grown trees, {} malformed lines", valid, malformed)));
+    checks
+}
+
+/// Check a file of the directory that holds a tree collection or grown trees. A missing
+/// file is fine, it only means nothing was saved yet.
+fn check_file(dir: &str, name: &str, check: fn(&str) -> Vec<Check>) -> Vec<Check> {
+    let path = Path::new(dir).join(name);
+
+    if !path.exists() {
+        return vec![Check::new(Health::Ok, name, "missing, nothing was saved yet".to_string())];
+    }
+
+    match fs::read_to_string(&path) {
+    Ok(x) => { check(&x) }
+    Err(x) => { vec![Check::new(Health::Problem, name, format!("Failed to read {}: {}", path.display(), x))] }
+    }
+}
+
+/// Check the files left while growing a tree: the lock, the status and the session.
+fn check_growing() -> Vec<Check> {
+    let mut checks = Vec::new();
+
+    if is_stale(GROW_LOCK) {
+        checks.push(Check::new(Health::Warning, GROW_LOCK, "left by a session that is not running; it's removed by the next grow".to_string()));
+    }
+
+    match GrowStatus::load() {
+    Ok(Some(status)) if status.is_stale(Local::now().timestamp()) => {
+        checks.push(Check::new(Health::Warning, "status", "left by a session that is not running; it's removed by the next status".to_string()));
+    }
+    Ok(_) => {}
+    Err(x) => { checks.push(Check::new(Health::Problem, "status", x)); }
+    }
+
+    match Session::load() {
+    Ok(Some(session)) => {
+        checks.push(Check::new(Health::Ok, "session", format!("an unfinished session of {} can be resumed", session.tree.name)));
+    }
+    Ok(None) => {}
+    Err(x) => { checks.push(Check::new(Health::Problem, "session", x)); }
+    }
+
+    checks
+}
+
+/// What a repair removed from a file.
+#[derive(Debug, Clone)]
+pub struct Repair {
+    /// The file that was repaired.
+    pub name: String,
+    /// Where the original file was copied.
+    pub backup: String,
+    /// The removed lines, with their line numbers.
+    pub removed: Vec<(usize, String)>,
+}
+
+/// Returns true if the line of `trees.conf` can be read.
+fn is_valid_tree(line: &str) -> bool {
+    Tree::import_tree(line.to_string()).is_ok()
+}
+
+/// Returns true if the line of `stats.conf` can be read.
+fn is_valid_stat(line: &str) -> bool {
+    GrownTree::from_str(line).is_ok()
+}
+
+/// Remove the lines that can't be read from the content of a file. Returns the content
+/// that is left and the removed lines, with their line numbers.
+pub fn drop_invalid_lines(content: &str, is_valid: fn(&str) -> bool) -> (String, Vec<(usize, String)>) {
+    let mut kept = String::new();
+    let mut removed = Vec::new();
+
+    for (line, text) in content.lines().enumerate() {
+        // The header is kept
+        if text.starts_with('#') || is_valid(text) {
+            kept += text;
+            kept += "\n";
+        } else {
+            removed.push((line + 1, text.to_string()));
+        }
+    }
+
+    (kept, removed)
+}
+
+/// Get a name for the backup of the file that isn't used yet: the name of the file with
+/// `.bak` added, then `.bak.1`, `.bak.2` and so on. The older backups are never replaced.
+fn unused_backup(path: &str) -> String {
+    let backup = path.to_string() + ".bak";
+    let mut name = backup.clone();
+    let mut cnt = 1;
+
+    while Path::new(&name).exists() {
+        name = format!("{}.{}", backup, cnt);
+        cnt += 1;
+    }
+
+    name
+}
+
+/// Rewrite a file of the directory without the lines that can't be read. The original file
+/// is copied next to it first, with `.bak` added to its name, or `.bak.1`, `.bak.2` and so on
+/// if there are older backups. Returns None if there was nothing to remove, or if the file
+/// doesn't exist.
+fn repair_file(dir: &str, name: &str, is_valid: fn(&str) -> bool) -> Result<Option<Repair>, String> {
+    let path = Path::new(dir).join(name).to_string_lossy().into_owned();
+
+    let content = match fs::read_to_string(&path) {
+    Ok(x) => { x }
+    Err(_) => { return Ok(None); }
+    };
+
+    let (kept, removed) = drop_invalid_lines(&content, is_valid);
+    if removed.is_empty() {
+        return Ok(None);
+    }
+
+    let backup = unused_backup(&path);
+    write_atomic(&backup, &content)?;
+    write_atomic(&path, &kept)?;
+
+    Ok(Some(Repair {
+        name: name.to_string(),
+        backup,
+        removed,
+    }))
+}
+
+/// Remove the lines that can't be read from the files of the tree collection and the grown
+/// trees in the directory.
+pub fn repair_dir(dir: &str) -> Result<Vec<Repair>, String> {
+    let mut repairs = Vec::new();
+
+    for (name, is_valid) in [("trees.conf", is_valid_tree as fn(&str) -> bool), ("stats.conf", is_valid_stat)] {
+        if let Some(x) = repair_file(dir, name, is_valid)? {
+            repairs.push(x);
+        }
+    }
+
+    Ok(repairs)
+}
+
+/// Remove the lines that can't be read from the tree collection and the grown trees.
+pub fn repair() -> Result<Vec<Repair>, String> {
+    repair_dir(&data_dir()?)
+}
+
+/// Check the directory and the files of the tree collection and the grown trees in it.
+pub fn diagnose_dir(dir: &str) -> Vec<Check> {
+    let mut checks = vec![check_dir(dir)];
+    checks.extend(check_file(dir, "trees.conf", check_trees));
+    checks.extend(check_file(dir, "stats.conf", check_stats));
+    checks
+}
+
+/// Run all the checks on the saved data.
+pub fn diagnose() -> Vec<Check> {
+    let dir = match data_dir() {
+    Ok(x) => { x }
+    Err(x) => { return vec![Check::new(Health::Problem, "data", format!("Failed to find the data directory: {}", x))]; }
+    };
+
+    let mut checks = diagnose_dir(&dir);
+    checks.extend(check_growing());
+    checks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{temp_dir, tree_line, grown};
+    use crate::tree::{read_trees, get_stats_from};
+
+    /// Make a data directory with files saved by an older version, without the header,
+    /// that have some lines that can't be read.
+    fn broken_dir(name: &str) -> String {
+        let dir = temp_dir(name);
+        let trees = [tree_line("oak"), "garbage line".to_string(), tree_line("oak"), tree_line("default-2")].join("\n") + "\n";
+        fs::write(Path::new(&dir).join("trees.conf"), trees).unwrap();
+
+        let stats = [grown("work", 30, 1700000000).to_string(), "0:30/work".to_string(), grown("study", 60, 1700000100).to_string()].join("\n") + "\n";
+        fs::write(Path::new(&dir).join("stats.conf"), stats).unwrap();
+        dir
+    }
+
+    fn find<'a>(checks: &'a [Check], subject: &str, detail: &str) -> Option<&'a Check> {
+        checks.iter().find(|x| x.subject == subject && x.detail.contains(detail))
+    }
+
+    #[test]
+    fn broken_files_are_reported() {
+        let dir = broken_dir("doctor-broken");
+        let checks = diagnose_dir(&dir);
+
+        assert_eq!(find(&checks, "data", &dir).unwrap().health, Health::Ok);
+        assert_eq!(find(&checks, "trees.conf", "line 2:").unwrap().health, Health::Problem);
+        assert_eq!(find(&checks, "trees.conf", "2 trees are named oak").unwrap().health, Health::Warning);
+        assert_eq!(find(&checks, "trees.conf", "oak looks the same as oak").unwrap().health, Health::Warning);
+        assert_eq!(find(&checks, "trees.conf", "default-2 is a built-in tree").unwrap().health, Health::Warning);
+        assert_eq!(find(&checks, "trees.conf", "3 trees, 1 malformed lines").unwrap().health, Health::Problem);
+        assert_eq!(find(&checks, "stats.conf", "line 2:").unwrap().health, Health::Problem);
+        assert_eq!(find(&checks, "stats.conf", "2 grown trees, 1 malformed lines").unwrap().health, Health::Problem);
+    }
+
+    #[test]
+    fn checking_and_loading_leave_the_files_unchanged() {
+        let dir = broken_dir("doctor-unchanged");
+        let trees_path = Path::new(&dir).join("trees.conf").to_string_lossy().into_owned();
+        let stats_path = Path::new(&dir).join("stats.conf").to_string_lossy().into_owned();
+        let (trees, stats) = (fs::read(&trees_path).unwrap(), fs::read(&stats_path).unwrap());
+
+        diagnose_dir(&dir);
+        assert_eq!(read_trees(&trees_path).len(), 2);
+        assert_eq!(get_stats_from(&stats_path).unwrap().len(), 2);
+
+        assert_eq!(fs::read(&trees_path).unwrap(), trees);
+        assert_eq!(fs::read(&stats_path).unwrap(), stats);
+    }
+
+    #[test]
+    fn missing_files_are_fine() {
+        let dir = temp_dir("doctor-missing-files");
+        let checks = diagnose_dir(&dir);
+
+        assert!(checks.iter().all(|x| x.health == Health::Ok));
+        assert_eq!(checks.len(), 3);
+    }
+
+    #[test]
+    fn missing_dir_is_a_problem() {
+        let dir = temp_dir("doctor-missing-dir") + "/nothing";
+        assert_eq!(find(&diagnose_dir(&dir), "data", "does not exist").unwrap().health, Health::Problem);
+    }
+
+    #[test]
+    fn repair_keeps_only_the_valid_records_and_a_backup() {
+        let dir = broken_dir("doctor-repair");
+        let trees_path = Path::new(&dir).join("trees.conf").to_string_lossy().into_owned();
+        let stats_path = Path::new(&dir).join("stats.conf").to_string_lossy().into_owned();
+        let (trees, stats) = (fs::read_to_string(&trees_path).unwrap(), fs::read_to_string(&stats_path).unwrap());
+
+        let repairs = repair_dir(&dir).unwrap();
+        assert_eq!(repairs.len(), 2);
+        assert_eq!(repairs[0].name, "trees.conf");
+        assert_eq!(repairs[0].removed, vec![(2, "garbage line".to_string())]);
+        assert_eq!(repairs[1].name, "stats.conf");
+        assert_eq!(repairs[1].removed, vec![(2, "0:30/work".to_string())]);
+
+        // Every line left can be read
+        let repaired = fs::read_to_string(&trees_path).unwrap();
+        assert_eq!(repaired.lines().count(), 3);
+        assert!(repaired.lines().all(is_valid_tree));
+        let repaired = fs::read_to_string(&stats_path).unwrap();
+        assert_eq!(repaired, format!("{}\n{}\n", grown("work", 30, 1700000000), grown("study", 60, 1700000100)));
+
+        // The originals are kept
+        assert_eq!(repairs[0].backup, trees_path.clone() + ".bak");
+        assert_eq!(fs::read_to_string(&repairs[0].backup).unwrap(), trees);
+        assert_eq!(fs::read_to_string(&repairs[1].backup).unwrap(), stats);
+
+        // There is nothing left to repair
+        assert!(repair_dir(&dir).unwrap().is_empty());
+        assert!(diagnose_dir(&dir).iter().all(|x| x.health != Health::Problem));
+
+        // Repairing again keeps the first backup
+        let repaired = fs::read_to_string(&trees_path).unwrap();
+        fs::write(&trees_path, repaired.clone() + "more garbage\n").unwrap();
+        let repairs = repair_dir(&dir).unwrap();
+        assert_eq!(repairs.len(), 1);
+        assert_eq!(repairs[0].backup, trees_path.clone() + ".bak.1");
+        assert_eq!(fs::read_to_string(&repairs[0].backup).unwrap(), repaired + "more garbage\n");
+        assert_eq!(fs::read_to_string(trees_path + ".bak").unwrap(), trees);
+    }
+}
@@ -1,12 +1,14 @@
 extern crate termion;
 
-use crate::tree::Tree;
+use crate::tree::{Tree, MAX_NAME_LEN};
 use termion::async_stdin;
 use termion::event::{Event, Key};
 use termion::terminal_size;
 use std::io::{Read};
+use std::cmp;
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
+use std::collections::VecDeque;
 use crate::display::Display;
 use crate::tree::Cell;
 
@@ -14,19 +16,44 @@ use crate::tree::Cell;
 const SMALL_SCREEN_ERROR: &str = "The screen is too small, so the editor cannot be displayed properly. Make it larger (at least 22x30)";
 
 /// Editor instructions displayed on the right side.
-const INSTRUCTIONS: &str = "Walk around with the arrow keys. Change colors with the menu below. To draw a character, just press the character to print. For a clear square, use Space. After finishing this, press Enter. To exit the editor without saving anything, use CTRL+c.";
+const INSTRUCTIONS: &str = "Walk around with the arrow keys. Change colors with the menu below. To draw a character, just press the character to print. For a clear square, use Space. CTRL+e picks the colors and the symbol of the current cell. CTRL+f fills the current cell and the same cells around it with the brush. Undo with CTRL+z and redo with CTRL+y. Tab and Shift+Tab draw the next or previous symbol from a set of tree symbols. On a color row, you can also type a number from 0 to 255 and press Enter to set it. After finishing this, press Enter. To name the tree before drawing it, press CTRL+n. To exit the editor without saving anything, use CTRL+c and confirm with y. Press ? to see all the keys.";
+
+/// Warning that appears for a while when a character that can't be used in names is typed.
+const REJECTED_CHAR: &str = "Only letters, digits, space, - and _ can be used in the name";
+
+/// How long the warnings about the name are displayed.
+const NAME_WARNING_DURATION: Duration = Duration::from_secs(2);
 
 /// Instructions that appear when naming the tree.
-const NAME_TREE: &str = "Now you should give a name to your tree. It should only contain letters, digits, spaces and '-' or '_'";
+const NAME_TREE: &str = "Now you should give a name to your tree. It should only contain letters, digits, spaces and '-' or '_'. Press CTRL+n to go back to drawing.";
 
 /// An enum used to hold the editor state. This will be either EditTree, which means 
 /// that the editor is used to actually create the tree, and NameTree, which means that 
 /// here, a name should be given to the tree. Essentially, there are two menus.
+#[derive(Debug, Clone, Copy)]
 enum EditorState {
     EditTree,
     NameTree,
 }
 
+impl EditorState {
+    /// The menu that CTRL+n switches to, so the tree can be named before it's drawn.
+    fn toggled(self) -> EditorState {
+        match self {
+        EditorState::EditTree => { EditorState::NameTree }
+        EditorState::NameTree => { EditorState::EditTree }
+        }
+    }
+
+    /// The menu that Enter goes to, or None if the editor is finished.
+    fn advanced(self) -> Option<EditorState> {
+        match self {
+        EditorState::EditTree => { Some(EditorState::NameTree) }
+        EditorState::NameTree => { None }
+        }
+    }
+}
+
 /// The color used on the background, the fg color is used on the text.
 pub const BACKGROUND_GREEN: Cell = Cell {
     bg: (44, 77, 52), 
@@ -41,8 +68,305 @@ pub const FOREST_BORDERS: Cell = Cell {
     symbol: ' ',
 };
 
-/// Start the tree editor that returns the created tree.
-pub fn run_tree_editor() -> Tree {
+/// The keys of the editor and what they do, displayed by the help.
+const KEY_BINDINGS: [(&str, &str); 17] = [
+    ("Arrows", "move on the tree or between the color rows"),
+    ("Left, Right", "change the color on a color row"),
+    ("Other characters", "draw them on the current cell"),
+    ("Space", "clear the current cell"),
+    ("Tab, Shift+Tab", "draw the next or previous tree symbol"),
+    ("0-9, Enter", "set the color on a color row"),
+    ("Backspace", "erase the last digit typed on a color row"),
+    ("Ctrl+f", "fill the current cell and the same cells connected to it"),
+    ("Ctrl+e", "pick the colors and the symbol of the current cell"),
+    ("Ctrl+z", "undo the last change of a cell"),
+    ("Ctrl+y", "redo the last change that was undone"),
+    ("Enter", "finish drawing and name the tree"),
+    ("Ctrl+n", "switch between drawing and naming the tree"),
+    ("Ctrl+g", "show or hide the row and column numbers"),
+    ("?", "show this help"),
+    ("Any key", "hide this help"),
+    ("Ctrl+c", "exit without saving, after confirming with y"),
+];
+
+/// Get the text of the help, with a line for each key.
+fn help_text() -> String {
+    let mut text = "Keys of the editor:\n".to_string();
+    for (key, action) in KEY_BINDINGS {
+        text += &format!("\n{}: {}", key, action);
+    }
+    text
+}
+
+/// Get the first character of the name that is displayed, so that the cursor stays in
+/// the visible part of the name. The name scrolls only when the cursor would leave it.
+fn scroll_offset(cursor: usize, offset: usize, visible: usize) -> usize {
+    if cursor < offset {
+        cursor
+    } else if cursor >= offset + visible {
+        cursor + 1 - visible
+    } else {
+        offset
+    }
+}
+
+/// The symbol drawn in the preview of the brush, when the brush has no symbol.
+const PREVIEW_SYMBOL: char = '@';
+
+/// The symbols that can be drawn with Tab and Shift+Tab. Trees with symbols that don't fit
+/// in a byte are saved in the second version of the tree format.
+const CYCLE_SYMBOLS: [char; 12] = ['▲', '♠', '❀', '●', '■', '░', '▒', '▓', '♣', '◆', '*', '^'];
+
+/// Get the index of the symbol that comes after the current one in CYCLE_SYMBOLS, or
+/// before it if forward is false. The first symbol is chosen if none was chosen before.
+pub fn cycle_symbol(current: Option<usize>, forward: bool) -> usize {
+    let len = CYCLE_SYMBOLS.len();
+
+    match current {
+    Some(idx) if forward => { (idx + 1) % len }
+    Some(idx) => { (idx + len - 1) % len }
+    None if forward => { 0 }
+    None => { len - 1 }
+    }
+}
+
+/// Paint the cell at the given position and all the cells connected to it, up, down, left
+/// or right, that are the same as it. Returns true if any cell changed.
+pub fn flood_fill(cells: &mut [Vec<Cell>], l: usize, c: usize, brush: Cell) -> bool {
+    let target = cells[l][c];
+    if target == brush {
+        return false;
+    }
+
+    let mut stack = vec![(l, c)];
+    while let Some((l, c)) = stack.pop() {
+        if cells[l][c] != target {
+            continue;
+        }
+        cells[l][c] = brush;
+
+        if l > 0 { stack.push((l - 1, c)); }
+        if l + 1 < cells.len() { stack.push((l + 1, c)); }
+        if c > 0 { stack.push((l, c - 1)); }
+        if c + 1 < cells[l].len() { stack.push((l, c + 1)); }
+    }
+
+    true
+}
+
+/// Write a color in the hex format used by HTML, like #2c4d34.
+fn hex_color(rgb: (u8, u8, u8)) -> String {
+    format!("#{:02x}{:02x}{:02x}", rgb.0, rgb.1, rgb.2)
+}
+
+/// Get the color channel of the brush changed by the given row of the editor. The rows
+/// 5 to 7 are the background channels, and the rows 8 to 10 are the foreground ones.
+fn brush_channel(brush: &mut Cell, row: usize) -> Option<&mut u8> {
+    match row {
+    5  => { Some(&mut brush.bg.0) }
+    6  => { Some(&mut brush.bg.1) }
+    7  => { Some(&mut brush.bg.2) }
+    8  => { Some(&mut brush.fg.0) }
+    9  => { Some(&mut brush.fg.1) }
+    10 => { Some(&mut brush.fg.2) }
+    _ => { None }
+    }
+}
+
+/// Convert the digits typed on a color row to the value of the channel, clamped to 255.
+pub fn parse_channel(typed: &str) -> u8 {
+    typed.parse::<u32>().map_or(0, |x| x.min(255) as u8)
+}
+
+/// How many changes of the tree can be undone.
+const HISTORY_LEN: usize = 64;
+
+/// The cells of the tree before a change, and the position of the changed cell.
+struct Snapshot {
+    cells: Vec<Vec<Cell>>,
+    l: usize,
+    c: usize,
+}
+
+/// The changes of the tree that can be undone and redone.
+#[derive(Default)]
+struct History {
+    undo: VecDeque<Snapshot>,
+    redo: Vec<Snapshot>,
+}
+
+impl History {
+    /// Remember the tree before the cell at the given position is changed. The changes
+    /// that were undone can't be redone anymore.
+    fn record(&mut self, tree: &Tree, l: usize, c: usize) {
+        if self.undo.len() == HISTORY_LEN {
+            self.undo.pop_front();
+        }
+        self.undo.push_back(Snapshot { cells: tree.cells.clone(), l, c });
+        self.redo.clear();
+    }
+
+    /// Undo the last change of the tree. Returns the position of the changed cell.
+    fn undo(&mut self, tree: &mut Tree) -> Option<(usize, usize)> {
+        let snapshot = self.undo.pop_back()?;
+        let cells = std::mem::replace(&mut tree.cells, snapshot.cells);
+        self.redo.push(Snapshot { cells, l: snapshot.l, c: snapshot.c });
+        Some((snapshot.l, snapshot.c))
+    }
+
+    /// Redo the last change that was undone. Returns the position of the changed cell.
+    fn redo(&mut self, tree: &mut Tree) -> Option<(usize, usize)> {
+        let snapshot = self.redo.pop()?;
+        let cells = std::mem::replace(&mut tree.cells, snapshot.cells);
+        self.undo.push_back(Snapshot { cells, l: snapshot.l, c: snapshot.c });
+        Some((snapshot.l, snapshot.c))
+    }
+}
+
+/// Insert a character typed while naming the tree at the cursor, and move the cursor after
+/// it. Returns the warning to display if the character can't be used or the name is too
+/// long.
+fn type_name_char(name: &mut String, cursor: &mut usize, x: char) -> Result<(), String> {
+    match x {
+    'a'..='z' | 'A'..='Z' | '0'..='9' | ' ' | '-' | '_' if name.len() >= MAX_NAME_LEN => {
+        Err(format!("The name can't be longer than {} characters", MAX_NAME_LEN))
+    }
+    'a'..='z' | 'A'..='Z' | '0'..='9' | ' ' | '-' | '_' => {
+        name.insert(*cursor, x);
+        *cursor = *cursor + 1;
+        Ok(())
+    }
+    _ => { Err(REJECTED_CHAR.to_string()) }
+    }
+}
+
+/// Returns true if the tree is the same as the one the editor started from, so there is
+/// nothing to lose when leaving the editor.
+pub fn is_unchanged(tree: &Tree, start: &Tree) -> bool {
+    tree.name == start.name && tree.cells == start.cells
+}
+
+/// What is shown while drawing the tree.
+struct EditView<'a> {
+    /// The tree that is drawn.
+    tree: &'a Tree,
+    /// The row of the cursor: 0 to 4 are the rows of the tree, the others are color rows.
+    l: usize,
+    /// The column of the cursor on the tree.
+    c: usize,
+    /// The colors and the symbol that are drawn.
+    brush: Cell,
+    /// The digits typed on a color row, until Enter is pressed.
+    typed_channel: &'a str,
+    /// Show the row and column numbers around the tree.
+    show_ruler: bool,
+    /// Show the help over everything else.
+    show_help: bool,
+}
+
+/// Draw the menu used to draw the tree, on a screen of the given size.
+fn draw_edit_tree(display: &mut Display, width: usize, height: usize, view: &EditView) {
+    for i in 1..width+1 {
+        display.draw_pixel(1, i, FOREST_BORDERS);
+        display.draw_pixel(height, i, FOREST_BORDERS);
+    }
+    
+    for i in 1..height+1 {
+        display.draw_pixel(i, 8, FOREST_BORDERS);
+        display.draw_pixel(i, 9, FOREST_BORDERS);
+    }
+
+    for i in 1..7+1 {
+        display.draw_pixel(2, i, FOREST_BORDERS);
+        display.draw_pixel(1 + i, 1, FOREST_BORDERS);
+        display.draw_pixel(8, i, FOREST_BORDERS);
+        display.draw_pixel(1 + i, 7, FOREST_BORDERS);
+    }
+
+    for l in 0..5 {
+        for c in 0..5 {
+            display.draw_pixel(3 + l, 2 + c, view.tree.cells[l][c]);
+        }
+    }
+
+    if view.show_ruler {
+        // The numbers are drawn on the border around the tree
+        let ruler = Cell { bg: FOREST_BORDERS.bg, fg: BACKGROUND_GREEN.fg, symbol: ' ' };
+        for i in 0..5 {
+            let digit = std::char::from_digit(i as u32 + 1, 10).unwrap();
+            display.draw_pixel(2, 2 + i, ruler.change_symbol(digit));
+            display.draw_pixel(3 + i, 1, ruler.change_symbol(digit));
+        }
+    }
+
+    display.draw_string( 9, 1, BACKGROUND_GREEN, "BG");
+    display.draw_string(10, 1, BACKGROUND_GREEN, "Red");
+    display.draw_string(11, 1, BACKGROUND_GREEN, "Green");
+    display.draw_string(12, 1, BACKGROUND_GREEN, "Blue");
+    display.draw_string(15, 1, BACKGROUND_GREEN, "FG");
+    display.draw_string(16, 1, BACKGROUND_GREEN, "Red");
+    display.draw_string(17, 1, BACKGROUND_GREEN, "Green");
+    display.draw_string(18, 1, BACKGROUND_GREEN, "Blue");
+    
+    if view.l < 5 {
+        let mut cursor_brush = view.brush;
+        cursor_brush.fg = (255, 255, 255);
+        cursor_brush.symbol = '*';
+        display.draw_pixel(view.l + 3, view.c + 2, cursor_brush);
+    } else if 5 <= view.l && view.l <= 7 {
+        display.draw_string(5 + view.l, 6, BACKGROUND_GREEN, "<>");
+    } else {
+        display.draw_string(8 + view.l, 6, BACKGROUND_GREEN, "<>");
+    }
+    
+    let cost = view.tree.cost();
+    let extended_instr = INSTRUCTIONS.to_owned() + &format!("\n\nTree cost: {:02}:{:02}", cost / 60, cost % 60);
+
+    display.fit_string_to_box(2, 10, width - 9, height - 2, 
+        BACKGROUND_GREEN, &extended_instr);
+    
+    let swatch = view.brush.change_symbol(' ');
+    display.draw_pixel(height - 1, 10, swatch);
+    display.draw_pixel(height - 1, 11, swatch);
+    
+    display.draw_string(height - 1, 12, BACKGROUND_GREEN,
+        &format!("BG: {:?}", view.brush.bg));
+    
+    display.draw_pixel(height - 2, 10, Cell::bg(view.brush.fg.0, view.brush.fg.1, view.brush.fg.2));
+    display.draw_pixel(height - 2, 11, Cell::bg(view.brush.fg.0, view.brush.fg.1, view.brush.fg.2));
+
+    display.draw_string(height - 2, 12, BACKGROUND_GREEN,
+        &format!("FG: {:?}", view.brush.fg));
+
+    // Preview a painted cell, with the foreground symbol over the background
+    let mut preview = view.brush;
+    if preview.symbol == ' ' {
+        preview.symbol = PREVIEW_SYMBOL;
+    }
+    display.draw_pixel(height - 3, 10, preview);
+    display.draw_pixel(height - 3, 11, preview);
+
+    display.draw_string(height - 3, 12, BACKGROUND_GREEN,
+        &format!("Brush: {} on {}", hex_color(view.brush.fg), hex_color(view.brush.bg)));
+
+    if !view.typed_channel.is_empty() {
+        display.draw_string(height - 4, 10, BACKGROUND_GREEN,
+            &format!("Value: {} (Enter to set)", view.typed_channel));
+    }
+
+    if view.show_help {
+        for l in 2..height {
+            for c in 2..width {
+                display.draw_pixel(l, c, BACKGROUND_GREEN);
+            }
+        }
+        display.fit_string_to_box(3, 3, width - 4, height - 4, BACKGROUND_GREEN, &help_text());
+    }
+}
+
+/// Start the tree editor from the given tree, which is blank when creating a new tree.
+/// Returns the edited tree, or None if the user left the editor without finishing it.
+pub fn run_tree_editor(start: Tree) -> Option<Tree> {
     let mut stdin = async_stdin().bytes();
     let mut exit_program = false;
 
@@ -50,17 +374,37 @@ pub fn run_tree_editor() -> Tree {
     display.clear_screen(Cell::default());
     
     let mut state = EditorState::EditTree;
-    let mut final_tree = Tree::default();
+    let mut final_tree = start.clone();
 
     let mut l_tree = 0;
     let mut c_tree = 0;
 
     let mut brush: Cell = Cell::default();
 
-    let mut str_cursor: usize = 0;
+    let mut str_cursor: usize = final_tree.name.len();
+    // The first character of the name that is displayed, when it's too long for the box
+    let mut name_offset: usize = 0;
+
+    let mut show_help = false;
+    let mut show_ruler = false;
+
+    // Leaving with CTRL+c asks for confirmation if something was drawn
+    let mut confirm_exit = false;
+    let mut discarded = false;
+
+    let mut history = History::default();
+
+    // The last symbol chosen with Tab or Shift+Tab
+    let mut symbol_idx: Option<usize> = None;
+
+    // The digits typed on a color row, until Enter is pressed
+    let mut typed_channel = String::new();
 
     let mut banner: String = NAME_TREE.to_string();
 
+    // The warning about the last character rejected from the name, and when it was typed
+    let mut name_warning: Option<(String, Instant)> = None;
+
     while !exit_program {
         let (width, height) = terminal_size().unwrap();
         let (width, height) = (width as usize, height as usize);
@@ -73,21 +417,71 @@ pub fn run_tree_editor() -> Tree {
             Some(k) => {
                 let e = termion::event::parse_event(k.unwrap(), &mut stdin);
                 match e {
-                Ok(Event::Key(Key::Ctrl('c'))) => { exit_program = true; } 
+                Ok(Event::Key(Key::Char('y'))) | Ok(Event::Key(Key::Char('Y'))) if confirm_exit => {
+                    exit_program = true;
+                    discarded = true;
+                }
+                _ if confirm_exit => { confirm_exit = false; }
+                _ if show_help => { show_help = false; }
+                Ok(Event::Key(Key::Ctrl('g'))) => { show_ruler = !show_ruler; }
+                Ok(Event::Key(key @ Key::Ctrl('z'))) | Ok(Event::Key(key @ Key::Ctrl('y'))) if matches!(state, EditorState::EditTree) => {
+                    typed_channel.clear();
+                    let changed = if key == Key::Ctrl('z') {
+                        history.undo(&mut final_tree)
+                    } else {
+                        history.redo(&mut final_tree)
+                    };
+                    // Go back to the cell that changed, so the change can be seen
+                    if let Some((l, c)) = changed {
+                        l_tree = l;
+                        c_tree = c;
+                    }
+                }
+                Ok(Event::Key(Key::Ctrl('e'))) if matches!(state, EditorState::EditTree) && l_tree < 5 => {
+                    brush = final_tree.cells[l_tree][c_tree];
+                    // Tab goes on from the picked symbol, if it's one of the tree symbols
+                    symbol_idx = CYCLE_SYMBOLS.iter().position(|x| *x == brush.symbol);
+                }
+                Ok(Event::Key(Key::Ctrl('f'))) if matches!(state, EditorState::EditTree) && l_tree < 5 => {
+                    let before = final_tree.clone();
+                    if flood_fill(&mut final_tree.cells, l_tree, c_tree, brush) {
+                        history.record(&before, l_tree, c_tree);
+                    }
+                }
+                Ok(Event::Key(Key::Ctrl('n'))) => {
+                    typed_channel.clear();
+                    state = state.toggled();
+                }
+                Ok(Event::Key(Key::Ctrl('c'))) => {
+                    if is_unchanged(&final_tree, &start) {
+                        exit_program = true;
+                        discarded = true;
+                    } else {
+                        confirm_exit = true;
+                    }
+                } 
                 Ok(Event::Key(Key::Char('\n'))) => {
                     match state {
-                    EditorState::EditTree => { state = EditorState::NameTree; }
-                    EditorState::NameTree => {
-                        if final_tree.name.is_empty() {
-                            banner = "Please name your tree!".to_string();
-                        } else {
-                            exit_program = true;
+                    EditorState::EditTree if !typed_channel.is_empty() => {
+                        if let Some(channel) = brush_channel(&mut brush, l_tree) {
+                            *channel = parse_channel(&typed_channel);
+                        }
+                        typed_channel.clear();
+                    }
+                    EditorState::NameTree if final_tree.name.is_empty() => {
+                        banner = "Please name your tree!".to_string();
+                    }
+                    _ => {
+                        match state.advanced() {
+                        Some(x) => { state = x; }
+                        None => { exit_program = true; }
                         }
                     }
                     }
                }
 
                 Ok(Event::Key(Key::Up)) => {
+                    typed_channel.clear();
                     match state {
                     EditorState::EditTree => {
                         if l_tree == 0 {
@@ -101,6 +495,7 @@ pub fn run_tree_editor() -> Tree {
                 }
                 
                 Ok(Event::Key(Key::Down)) => {
+                    typed_channel.clear();
                     match state {
                     EditorState::EditTree => {
                         l_tree = (l_tree + 1) % 11;
@@ -110,6 +505,7 @@ pub fn run_tree_editor() -> Tree {
                 }
 
                 Ok(Event::Key(Key::Left)) => {
+                    typed_channel.clear();
                     match state {
                     EditorState::EditTree => {
                         match l_tree {
@@ -138,6 +534,7 @@ pub fn run_tree_editor() -> Tree {
                 }
 
                 Ok(Event::Key(Key::Right)) => {
+                    typed_channel.clear();
                     match state {
                     EditorState::EditTree => {
                         match l_tree {
@@ -160,22 +557,41 @@ pub fn run_tree_editor() -> Tree {
                     
                 }
 
+                Ok(Event::Key(key @ Key::Char('\t'))) | Ok(Event::Key(key @ Key::BackTab)) => {
+                    match state {
+                    EditorState::EditTree => {
+                        let forward = key == Key::Char('\t');
+                        let idx = cycle_symbol(symbol_idx, forward);
+                        symbol_idx = Some(idx);
+                        brush.symbol = CYCLE_SYMBOLS[idx];
+                        if l_tree < 5 {
+                            history.record(&final_tree, l_tree, c_tree);
+                            final_tree.cells[l_tree][c_tree] = brush;
+                        }
+                    }
+                    EditorState::NameTree => {}
+                    }
+                }
+
+                Ok(Event::Key(Key::Char('?'))) if matches!(state, EditorState::EditTree) => {
+                    show_help = true;
+                }
+
                 Ok(Event::Key(Key::Char(x))) => {
                     match state {
                     EditorState::EditTree => {
                         if l_tree < 5 {
                             brush.symbol = x;
+                            history.record(&final_tree, l_tree, c_tree);
                             final_tree.cells[l_tree][c_tree] = brush;
+                        } else if x.is_ascii_digit() && typed_channel.len() < 3 {
+                            typed_channel.push(x);
                         }
                     }
                     EditorState::NameTree => {
-                        match x {
-                        'a'..='z' | 'A'..='Z' | '0'..='9' | ' ' | '-' | '_' => {
-                            final_tree.name.insert(str_cursor, x);
-                            str_cursor = str_cursor + 1;
-                        }
-                        _ => {
-                        }
+                        match type_name_char(&mut final_tree.name, &mut str_cursor, x) {
+                        Ok(()) => { name_warning = None; }
+                        Err(warning) => { name_warning = Some((warning, Instant::now())); }
                         }
                     }
                     }
@@ -183,13 +599,15 @@ pub fn run_tree_editor() -> Tree {
                 
                 Ok(Event::Key(Key::Backspace)) => {
                     match state {
+                    EditorState::EditTree => {
+                        typed_channel.pop();
+                    }
                     EditorState::NameTree => {
                         if str_cursor > 0 {
                             str_cursor = str_cursor - 1;
                             final_tree.name.remove(str_cursor);
                         }
                     }
-                    _ => {}
                     }
                 }
 
@@ -230,67 +648,16 @@ pub fn run_tree_editor() -> Tree {
         } else {
             match state {
             EditorState::EditTree => {
-                for i in 1..width+1 {
-                    display.draw_pixel(1, i, FOREST_BORDERS);
-                    display.draw_pixel(height, i, FOREST_BORDERS);
-                }
-                
-                for i in 1..height+1 {
-                    display.draw_pixel(i, 8, FOREST_BORDERS);
-                    display.draw_pixel(i, 9, FOREST_BORDERS);
-                }
-            
-                for i in 1..7+1 {
-                    display.draw_pixel(2, i, FOREST_BORDERS);
-                    display.draw_pixel(1 + i, 1, FOREST_BORDERS);
-                    display.draw_pixel(8, i, FOREST_BORDERS);
-                    display.draw_pixel(1 + i, 7, FOREST_BORDERS);
-                }
-            
-                for l in 0..5 {
-                    for c in 0..5 {
-                        display.draw_pixel(3 + l, 2 + c, final_tree.cells[l][c]);
-                    }
-                }
-
-                display.draw_string( 9, 1, BACKGROUND_GREEN, "BG");
-                display.draw_string(10, 1, BACKGROUND_GREEN, "Red");
-                display.draw_string(11, 1, BACKGROUND_GREEN, "Green");
-                display.draw_string(12, 1, BACKGROUND_GREEN, "Blue");
-                display.draw_string(15, 1, BACKGROUND_GREEN, "FG");
-                display.draw_string(16, 1, BACKGROUND_GREEN, "Red");
-                display.draw_string(17, 1, BACKGROUND_GREEN, "Green");
-                display.draw_string(18, 1, BACKGROUND_GREEN, "Blue");
-                
-                if l_tree < 5 {
-                    let mut cursor_brush = brush;
-                    cursor_brush.fg = (255, 255, 255);
-                    cursor_brush.symbol = '*';
-                    display.draw_pixel(l_tree + 3, c_tree + 2, cursor_brush);
-                } else if 5 <= l_tree && l_tree <= 7 {
-                    display.draw_string(5 + l_tree, 6, BACKGROUND_GREEN, "<>");
-                } else {
-                    display.draw_string(8 + l_tree, 6, BACKGROUND_GREEN, "<>");
-                }
-                
-                let cost = final_tree.cost();
-                let extended_instr = INSTRUCTIONS.to_owned() + &format!("\n\nTree cost: {:02}:{:02}", cost / 60, cost % 60);
-
-                display.fit_string_to_box(2, 10, width - 9, height - 2, 
-                    BACKGROUND_GREEN, &extended_instr);
-                
-                brush.symbol = ' ';
-                display.draw_pixel(height - 1, 10, brush);
-                display.draw_pixel(height - 1, 11, brush);
-                
-                display.draw_string(height - 1, 12, BACKGROUND_GREEN,
-                    &format!("BG: {:?}", brush.bg));
-                
-                display.draw_pixel(height - 2, 10, Cell::bg(brush.fg.0, brush.fg.1, brush.fg.2));
-                display.draw_pixel(height - 2, 11, Cell::bg(brush.fg.0, brush.fg.1, brush.fg.2));
-
-                display.draw_string(height - 2, 12, BACKGROUND_GREEN,
-                    &format!("FG: {:?}", brush.fg));
+                let view = EditView {
+                    tree: &final_tree,
+                    l: l_tree,
+                    c: c_tree,
+                    brush,
+                    typed_channel: &typed_channel,
+                    show_ruler,
+                    show_help,
+                };
+                draw_edit_tree(&mut display, width, height, &view);
             }
             EditorState::NameTree => {
                 for i in 1..width+1 {
@@ -302,21 +669,361 @@ pub fn run_tree_editor() -> Tree {
                     display.draw_pixel(i, 1, FOREST_BORDERS);
                     display.draw_pixel(i, width, FOREST_BORDERS);
                 }
-                display.fit_string_to_box(2, 2, width - 2, 4, BACKGROUND_GREEN, &banner);
-                display.fit_string_to_box_hard_wrap(7, 2, width - 2, height - 6, BACKGROUND_GREEN, &final_tree.name);
-            
-                let curs_lin = str_cursor / (width - 2) + 7;
-                let curs_col = str_cursor % (width - 2) + 2;
-                display.draw_pixel(curs_lin, curs_col, Cell::bg(255, 255, 255));
+                let shown_banner = match name_warning {
+                Some((ref warning, at)) if at.elapsed() < NAME_WARNING_DURATION => { warning }
+                _ => { &banner }
+                };
+                display.fit_string_to_box(2, 2, width - 2, 4, BACKGROUND_GREEN, shown_banner);
+
+                // The name is kept on a single line, which scrolls to follow the cursor
+                let visible = width - 2;
+                name_offset = scroll_offset(str_cursor, name_offset, visible);
+                let shown_end = cmp::min(final_tree.name.len(), name_offset + visible);
+                display.draw_string(7, 2, BACKGROUND_GREEN, &final_tree.name[name_offset..shown_end]);
+
+                display.draw_pixel(7, str_cursor - name_offset + 2, Cell::bg(255, 255, 255));
             }
             }
         }
 
+        if confirm_exit {
+            display.draw_string(1, 1, BACKGROUND_GREEN, "Discard the unsaved tree? [y/N]");
+        }
+
         display.display();
         thread::sleep(Duration::from_millis(50));
     }
 
-
-    return final_tree;
+    if discarded {
+        None
+    } else {
+        Some(final_tree)
+    }
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paint(symbol: char) -> Cell {
+        Cell::default().change_symbol(symbol)
+    }
+
+    const WIDTH: usize = 60;
+    const HEIGHT: usize = 30;
+
+    fn view(tree: &Tree) -> EditView<'_> {
+        EditView {
+            tree,
+            l: 0,
+            c: 0,
+            brush: Cell::default(),
+            typed_channel: "",
+            show_ruler: false,
+            show_help: false,
+        }
+    }
+
+    fn draw(view: &EditView) -> Display {
+        let mut display = Display::headless(WIDTH, HEIGHT);
+        draw_edit_tree(&mut display, WIDTH, HEIGHT, view);
+        display
+    }
+
+    #[test]
+    fn channel_is_clamped_to_255() {
+        assert_eq!(parse_channel("128"), 128);
+        assert_eq!(parse_channel("0"), 0);
+        assert_eq!(parse_channel("255"), 255);
+        assert_eq!(parse_channel("256"), 255);
+        assert_eq!(parse_channel("999"), 255);
+        assert_eq!(parse_channel(""), 0);
+    }
+
+    #[test]
+    fn channel_rows_change_the_brush() {
+        let mut brush = Cell::default();
+        *brush_channel(&mut brush, 5).unwrap() = 10;
+        *brush_channel(&mut brush, 10).unwrap() = 20;
+
+        assert_eq!(brush.bg.0, 10);
+        assert_eq!(brush.fg.2, 20);
+        assert!(brush_channel(&mut brush, 4).is_none());
+        assert!(brush_channel(&mut brush, 11).is_none());
+    }
+
+    #[test]
+    fn fill_paints_only_the_connected_same_cells() {
+        let mut tree = Tree::default();
+        // A wall on the third column splits the tree in two
+        for l in 0..5 {
+            tree.cells[l][2] = paint('#');
+        }
+
+        assert!(flood_fill(&mut tree.cells, 0, 0, paint('*')));
+        for l in 0..5 {
+            assert_eq!(tree.cells[l][0], paint('*'));
+            assert_eq!(tree.cells[l][1], paint('*'));
+            assert_eq!(tree.cells[l][2], paint('#'));
+            assert_eq!(tree.cells[l][3], Cell::default());
+            assert_eq!(tree.cells[l][4], Cell::default());
+        }
+    }
+
+    #[test]
+    fn fill_with_the_same_cell_changes_nothing() {
+        let mut tree = Tree::default();
+        tree.cells[2][2] = paint('*');
+        let before = tree.cells.clone();
+
+        assert!(!flood_fill(&mut tree.cells, 2, 2, paint('*')));
+        assert!(!flood_fill(&mut tree.cells, 0, 0, Cell::default()));
+        assert_eq!(tree.cells, before);
+    }
+
+    #[test]
+    fn undo_and_redo_restore_the_cells() {
+        let mut tree = Tree::default();
+        let mut history = History::default();
+
+        history.record(&tree, 1, 2);
+        tree.cells[1][2] = paint('a');
+        history.record(&tree, 3, 4);
+        tree.cells[3][4] = paint('b');
+
+        assert_eq!(history.undo(&mut tree), Some((3, 4)));
+        assert_eq!(tree.cells[3][4], Cell::default());
+        assert_eq!(tree.cells[1][2], paint('a'));
+        assert_eq!(history.undo(&mut tree), Some((1, 2)));
+        assert_eq!(tree.cells, Tree::default().cells);
+        assert_eq!(history.undo(&mut tree), None);
+
+        assert_eq!(history.redo(&mut tree), Some((1, 2)));
+        assert_eq!(tree.cells[1][2], paint('a'));
+
+        // A new change drops the changes that could be redone
+        history.record(&tree, 0, 0);
+        tree.cells[0][0] = paint('c');
+        assert_eq!(history.redo(&mut tree), None);
+    }
+
+    #[test]
+    fn only_the_last_changes_can_be_undone() {
+        let mut tree = Tree::default();
+        let mut history = History::default();
+
+        for i in 0..HISTORY_LEN + 10 {
+            history.record(&tree, 0, 0);
+            tree.cells[0][0] = paint(std::char::from_u32('a' as u32 + (i % 26) as u32).unwrap());
+        }
+
+        let mut undone = 0;
+        while history.undo(&mut tree).is_some() {
+            undone += 1;
+        }
+        assert_eq!(undone, HISTORY_LEN);
+        // The oldest changes were forgotten, so the tree is not blank again
+        assert_ne!(tree.cells, Tree::default().cells);
+
+        let mut redone = 0;
+        while history.redo(&mut tree).is_some() {
+            redone += 1;
+        }
+        assert_eq!(redone, HISTORY_LEN);
+        assert_eq!(history.redo(&mut tree), None);
+    }
+
+    #[test]
+    fn name_scrolls_only_at_the_edges() {
+        // The cursor is visible, so nothing scrolls
+        assert_eq!(scroll_offset(0, 0, 10), 0);
+        assert_eq!(scroll_offset(9, 0, 10), 0);
+        assert_eq!(scroll_offset(12, 5, 10), 5);
+        assert_eq!(scroll_offset(5, 5, 10), 5);
+        // The cursor goes past the right edge
+        assert_eq!(scroll_offset(10, 0, 10), 1);
+        assert_eq!(scroll_offset(15, 5, 10), 6);
+        // The cursor goes past the left edge
+        assert_eq!(scroll_offset(4, 5, 10), 4);
+        assert_eq!(scroll_offset(0, 5, 10), 0);
+    }
+
+    #[test]
+    fn brush_preview_has_the_brush_colors() {
+        let tree = Tree::default();
+        let mut view = view(&tree);
+        view.brush = Cell::new(10, 20, 30, 200, 100, 50, 'A');
+        let display = draw(&view);
+
+        assert_eq!(display.cell(HEIGHT - 3, 10), view.brush);
+        assert_eq!(display.cell(HEIGHT - 3, 11), view.brush);
+        assert!(display.line(HEIGHT - 3).contains("Brush: #c86432 on #0a141e"));
+
+        // A brush without a symbol is previewed with one, so the foreground can be seen
+        view.brush.symbol = ' ';
+        let display = draw(&view);
+        assert_eq!(display.cell(HEIGHT - 3, 10), view.brush.change_symbol(PREVIEW_SYMBOL));
+    }
+
+    #[test]
+    fn symbols_cycle_in_both_directions() {
+        let last = CYCLE_SYMBOLS.len() - 1;
+
+        assert_eq!(cycle_symbol(None, true), 0);
+        assert_eq!(cycle_symbol(None, false), last);
+        assert_eq!(cycle_symbol(Some(0), true), 1);
+        assert_eq!(cycle_symbol(Some(last), true), 0);
+        assert_eq!(cycle_symbol(Some(0), false), last);
+        assert_eq!(cycle_symbol(Some(3), false), 2);
+
+        // Going around once comes back to the same symbol
+        let mut idx = Some(4);
+        for _ in 0..CYCLE_SYMBOLS.len() {
+            idx = Some(cycle_symbol(idx, true));
+        }
+        assert_eq!(idx, Some(4));
+
+        // Every symbol is kept when the tree is saved
+        let mut tree = Tree { name: "glyphs".to_string(), ..Tree::default() };
+        for (idx, symbol) in CYCLE_SYMBOLS.iter().enumerate() {
+            tree.cells[idx / 5][idx % 5].symbol = *symbol;
+        }
+        assert_eq!(Tree::read_tree(tree.to_string()).unwrap().cells, tree.cells);
+    }
+
+    #[test]
+    fn help_is_drawn_over_the_tree() {
+        let mut tree = Tree::default();
+        tree.cells[0][0] = paint('#');
+        let mut view = view(&tree);
+        view.l = 5;
+        let shown = |display: &Display| (1..=HEIGHT).any(|l| display.line(l).contains("Keys of the editor:"));
+
+        let display = draw(&view);
+        assert!(!shown(&display));
+        assert_eq!(display.cell(3, 2), paint('#'));
+
+        view.show_help = true;
+        let display = draw(&view);
+        assert!(shown(&display));
+        assert!(display.line(3).starts_with("  Keys of the editor:"));
+        assert_eq!(display.cell(3, 2), BACKGROUND_GREEN);
+    }
+
+    #[test]
+    fn help_has_every_key() {
+        let help = help_text();
+        for (key, action) in KEY_BINDINGS {
+            assert!(help.contains(&format!("{}: {}", key, action)));
+        }
+    }
+
+    #[test]
+    fn only_a_changed_tree_is_unsaved() {
+        let start = Tree::default();
+        let mut tree = start.clone();
+        assert!(is_unchanged(&tree, &start));
+
+        tree.cells[2][3] = paint('*');
+        assert!(!is_unchanged(&tree, &start));
+
+        // Drawing the same cell again is not a change
+        tree.cells[2][3] = start.cells[2][3];
+        assert!(is_unchanged(&tree, &start));
+
+        tree.name = "oak".to_string();
+        assert!(!is_unchanged(&tree, &start));
+
+        // An edited tree is unsaved only if it differs from the tree it started from
+        let start = tree.clone();
+        assert!(is_unchanged(&tree, &start));
+        tree.name.pop();
+        assert!(!is_unchanged(&tree, &start));
+    }
+
+    #[test]
+    fn ruler_is_drawn_only_when_toggled() {
+        let tree = Tree::default();
+        let mut view = view(&tree);
+
+        let display = draw(&view);
+        assert_eq!(display.cell(2, 2), FOREST_BORDERS);
+        assert_eq!(display.cell(3, 1), FOREST_BORDERS);
+
+        view.show_ruler = true;
+        let display = draw(&view);
+        for i in 0..5 {
+            let digit = std::char::from_digit(i as u32 + 1, 10).unwrap();
+            assert_eq!(display.cell(2, 2 + i).symbol, digit);
+            assert_eq!(display.cell(3 + i, 1).symbol, digit);
+        }
+        // The tree itself doesn't change
+        assert_eq!(display.cell(4, 3), Cell::default());
+    }
+
+    #[test]
+    fn naming_and_drawing_can_be_switched() {
+        assert!(matches!(EditorState::EditTree.toggled(), EditorState::NameTree));
+        assert!(matches!(EditorState::NameTree.toggled(), EditorState::EditTree));
+        assert!(matches!(EditorState::EditTree.toggled().toggled(), EditorState::EditTree));
+
+        // Enter still goes from drawing to naming, and naming finishes the tree
+        assert!(matches!(EditorState::EditTree.advanced(), Some(EditorState::NameTree)));
+        assert!(EditorState::NameTree.advanced().is_none());
+        assert!(EditorState::EditTree.toggled().advanced().is_none());
+    }
+
+    #[test]
+    fn rejected_name_characters_give_a_warning() {
+        let mut name = "ok".to_string();
+        let mut cursor = 1;
+
+        assert_eq!(type_name_char(&mut name, &mut cursor, '-'), Ok(()));
+        assert_eq!((name.as_str(), cursor), ("o-k", 2));
+
+        for x in ['!', '/', ':', '\u{e9}', '\t'] {
+            assert_eq!(type_name_char(&mut name, &mut cursor, x), Err(REJECTED_CHAR.to_string()));
+        }
+        assert_eq!((name.as_str(), cursor), ("o-k", 2));
+    }
+
+    #[test]
+    fn long_name_keeps_the_cursor_in_a_small_box() {
+        let visible = 8;
+        let mut name = String::new();
+        let (mut cursor, mut offset) = (0, 0);
+
+        // Type a long name, then go back to its beginning
+        for x in "a-long-name-for-a-small-box".chars() {
+            type_name_char(&mut name, &mut cursor, x).unwrap();
+            offset = scroll_offset(cursor, offset, visible);
+            assert!(offset <= cursor && cursor < offset + visible);
+        }
+        assert_eq!(&name[offset..], "all-box");
+
+        while cursor > 0 {
+            cursor -= 1;
+            offset = scroll_offset(cursor, offset, visible);
+            assert!(offset <= cursor && cursor < offset + visible);
+        }
+        assert_eq!(offset, 0);
+    }
+
+    #[test]
+    fn name_stops_at_the_limit_in_the_editor() {
+        let mut name = "x".repeat(MAX_NAME_LEN - 1);
+        let mut cursor = name.len();
+
+        // Below the limit, then at it
+        assert_eq!(type_name_char(&mut name, &mut cursor, 'y'), Ok(()));
+        assert_eq!(name.len(), MAX_NAME_LEN);
+
+        // Above it, nothing is typed
+        let err = type_name_char(&mut name, &mut cursor, 'z').unwrap_err();
+        assert!(err.contains(&MAX_NAME_LEN.to_string()));
+        assert_eq!(name.len(), MAX_NAME_LEN);
+        assert_eq!(cursor, MAX_NAME_LEN);
+        assert!(name.ends_with('y'));
+    }
+}
@@ -0,0 +1,109 @@
+//! The errors that end the program. Each kind of error has its own exit code, so the
+//! scripts that run rusty-forest can tell what went wrong: 0 is a success, 1 is an error
+//! while running the command and 2 is a wrong use of the command line.
+
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use serde_json::json;
+
+/// An error that ends the program.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ForestError {
+    /// The command line is wrong, like an unknown option or an invalid argument.
+    Usage(String),
+    /// The command failed, like a file that can't be read or a tree that doesn't exist.
+    Runtime(String),
+}
+
+impl ForestError {
+    /// The exit code of the program for this error.
+    pub fn code(&self) -> i32 {
+        match self {
+        ForestError::Usage(_) => { 2 }
+        ForestError::Runtime(_) => { 1 }
+        }
+    }
+
+    /// The message that describes the error.
+    pub fn message(&self) -> &str {
+        match self {
+        ForestError::Usage(x) | ForestError::Runtime(x) => { x }
+        }
+    }
+}
+
+impl fmt::Display for ForestError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message())
+    }
+}
+
+/// Whether the errors are printed as JSON instead of text.
+static JSON_ERRORS: AtomicBool = AtomicBool::new(false);
+
+/// Print the errors as single-line JSON objects, like `{"error": "...", "code": 1}`, so
+/// they can be read by other programs.
+pub fn set_json_errors(enabled: bool) {
+    JSON_ERRORS.store(enabled, Ordering::Relaxed);
+}
+
+/// Print the error to stderr and end the program with its exit code.
+pub fn fail(err: ForestError) -> ! {
+    if JSON_ERRORS.load(Ordering::Relaxed) {
+        eprintln!("{}", json!({ "error": err.message(), "code": err.code() }));
+    } else {
+        eprintln!("{}", err);
+    }
+    std::process::exit(err.code())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::process::Command;
+
+    /// Set to the kind of error ended by `fail_child`.
+    const CHILD_ENV: &str = "RUSTY_FOREST_FAIL_CHILD";
+
+    /// End the program with an error printed as JSON. This only runs in the child
+    /// processes started by `json_error_is_a_single_object_on_stderr`.
+    #[test]
+    fn fail_child() {
+        let kind = match env::var(CHILD_ENV) {
+        Ok(x) => { x }
+        Err(_) => { return; }
+        };
+
+        set_json_errors(true);
+        if kind == "usage" {
+            fail(ForestError::Usage("Unrecognized option: \"x\"".to_string()));
+        } else {
+            fail(ForestError::Runtime("There is no tree named oak".to_string()));
+        }
+    }
+
+    #[test]
+    fn json_error_is_a_single_object_on_stderr() {
+        for (kind, message, code) in [("usage", "Unrecognized option: \"x\"", 2), ("runtime", "There is no tree named oak", 1)] {
+            let output = Command::new(env::current_exe().unwrap())
+                .args(["--exact", "error::tests::fail_child", "--nocapture", "--test-threads=1"])
+                .env(CHILD_ENV, kind)
+                .output()
+                .unwrap();
+
+            assert_eq!(output.status.code(), Some(code));
+            let stderr = String::from_utf8(output.stderr).unwrap();
+            assert_eq!(stderr.lines().count(), 1);
+            let value: serde_json::Value = serde_json::from_str(&stderr).unwrap();
+            assert_eq!(value, json!({ "error": message, "code": code }));
+        }
+    }
+
+    #[test]
+    fn codes_match_the_kind() {
+        assert_eq!(ForestError::Usage("x".to_string()).code(), 2);
+        assert_eq!(ForestError::Runtime("x".to_string()).code(), 1);
+        assert_eq!(ForestError::Runtime("no tree".to_string()).to_string(), "no tree");
+    }
+}
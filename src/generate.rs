@@ -0,0 +1,218 @@
+//! Generate random trees, for users who don't want to draw their own trees. The trees
+//! have a canopy of leaves on top of a trunk, with a few random variations. The colors
+//! are taken from a theme, so the trees can look like they belong to a season.
+
+use rand::Rng;
+use crate::tree::{Cell, Tree};
+
+/// Possible shapes of the canopy, on the first three lines of the tree.
+const CANOPIES: [[[bool; 5]; 3]; 4] = [
+    [[false, false, true, false, false],
+     [false, true,  true, true,  false],
+     [true,  true,  true, true,  true ]],
+    [[false, true,  true, true,  false],
+     [true,  true,  true, true,  true ],
+     [false, true,  true, true,  false]],
+    [[false, true,  true, true,  false],
+     [true,  true,  true, true,  true ],
+     [true,  true,  true, true,  true ]],
+    [[false, false, true, false, false],
+     [false, true,  true, true,  false],
+     [false, true,  true, true,  false]],
+];
+
+/// Symbols that can be drawn on the leaves, like fruits or flowers.
+const DECORATIONS: [char; 4] = ['o', '*', '.', '@'];
+
+/// A set of colors used to generate trees.
+pub struct Theme {
+    pub name: &'static str,
+    /// Colors of the leaves.
+    pub leaves: &'static [(u8, u8, u8)],
+    /// Colors of the trunk.
+    pub trunk: &'static [(u8, u8, u8)],
+    /// Colors of the symbols drawn on the leaves.
+    pub decorations: &'static [(u8, u8, u8)],
+}
+
+/// All the themes that can be used to generate trees. The first one is the default.
+pub const THEMES: [Theme; 4] = [
+    Theme {
+        name: "summer",
+        leaves: &[(30, 110, 0), (36, 170, 32), (17, 100, 36), (50, 140, 20)],
+        trunk: &[(50, 30, 0), (65, 32, 6), (80, 25, 12)],
+        decorations: &[(255, 0, 0), (230, 60, 40), (250, 200, 30)],
+    },
+    Theme {
+        name: "spring",
+        leaves: &[(90, 190, 70), (120, 210, 90), (70, 170, 60)],
+        trunk: &[(70, 45, 20), (85, 55, 25)],
+        decorations: &[(255, 170, 200), (255, 255, 255), (240, 130, 180)],
+    },
+    Theme {
+        name: "autumn",
+        leaves: &[(200, 90, 20), (180, 40, 20), (220, 150, 30), (150, 60, 10)],
+        trunk: &[(60, 35, 15), (75, 40, 20)],
+        decorations: &[(120, 60, 20), (90, 40, 10)],
+    },
+    Theme {
+        name: "winter",
+        leaves: &[(200, 220, 240), (230, 240, 255), (150, 180, 220)],
+        trunk: &[(60, 50, 45), (75, 65, 60)],
+        decorations: &[(255, 255, 255), (180, 210, 255)],
+    },
+];
+
+/// Find the theme with the given name.
+pub fn find_theme(name: &str) -> Option<&'static Theme> {
+    THEMES.iter().find(|theme| theme.name == name)
+}
+
+/// Pick a random color from the given set, slightly changing its shade.
+fn pick_color<R: Rng>(rng: &mut R, colors: &[(u8, u8, u8)]) -> (u8, u8, u8) {
+    let (r, g, b) = colors[rng.gen_range(0..colors.len())];
+    let shade = |rng: &mut R, x: u8| { (x as i16 + rng.gen_range(-10..=10)).clamp(0, 255) as u8 };
+
+    (shade(rng, r), shade(rng, g), shade(rng, b))
+}
+
+/// Generate a random tree with the given name, using the default theme.
+pub fn generate_tree<R: Rng>(rng: &mut R, name: String) -> Tree {
+    generate_themed_tree(rng, name, &THEMES[0])
+}
+
+/// Generate a random tree with the given name, using the colors of the theme.
+pub fn generate_themed_tree<R: Rng>(rng: &mut R, name: String, theme: &Theme) -> Tree {
+    let mut tree = Tree {
+        name,
+        ..Tree::default()
+    };
+
+    let canopy = CANOPIES[rng.gen_range(0..CANOPIES.len())];
+    let leaves = pick_color(rng, theme.leaves);
+    let trunk = pick_color(rng, theme.trunk);
+
+    let decoration = if rng.gen_bool(0.5) {
+        Some((DECORATIONS[rng.gen_range(0..DECORATIONS.len())], pick_color(rng, theme.decorations)))
+    } else {
+        None
+    };
+
+    for (l, line) in canopy.iter().enumerate() {
+        for (c, &leaf) in line.iter().enumerate() {
+            if leaf {
+                // Slightly change the shade of each leaf
+                let shade = rng.gen_range(0..20);
+                let mut cell = Cell::bg(leaves.0, leaves.1.saturating_sub(shade), leaves.2);
+
+                if let Some((symbol, fg)) = decoration {
+                    if rng.gen_bool(0.2) {
+                        cell.fg = fg;
+                        cell.symbol = symbol;
+                    }
+                }
+
+                tree.cells[l][c] = cell;
+            }
+        }
+    }
+
+    for l in 3..5 {
+        tree.cells[l][2] = Cell::bg(trunk.0, trunk.1, trunk.2);
+    }
+
+    tree
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    #[test]
+    fn generated_trees_round_trip() {
+        let mut rng = StdRng::seed_from_u64(1);
+
+        for idx in 0..100 {
+            let tree = generate_tree(&mut rng, format!("generated-{}", idx));
+            let line = tree.to_string();
+            assert!(Tree::is_legit(&line), "{}", line);
+
+            let read = Tree::import_tree(line).unwrap();
+            assert_eq!(read.name, tree.name);
+            assert!(read.same_appearance(&tree));
+        }
+    }
+
+    #[test]
+    fn generated_trees_have_a_trunk_under_the_canopy() {
+        let mut rng = StdRng::seed_from_u64(2);
+
+        for _ in 0..100 {
+            let tree = generate_tree(&mut rng, "tree".to_string());
+            assert!(tree.cells[3][2] != Cell::default() && tree.cells[4][2] != Cell::default());
+            assert!(tree.cells[4][0] == Cell::default() && tree.cells[4][4] == Cell::default());
+            assert!(tree.cells[1][2] != Cell::default());
+        }
+    }
+
+    #[test]
+    fn same_seed_generates_the_same_trees() {
+        let first = generate_tree(&mut StdRng::seed_from_u64(3), "a".to_string());
+        let second = generate_tree(&mut StdRng::seed_from_u64(3), "a".to_string());
+        assert_eq!(first.to_string(), second.to_string());
+    }
+
+    /// Returns true if the color is one from the set, with its shade changed by at most
+    /// `spread` below and `10` above.
+    fn near(color: (u8, u8, u8), colors: &[(u8, u8, u8)], spread: i16) -> bool {
+        let close = |x: u8, y: u8, below: i16| { (x as i16 - y as i16) >= -below && (x as i16 - y as i16) <= 10 };
+        colors.iter().any(|&(r, g, b)| close(color.0, r, 10) && close(color.1, g, spread) && close(color.2, b, 10))
+    }
+
+    #[test]
+    fn themed_trees_use_the_colors_of_the_theme() {
+        let mut rng = StdRng::seed_from_u64(4);
+
+        for theme in THEMES.iter() {
+            for _ in 0..50 {
+                let tree = generate_themed_tree(&mut rng, "tree".to_string(), theme);
+                assert!(near(tree.cells[4][2].bg, theme.trunk, 10), "{} trunk {:?}", theme.name, tree.cells[4][2].bg);
+
+                for l in 0..3 {
+                    for cell in tree.cells[l].iter().filter(|&&x| x != Cell::default()) {
+                        // Each leaf is also made a little less green
+                        assert!(near(cell.bg, theme.leaves, 30), "{} leaf {:?}", theme.name, cell.bg);
+                        if cell.symbol != ' ' {
+                            assert!(near(cell.fg, theme.decorations, 10), "{} decoration {:?}", theme.name, cell.fg);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn themes_look_like_their_season() {
+        let mut rng = StdRng::seed_from_u64(5);
+        let leaf = |rng: &mut StdRng, name: &str| { generate_themed_tree(rng, "tree".to_string(), find_theme(name).unwrap()).cells[2][2].bg };
+
+        for _ in 0..50 {
+            let (r, g, b) = leaf(&mut rng, "summer");
+            assert!(g > r && g > b);
+            let (r, g, b) = leaf(&mut rng, "spring");
+            assert!(g > r && g > b);
+            let (r, g, b) = leaf(&mut rng, "autumn");
+            assert!(r > g && r > b);
+            let (r, g, b) = leaf(&mut rng, "winter");
+            assert!(b >= 140 && b >= r && b >= g);
+        }
+    }
+
+    #[test]
+    fn unknown_theme_is_not_found() {
+        assert!(find_theme("autumn").is_some());
+        assert!(find_theme("monsoon").is_none());
+    }
+}
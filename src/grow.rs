@@ -1,18 +1,36 @@
 use std::str::FromStr;
 use std::string::ToString;
-use crate::tree::{Tree};
+use crate::tree::{Tree, Cell, GrownTree, data_file};
+use crate::store::StatsStore;
 use crate::display::Display;
 use crate::editor::{BACKGROUND_GREEN, FOREST_BORDERS};
+use crate::status::GrowStatus;
+use crate::state::State;
+use crate::session::Session;
+use crate::error::{ForestError, fail};
+use crate::hooks::{HookEnv, run_hook};
+use crate::notify::{notify, play_sound};
+use crate::log;
 use std::time::{Duration, Instant};
-use std::io::{Read, Write};
-use std::fs::{OpenOptions};
+use std::io::{self, Read, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::cmp;
+use std::path::Path;
+use std::fs;
 use rand::{Rng};
 use termion::terminal_size;
 use termion::async_stdin;
 use termion::event::{Event, Key};
 
-/// Error message when the screen is too small.
-const GROW_SMALL_SCREEN_ERROR: &str = "The screen is too small, so the editor cannot be displayed properly. Make it larger (at least 25x26)";
+/// Error message when the screen is too small. The minimum size is added at the end.
+const GROW_SMALL_SCREEN_ERROR: &str = "The screen is too small, so the editor cannot be displayed properly. Make it larger";
+
+/// Messages displayed when a tree is cancelled, unless other messages are configured.
+pub const CANCEL: [&str; 3] = ["Your tree died. Maybe next time!",
+                               "Giving up already? Your tree deserved better.",
+                               "The tree withered away. Try a shorter session next time."];
 
 /// Positive messages that are displayed each 5 minutes.
 const POSITIVE: [&str; 3] = ["You're doing great, keep it up!", 
@@ -20,6 +38,7 @@ const POSITIVE: [&str; 3] = ["You're doing great, keep it up!",
                              "Why are you reading this? Get back to work!"];
 
 /// The ammount of time used to grow a tree.
+#[derive(Debug)]
 pub struct GrowthTime { // This feels kinda stupid, I should just use minutes
     pub h: u64,         // Also u64 feels really unnecessary, probably an u16 would be better
     pub m: u64,
@@ -30,28 +49,56 @@ impl GrowthTime {
     pub fn to_min(&self) -> u64 {
         return self.h * 60 + self.m;
     }
+
+    /// Parse a time duration saved in a file. Unlike from_str, a duration of zero is
+    /// accepted, since a killed tree may have grown for less than a minute. Older versions
+    /// saved the duration as it was typed, so 60 minutes or more are turned into hours.
+    pub fn from_record(s: &str) -> Result<Self, String> {
+        let (hh, mm) = parse_hh_mm(s)?;
+
+        Ok(GrowthTime {
+            h: hh + mm / 60,
+            m: mm % 60,
+        })
+    }
+}
+
+/// Split a duration written as `hh:mm` into the hours and the minutes, as they were written.
+fn parse_hh_mm(s: &str) -> Result<(u64, u64), String> {
+    let parts: Vec<&str> = s.split(':').collect();
+
+    if parts.len() != 2 {
+        return Err("Failed to parse time: incorrect number of components".to_string());
+    }
+
+    let hh: u64 = match parts[0].parse() { 
+    Ok(x) => { x }  
+    Err(x) => { return Err(format!("Failed to parse time (hh): {}", x)); }
+    };
+    
+    let mm: u64 = match parts[1].parse() { 
+    Ok(x) => { x }  
+    Err(x) => { return Err(format!("Failed to parse time (mm): {}", x)); }
+    };
+
+    Ok((hh, mm))
 }
 
 impl FromStr for GrowthTime {
     type Err = String;
 
-    /// Parse a time duration from a string.
+    /// Parse a time duration from a string. The minutes must be less than 60, and the
+    /// duration can't be zero.
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let parts: Vec<&str> = s.split(':').collect();
+        let (hh, mm) = parse_hh_mm(s)?;
 
-        if parts.len() != 2 {
-            return Err("Failed to parse time: incorrect number of components".to_string());
+        if mm >= 60 {
+            return Err(format!("Failed to parse time (mm): {} minutes should be written as hours", mm));
         }
 
-        let hh: u64 = match parts[0].parse() { 
-        Ok(x) => { x }  
-        Err(x) => { return Err(format!("Failed to parse time (hh): {}", x)); }
-        };
-        
-        let mm: u64 = match parts[1].parse() { 
-        Ok(x) => { x }  
-        Err(x) => { return Err(format!("Failed to parse time (mm): {}", x)); }
-        };
+        if hh == 0 && mm == 0 {
+            return Err("Failed to parse time: the duration can't be zero".to_string());
+        }
 
         Ok(GrowthTime {
             h: hh,
@@ -67,17 +114,369 @@ impl ToString for GrowthTime {
     }
 }
 
+/// Returns true if the character can be used in labels. Labels are stored in the stats
+/// file, so they can only have letters, digits, spaces, `-` and `_`.
+fn is_label_char(chr: char) -> bool {
+    chr.is_ascii_alphanumeric() || chr == '-' || chr == '_' || chr == ' '
+}
+
+/// Returns true if the label can be used for a grown tree.
+pub fn is_valid_label(label: &str) -> bool {
+    !label.is_empty() && label.chars().all(is_label_char)
+}
+
+/// Make a label from the name of a directory, replacing the characters that can't be
+/// used in labels with `-`. Returns None if the directory has no name, like `/`.
+pub fn label_from_dir(dir: &Path) -> Option<String> {
+    let name = dir.file_name()?.to_string_lossy();
+
+    let label: String = name.chars()
+        .map(|x| if is_label_char(x) { x } else { '-' })
+        .collect();
+
+    if is_valid_label(&label) { Some(label) } else { None }
+}
+
+/// Get the last session that wasn't cancelled, whose duration, label and tree are reused
+/// by `grow --resume-last`.
+pub fn last_session(stats: Vec<GrownTree>) -> Option<GrownTree> {
+    stats.into_iter().rfind(|x| !x.killed)
+}
+
+/// How the remaining time is displayed while a tree is growing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeFormat {
+    /// Hours, minutes and seconds, like 01:05:30.
+    Hms,
+    /// Hours and minutes, like 01:05.
+    Hm,
+    /// Only the units that are needed, like 1h5m30s or 45s.
+    Compact,
+}
+
+impl FromStr for TimeFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+        "hms" => { Ok(TimeFormat::Hms) }
+        "hm" => { Ok(TimeFormat::Hm) }
+        "compact" => { Ok(TimeFormat::Compact) }
+        _ => { Err(format!("Unknown time format {}; it should be hms, hm or compact", s)) }
+        }
+    }
+}
+
+/// Format a number of seconds in the given time format.
+pub fn format_remaining(secs: u64, format: TimeFormat) -> String {
+    let (h, m, s) = (secs / 3600, secs / 60 % 60, secs % 60);
+
+    match format {
+    TimeFormat::Hms => { format!("{:02}:{:02}:{:02}", h, m, s) }
+    TimeFormat::Hm => { format!("{:02}:{:02}", h, m) }
+    TimeFormat::Compact => {
+        let mut result = String::new();
+        if h > 0 {
+            result += &format!("{}h", h);
+        }
+        if m > 0 {
+            result += &format!("{}m", m);
+        }
+        if s > 0 || result.is_empty() {
+            result += &format!("{}s", s);
+        }
+        result
+    }
+    }
+}
+
+/// Options that change how a tree is grown.
+pub struct GrowOptions {
+    /// Do not display the growing menu, just print messages to stdout.
+    pub nogui: bool,
+    /// Keep the status file updated while growing, so the session can be queried.
+    pub status: bool,
+    /// Also keep the status file that can be sourced by a shell updated.
+    pub status_env: bool,
+    /// Draw each cell of the tree as a block of scale x scale characters.
+    pub scale: usize,
+    /// How the remaining time is displayed.
+    pub time_format: TimeFormat,
+    /// The command run right before the tree starts growing.
+    pub on_start: Option<String>,
+    /// The command run when the tree finishes growing.
+    pub on_complete: Option<String>,
+    /// Don't grow the tree if the command run before it fails.
+    pub require_hooks: bool,
+    /// Keep the session file updated while growing, so the session can be resumed.
+    pub session: bool,
+    /// How many seconds the tree already grew, when resuming a session.
+    pub grown: u64,
+    /// Only count down, without a tree and without recording anything in the stats.
+    pub timer_only: bool,
+    /// Count up until the user stops, and record the tree with the time it grew.
+    pub stopwatch: bool,
+    /// The minimum duration of a session, in minutes. Only checked for the stopwatch, since
+    /// the other durations are checked before growing.
+    pub min_session: u64,
+    /// The messages from which one is displayed when the tree is cancelled.
+    pub cancel_messages: Vec<String>,
+    /// The messages from which one is displayed each 5 minutes.
+    pub positive_messages: Vec<String>,
+    /// Do not display any message while the tree grows, or when it's cancelled.
+    pub quiet: bool,
+    /// Record the tree as killed in the stats when it's cancelled.
+    pub record_failures: bool,
+    /// Post a desktop notification when the tree finishes growing.
+    pub notify: bool,
+    /// Ring the terminal bell when the tree finishes growing.
+    pub bell: bool,
+    /// The sound file played when the tree finishes growing.
+    pub sound: Option<String>,
+    /// Where the grown tree is recorded.
+    pub stats: Box<dyn StatsStore>,
+}
+
+/// Parse the messages displayed when a tree is cancelled from the configuration, where
+/// they are separated by `|`. An empty value means no messages are displayed.
+pub fn parse_cancel_messages(value: &str) -> Vec<String> {
+    value.split('|')
+        .map(|x| x.trim().to_string())
+        .filter(|x| !x.is_empty())
+        .collect()
+}
+
+/// Get the messages displayed each 5 minutes: the built-in ones, and the ones from
+/// `~/.rusty-forest/messages.txt`, one on each line. A missing file adds nothing.
+pub fn load_positive_messages() -> Vec<String> {
+    let mut messages: Vec<String> = POSITIVE.iter().map(|x| x.to_string()).collect();
+
+    let path = match data_file("messages.txt") {
+    Ok(x) => { x }
+    Err(_) => { return messages; }
+    };
+
+    if let Ok(content) = fs::read_to_string(&path) {
+        let before = messages.len();
+        messages.extend(content.lines()
+            .map(|x| x.trim().to_string())
+            .filter(|x| !x.is_empty()));
+        log::info(&format!("Loaded {} messages from {}", messages.len() - before, path));
+    }
+
+    messages
+}
+
+/// Pick one of the messages, if there are any.
+pub fn pick_message<'a, R: Rng>(rng: &mut R, messages: &'a [String]) -> Option<&'a str> {
+    if messages.is_empty() {
+        return None;
+    }
+
+    Some(&messages[rng.gen_range(0..messages.len())])
+}
+
+/// How often the session file is written while growing.
+const SESSION_SAVE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Set when CTRL+C is pressed in the no-display mode.
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+#[cfg(unix)]
+extern "C" fn on_interrupt(_: libc::c_int) {
+    INTERRUPTED.store(true, Ordering::SeqCst);
+}
+
+/// Catch CTRL+C, so the session can be cleaned up before the program ends.
+#[cfg(unix)]
+fn catch_interrupt() {
+    unsafe {
+        libc::signal(libc::SIGINT, on_interrupt as extern "C" fn(libc::c_int) as libc::sighandler_t);
+    }
+}
+
+#[cfg(not(unix))]
+fn catch_interrupt() {}
+
+/// If CTRL+C was caught while growing, end the program the way CTRL+C would have. This
+/// should be called once everything is cleaned up.
+#[cfg(unix)]
+pub fn resume_interrupt() {
+    if INTERRUPTED.load(Ordering::SeqCst) {
+        unsafe {
+            libc::signal(libc::SIGINT, libc::SIG_DFL);
+            libc::raise(libc::SIGINT);
+        }
+    }
+}
+
+#[cfg(not(unix))]
+pub fn resume_interrupt() {}
+
+/// Read the lines of stdin in another thread, so they can be checked without waiting.
+fn read_lines() -> Receiver<String> {
+    let (sender, receiver) = mpsc::channel();
+
+    thread::spawn(move || {
+        // Stop when stdin is closed or nobody is listening anymore
+        for line in io::stdin().lines() {
+            let line = match line {
+            Ok(x) => { x }
+            Err(_) => { break; }
+            };
+
+            if sender.send(line).is_err() {
+                break;
+            }
+        }
+    });
+
+    receiver
+}
+
+/// Get the duration recorded for a tree grown with the stopwatch. Only the whole minutes
+/// are counted.
+pub fn stopwatch_time(elapsed: Duration) -> GrowthTime {
+    let minutes = elapsed.as_secs() / 60;
+
+    GrowthTime { h: minutes / 60, m: minutes % 60 }
+}
+
+/// Check that a session of the given number of minutes is not shorter than the minimum
+/// duration of a session.
+pub fn check_min_session(minutes: u64, min_session: u64) -> Result<(), String> {
+    if minutes < min_session {
+        return Err(format!("This session is too short. It should be at least {:02}:{:02}", min_session / 60, min_session % 60));
+    }
+    Ok(())
+}
+
+/// Get why a tree grown with the stopwatch for the given time can't be recorded, if it
+/// can't. It needs at least its cost and the minimum duration of a session.
+pub fn stopwatch_rejection(time: &GrowthTime, cost: u64, min_session: u64) -> Option<String> {
+    if time.to_min() < cost {
+        Some(format!("This tree needs at least {:02}:{:02} to grow, so it was not recorded.", cost / 60, cost % 60))
+    } else if let Err(x) = check_min_session(time.to_min(), min_session) {
+        Some(format!("{}, so it was not recorded.", x))
+    } else {
+        None
+    }
+}
+
+/// Get how long the tree has been growing, without the time it was paused.
+fn growing_time(start: Instant, paused_for: Duration, paused_at: Option<Instant>) -> Duration {
+    let paused = paused_for + paused_at.map_or(Duration::ZERO, |x| x.elapsed());
+
+    start.elapsed().saturating_sub(paused)
+}
+
+/// The color of the filled part of the progress bar.
+const PROGRESS_BAR: Cell = Cell {
+    bg: (117, 199, 139),
+    fg: (0, 0, 0),
+    symbol: ' ',
+};
+
+/// The width of the progress bar printed in the no-display mode.
+const PROGRESS_LINE_WIDTH: usize = 30;
+
+/// Get how many of the `width` cells of a progress bar are filled, when `remaining`
+/// seconds of the `total` are left.
+pub fn progress_cells(total: u64, remaining: u64, width: usize) -> usize {
+    if total == 0 {
+        return width;
+    }
+
+    let done = total.saturating_sub(remaining);
+    (done * width as u64 / total) as usize
+}
+
+/// Get the tree as it looks when only `grown` of its 25 cells grew. The cells grow from the
+/// bottom row to the top one, from left to right; the others are empty.
+pub fn partly_grown(tree: &Tree, grown: usize) -> Tree {
+    let mut partial = tree.clone();
+
+    for (row, line) in partial.cells.iter_mut().enumerate() {
+        for (col, cell) in line.iter_mut().enumerate() {
+            if (4 - row) * 5 + col >= grown {
+                *cell = Cell::default();
+            }
+        }
+    }
+
+    partial
+}
+
+/// Format the progress bar printed in the no-display mode, like `[#####-----]  50%`.
+pub fn progress_line(total: u64, remaining: u64) -> String {
+    let filled = progress_cells(total, remaining, PROGRESS_LINE_WIDTH);
+    let percent = progress_cells(total, remaining, 100);
+
+    format!("[{}{}] {:3}%", "#".repeat(filled), "-".repeat(PROGRESS_LINE_WIDTH - filled), percent)
+}
+
+/// Print a line in the no-display mode. If the progress bar is on the current line, the
+/// line is ended first, so the progress bar is not overwritten.
+fn print_line(progress_shown: &mut bool, line: &str) {
+    if *progress_shown {
+        println!();
+        *progress_shown = false;
+    }
+    println!("{}", line);
+}
+
+/// Run the command chosen to run before the tree grows. If it fails, the tree is not grown
+/// when `require` is set, otherwise only a warning is printed.
+fn run_start_hook(command: &str, env: &HookEnv, require: bool) -> Result<(), String> {
+    match run_hook(command, env) {
+    Ok(()) => { Ok(()) }
+    Err(x) if require => { Err(format!("on_start: {}; the tree was not grown", x)) }
+    Err(x) => {
+        log::warn(&format!("on_start: {}", x));
+        Ok(())
+    }
+    }
+}
+
 /// Grow a tree. This implies waiting for the ammount of time requested by the user,
 /// ocasionally send positive messages, and display a fancy menu if nogui is true.
-pub fn grow_tree(chosen_tree: Tree, label: String, time: GrowthTime, nogui: bool) {
-    if nogui {
+pub fn grow_tree(chosen_tree: Tree, label: String, time: GrowthTime, options: GrowOptions) {
+    let nogui = options.nogui;
+
+    if let Some(ref command) = options.on_start {
+        let env = HookEnv {
+            tree: chosen_tree.name.clone(),
+            label: label.clone(),
+            duration: time.to_min(),
+        };
+
+        if let Err(x) = run_start_hook(command, &env, options.require_hooks) {
+            fail(ForestError::Runtime(x));
+        }
+    }
+
+    if nogui && options.stopwatch {
+        println!("Started the stopwatch!");
+        println!("To stop it and grow your tree, press Enter");
+        println!("If you ever want to cancel, you can CTRL+C");
+        println!("To pause, type p and press Enter");
+    } else if nogui && options.timer_only {
+        println!("Started the timer!");
+        println!("If you ever want to cancel, you can CTRL+C");
+        println!("To pause, type p and press Enter");
+    } else if nogui {
         println!("Started growing your tree!");
         println!("If you ever want to cancel, you can CTRL+C");
         println!("But then your tree will die ;(");
+        println!("To pause, type p and press Enter");
     }
 
     let start = Instant::now();
-    let target_duration = Duration::from_secs(time.h * 60 * 60 + time.m * 60);
+    let start_timestamp = chrono::offset::Local::now().timestamp();
+
+    // When resuming, only the time that is left is grown
+    let target_duration = Duration::from_secs(time.h * 60 * 60 + time.m * 60)
+        .saturating_sub(Duration::from_secs(options.grown));
 
     let mut last_positivity = target_duration.as_secs();
     let mut positive_message = String::new();
@@ -87,30 +486,128 @@ pub fn grow_tree(chosen_tree: Tree, label: String, time: GrowthTime, nogui: bool
     let mut gui = if nogui { None } else { Some((Display::new(), async_stdin().bytes())) };
 
     let mut exit_program = false;
+    // Set when the stopwatch is stopped, so the tree can be recorded
+    let mut stopped = false;
+
+    // In the no-display mode, the keys are read only after Enter
+    let nogui_input = if nogui { Some(read_lines()) } else { None };
+
+    if nogui {
+        catch_interrupt();
+    }
+
+    let mut session = Session {
+        duration: time.to_min(),
+        label: label.clone(),
+        start: start_timestamp,
+        grown: options.grown,
+        tree: chosen_tree.clone(),
+    };
+    let mut write_session = options.session;
+    let mut last_session_save: Option<Instant> = None;
+
+    // The time spent in the previous pauses, and the start of the current one
+    let mut paused_for = Duration::ZERO;
+    let mut paused_at: Option<Instant> = None;
+    let mut toggle_pause = false;
+
+    let mut write_status = options.status;
+    let mut last_status: Option<(u64, bool, i64)> = None;
 
-    while start.elapsed() < target_duration && !exit_program {
-        let remaining = (target_duration - start.elapsed()).as_secs();
+    // The progress is measured on the whole session, including the time grown before resuming
+    let total_duration = time.h * 60 * 60 + time.m * 60;
+    // In the no-display mode, the progress bar is printed again each minute on the same line
+    let mut last_progress: Option<u64> = None;
+    let mut progress_shown = false;
 
-        if remaining < last_positivity && remaining >= 3600 && remaining % 3600 == 0 {
+    while (options.stopwatch || growing_time(start, paused_for, paused_at) < target_duration) && !exit_program && !stopped {
+        let elapsed = growing_time(start, paused_for, paused_at).as_secs();
+        let remaining = target_duration.as_secs().saturating_sub(elapsed);
+        let paused = paused_at.is_some();
+        let now = chrono::offset::Local::now().timestamp();
+
+        // The stopwatch shows the time that passed instead of the time left
+        let shown = if options.stopwatch { elapsed } else { remaining };
+
+        // The status is written at least once a second, so it doesn't look stale while paused
+        if write_status && last_status != Some((shown, paused, now)) {
+            last_status = Some((shown, paused, now));
+            let status = GrowStatus {
+                pid: std::process::id(),
+                tree: chosen_tree.name.clone(),
+                label: label.clone(),
+                remaining: shown,
+                paused,
+                stopwatch: options.stopwatch,
+                updated: now,
+            };
+            let res = if options.status_env {
+                status.write().and_then(|_| status.write_env())
+            } else {
+                status.write()
+            };
+            if let Err(x) = res {
+                if nogui {
+                    eprintln!("{}", x);
+                }
+                write_status = false; // don't complain every second
+            }
+        }
+
+        if write_session && last_session_save.is_none_or(|x| x.elapsed() >= SESSION_SAVE_INTERVAL) {
+            last_session_save = Some(Instant::now());
+            session.grown = options.grown + growing_time(start, paused_for, paused_at).as_secs();
+            if let Err(x) = session.save() {
+                if nogui {
+                    eprintln!("{}", x);
+                }
+                write_session = false;
+            }
+        }
+
+        if nogui && !options.stopwatch && last_progress != Some(remaining.div_ceil(60)) {
+            last_progress = Some(remaining.div_ceil(60));
+            print!("\r{}", progress_line(total_duration, remaining));
+            let _ = io::stdout().flush();
+            progress_shown = true;
+        }
+
+        if options.stopwatch || options.quiet {
+            // There is no time left to cheer about, or the user doesn't want to be cheered
+        } else if remaining < last_positivity && remaining >= 3600 && remaining % 3600 == 0 {
             last_positivity = remaining;
             positive_message = format!("Hang in there! You got {}h left!", remaining / 3600);
             if nogui {
-                println!("{}", positive_message);
+                print_line(&mut progress_shown, &format!("[{}] {}", format_remaining(remaining, options.time_format), positive_message));
             }
         } else if remaining < last_positivity && remaining < 3600 && remaining % (10 * 60) == 0 {
             last_positivity = remaining;
             positive_message = format!("You're close! You got {}m left!", remaining / 60);
             if nogui {
-                println!("{}", positive_message);
+                print_line(&mut progress_shown, &format!("[{}] {}", format_remaining(remaining, options.time_format), positive_message));
             }
         } else if remaining < last_positivity && remaining % (5 * 60) == 0 {
             last_positivity = remaining;
-            positive_message = POSITIVE[rng.gen::<usize>() % POSITIVE.len()].to_string();
+            positive_message = pick_message(&mut rng, &options.positive_messages).unwrap_or_default().to_string();
             if nogui {
-                println!("{}", positive_message);
+                print_line(&mut progress_shown, &format!("[{}] {}", format_remaining(remaining, options.time_format), positive_message));
             }
         }
         
+        if INTERRUPTED.load(Ordering::SeqCst) {
+            exit_program = true;
+        }
+
+        if let Some(ref input) = nogui_input {
+            while let Ok(line) = input.try_recv() {
+                if line.trim() == "p" {
+                    toggle_pause = true;
+                } else if options.stopwatch && line.trim().is_empty() {
+                    stopped = true;
+                }
+            }
+        }
+
         if let Some((ref mut gui, ref mut stdin)) = gui {
             let (width, height) = terminal_size().unwrap();
             let (width, height) = (width as usize, height as usize);
@@ -125,6 +622,8 @@ pub fn grow_tree(chosen_tree: Tree, label: String, time: GrowthTime, nogui: bool
                     let e = termion::event::parse_event(k.unwrap(), stdin);
                     match e {
                     Ok(Event::Key(Key::Ctrl('c'))) => { exit_program = true; } 
+                    Ok(Event::Key(Key::Char('p'))) => { toggle_pause = true; }
+                    Ok(Event::Key(Key::Char('\n'))) if options.stopwatch => { stopped = true; }
                     _ => {}
                     }
                 }
@@ -132,10 +631,18 @@ pub fn grow_tree(chosen_tree: Tree, label: String, time: GrowthTime, nogui: bool
                 }
             }
 
-            if width < 25 || height < 26 {
-                gui.fit_string_to_box_hard_wrap(1, 1, width, height, BACKGROUND_GREEN, GROW_SMALL_SCREEN_ERROR);
+            // The box of the tree grows with the scale, and so does the screen
+            let box_size = 5 * options.scale + 2;
+            let (min_width, min_height) = (cmp::max(25, box_size + 4), box_size + 19);
+
+            if width < min_width || height < min_height {
+                let error = format!("{} (at least {}x{})", GROW_SMALL_SCREEN_ERROR, min_width, min_height);
+                gui.fit_string_to_box_hard_wrap(1, 1, width, height, BACKGROUND_GREEN, &error);
             } else {
                 let middle_col = (width + 1) / 2;
+                let box_left = middle_col - (box_size - 1) / 2;
+                let box_right = box_left + box_size - 1;
+                let (box_top, box_bottom) = (6, 6 + box_size - 1);
                 
                 for i in 1..height+1 {
                     gui.draw_pixel(i, 1, FOREST_BORDERS);
@@ -149,53 +656,434 @@ pub fn grow_tree(chosen_tree: Tree, label: String, time: GrowthTime, nogui: bool
                     gui.draw_pixel(1, i, FOREST_BORDERS);
                     gui.draw_pixel(height, i, FOREST_BORDERS);
                     gui.draw_pixel(height - 7, i, FOREST_BORDERS);
-                    gui.draw_pixel(9, i, FOREST_BORDERS);
+                    gui.draw_pixel(box_top + (box_size - 1) / 2, i, FOREST_BORDERS);
                 }
 
-                for i in 0..7 {
-                    gui.draw_pixel(6, middle_col - 3 + i, FOREST_BORDERS);
-                    gui.draw_pixel(6 + i, middle_col - 3, FOREST_BORDERS);
-                    gui.draw_pixel(12, middle_col - 3 + i, FOREST_BORDERS);
-                    gui.draw_pixel(6 + i, middle_col + 3, FOREST_BORDERS);
+                // The progress bar fills the bottom border, between the corners
+                if !options.stopwatch {
+                    for i in 0..progress_cells(total_duration, remaining, width - 2) {
+                        gui.draw_pixel(height, 2 + i, PROGRESS_BAR);
+                    }
                 }
-            
-                for l in 0..5 {
-                    for c in 0..5 {
-                        gui.draw_pixel(7 + l, middle_col - 2 + c, chosen_tree.cells[l][c]);
+
+                if !options.timer_only {
+                    for i in 0..box_size {
+                        gui.draw_pixel(box_top, box_left + i, FOREST_BORDERS);
+                        gui.draw_pixel(box_top + i, box_left, FOREST_BORDERS);
+                        gui.draw_pixel(box_bottom, box_left + i, FOREST_BORDERS);
+                        gui.draw_pixel(box_top + i, box_right, FOREST_BORDERS);
                     }
+
+                    // The tree grows with the progress, but the stopwatch has no end to grow to
+                    let grown_cells = if options.stopwatch { 25 } else { progress_cells(total_duration, remaining, 25) };
+                    partly_grown(&chosen_tree, grown_cells).draw_at(gui, box_top + 1, box_left + 1, options.scale);
                 }
             
                 gui.fit_string_to_box(height - 6, 2, width - 2, 6, BACKGROUND_GREEN, &positive_message);
-                gui.draw_string(3, 3, BACKGROUND_GREEN, "left:");
-                gui.draw_string(4, 3, BACKGROUND_GREEN, format!("{:02}:{:02}:{:02}", remaining / 3600, remaining / 60 % 60, remaining % 60).as_str());
+                if paused {
+                    gui.draw_string(2, 3, BACKGROUND_GREEN, "PAUSED (press p to resume)");
+                }
+                if options.stopwatch {
+                    gui.draw_string(3, 3, BACKGROUND_GREEN, "elapsed:");
+                    gui.draw_string(5, 3, BACKGROUND_GREEN, "Enter to stop");
+                } else {
+                    gui.draw_string(3, 3, BACKGROUND_GREEN, "left:");
+                }
+                gui.draw_string(4, 3, BACKGROUND_GREEN, &format_remaining(shown, options.time_format));
             }
 
             gui.display();
         }
         
+        if toggle_pause {
+            toggle_pause = false;
+            match paused_at {
+            Some(x) => {
+                paused_for += x.elapsed();
+                paused_at = None;
+                if nogui {
+                    if options.stopwatch {
+                        print_line(&mut progress_shown, &format!("Resumed, {} elapsed", format_remaining(shown, options.time_format)));
+                    } else {
+                        print_line(&mut progress_shown, &format!("Resumed, {} left", format_remaining(remaining, options.time_format)));
+                    }
+                }
+            }
+            None => {
+                paused_at = Some(Instant::now());
+                if nogui {
+                    print_line(&mut progress_shown, "Paused; type p and press Enter to resume");
+                }
+            }
+            }
+        }
+
         std::thread::sleep(Duration::from_millis(50));
     }
 
-    if !exit_program { // the user actually waited, so we must register this W
-        let home = std::env::var("HOME");
+    // Give the terminal back before running the hooks, which may print to it
+    drop(gui);
 
-        let home = match home {
-        Ok(x) => { x }
-        Err(x) => { println!("Failed to save data: {}", x); std::process::exit(1); }
+    if progress_shown {
+        if !exit_program {
+            print!("\r{}", progress_line(total_duration, 0));
+        }
+        println!();
+    }
+
+    if options.status {
+        GrowStatus::remove();
+    }
+
+    if options.session {
+        Session::remove();
+    }
+
+    let mut grown = !exit_program;
+
+    // Only a tree can die, so a cancelled timer gets no message and is not recorded
+    if exit_program && !options.timer_only {
+        if let Some(message) = pick_message(&mut rng, &options.cancel_messages) {
+            println!("{}", message);
+        }
+
+        // The stopwatch has no duration to fail, so cancelling it records nothing
+        if options.record_failures && !options.stopwatch {
+            let record = GrownTree {
+                duration: (options.grown + growing_time(start, paused_for, paused_at).as_secs()) / 60,
+                tree: chosen_tree.clone(),
+                label: label.clone(),
+                timestamp: chrono::offset::Local::now().timestamp(),
+                killed: true,
+            };
+
+            if let Err(x) = options.stats.append(&record) {
+                log::warn(&format!("Failed to record the killed tree: {}", x));
+            }
+        }
+    }
+
+    // The stopwatch grows the tree for as long as it ran, if that's enough for the tree
+    let time = if options.stopwatch {
+        let time = stopwatch_time(growing_time(start, paused_for, paused_at));
+        if grown {
+            match stopwatch_rejection(&time, chosen_tree.cost(), options.min_session) {
+            Some(x) => {
+                println!("{}", x);
+                grown = false;
+            }
+            None => { println!("Your tree grew for {:02}:{:02}!", time.h, time.m); }
+            }
+        }
+        time
+    } else {
+        time
+    };
+
+    // A timer is not a tree, so it's not recorded
+    if grown && !options.timer_only { // the user actually waited, so we must register this W
+        // Load the state before recording the tree, in case it has to be computed from it
+        let state = State::load();
+
+        let record = GrownTree {
+            duration: time.to_min(),
+            tree: chosen_tree.clone(),
+            label: label.clone(),
+            timestamp: chrono::offset::Local::now().timestamp(),
+            killed: false,
         };
-        
-        let file = OpenOptions::new()
-            .append(true)
-            .create(true)
-            .open(home + &"/.rusty-forest/stats.conf");
-
-        let mut file_res = match file {
-        Err(x) => { println!("Failed to open stats file: {}", x); std::process::exit(1); }
-        Ok(x)  => { x }
+
+        if let Err(x) = options.stats.append(&record) {
+            fail(ForestError::Runtime(x));
+        }
+
+        // The state is only a summary of the stats file, so failing to update it is not fatal
+        match state {
+        Ok(mut state) => {
+            state.add_session(time.to_min());
+            if let Err(x) = state.save() {
+                eprintln!("{}", x);
+            }
+        }
+        Err(x) => { eprintln!("{}; use stats --recompute to fix it", x); }
+        }
+    }
+
+    // The stopwatch is stopped by the user, who doesn't need to be told
+    let completed = grown && !options.stopwatch;
+
+    if completed && options.notify {
+        let body = if options.timer_only {
+            format!("Your timer of {:02}:{:02} finished.", time.h, time.m)
+        } else {
+            format!("Your {} tree grew for {:02}:{:02} ({}).", chosen_tree.name, time.h, time.m, label)
         };
-        
-        file_res.write_all(format!("{}/{}/{}/{}\n", time.to_string(), label, chrono::offset::Local::now().timestamp(), chosen_tree.to_string()).as_bytes())
-            .expect("Failed to write to file");
+
+        if let Err(x) = notify("rusty-forest", &body) {
+            log::warn(&format!("Failed to post the notification: {}", x));
+        }
+    }
+
+    // The display was dropped above, so the bell reaches the terminal
+    if completed && options.bell {
+        print!("\x07");
+        let _ = io::stdout().flush();
+    }
+
+    if let (true, Some(file)) = (completed, &options.sound) {
+        if let Err(x) = play_sound(file) {
+            log::warn(&format!("Failed to play {}: {}", file, x));
+        }
+    }
+
+    if grown {
+        if let Some(command) = options.on_complete {
+            let env = HookEnv {
+                tree: chosen_tree.name.clone(),
+                label,
+                duration: time.to_min(),
+            };
+
+            if let Err(x) = run_hook(&command, &env) {
+                log::warn(&format!("on_complete: {}", x));
+            }
+        }
     }
 } 
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+    use std::env;
+    use std::process::{Command, Stdio};
+    use crate::testing::{MemoryStats, temp_dir, tree, tree_line};
+    use crate::tree::{TreeCollection, format_header, get_stats_from, set_data_dir};
+
+    #[test]
+    fn remaining_time_is_formatted() {
+        assert_eq!(format_remaining(3930, TimeFormat::Hms), "01:05:30");
+        assert_eq!(format_remaining(0, TimeFormat::Hms), "00:00:00");
+        assert_eq!(format_remaining(3930, TimeFormat::Hm), "01:05");
+        assert_eq!(format_remaining(59, TimeFormat::Hm), "00:00");
+        assert_eq!(format_remaining(3930, TimeFormat::Compact), "1h5m30s");
+        assert_eq!(format_remaining(45, TimeFormat::Compact), "45s");
+        assert_eq!(format_remaining(3600, TimeFormat::Compact), "1h");
+        assert_eq!(format_remaining(0, TimeFormat::Compact), "0s");
+        assert_eq!(format_remaining(100 * 3600, TimeFormat::Hms), "100:00:00");
+    }
+
+    #[test]
+    fn time_format_is_parsed() {
+        assert_eq!("hms".parse::<TimeFormat>(), Ok(TimeFormat::Hms));
+        assert_eq!("hm".parse::<TimeFormat>(), Ok(TimeFormat::Hm));
+        assert_eq!("compact".parse::<TimeFormat>(), Ok(TimeFormat::Compact));
+        assert!("HMS".parse::<TimeFormat>().is_err());
+    }
+
+    #[test]
+    fn stopwatch_counts_whole_minutes() {
+        let time = stopwatch_time(Duration::from_secs(59));
+        assert_eq!((time.h, time.m), (0, 0));
+
+        let time = stopwatch_time(Duration::from_secs(25 * 60 + 59));
+        assert_eq!((time.h, time.m), (0, 25));
+
+        let time = stopwatch_time(Duration::from_secs(2 * 3600 + 5 * 60));
+        assert_eq!((time.h, time.m), (2, 5));
+    }
+
+    #[test]
+    fn sessions_shorter_than_the_minimum_are_rejected() {
+        assert!(check_min_session(25, 0).is_ok());
+        assert!(check_min_session(0, 0).is_ok());
+        assert!(check_min_session(30, 30).is_ok());
+        assert_eq!(check_min_session(29, 30), Err("This session is too short. It should be at least 00:30".to_string()));
+        assert!(check_min_session(60, 90).unwrap_err().ends_with("01:30"));
+    }
+
+    #[test]
+    fn stopwatch_needs_the_cost_and_the_minimum_session() {
+        let time = GrowthTime { h: 0, m: 20 };
+
+        assert_eq!(stopwatch_rejection(&time, 15, 0), None);
+        assert_eq!(stopwatch_rejection(&time, 20, 20), None);
+        assert!(stopwatch_rejection(&time, 21, 0).unwrap().contains("needs at least 00:21"));
+        assert!(stopwatch_rejection(&time, 15, 30).unwrap().contains("too short"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn failed_start_hook_stops_the_session_only_if_required() {
+        let env = HookEnv { tree: "oak".to_string(), label: "work".to_string(), duration: 25 };
+
+        assert_eq!(run_start_hook("true", &env, true), Ok(()));
+        assert_eq!(run_start_hook("false", &env, false), Ok(()));
+        let err = run_start_hook("false", &env, true).unwrap_err();
+        assert!(err.starts_with("on_start: false failed with"));
+        assert!(err.ends_with("the tree was not grown"));
+    }
+
+    #[test]
+    fn label_is_made_from_the_directory_name() {
+        assert_eq!(label_from_dir(Path::new("/home/me/rusty-forest")), Some("rusty-forest".to_string()));
+        assert_eq!(label_from_dir(Path::new("/home/me/my project_2")), Some("my project_2".to_string()));
+        assert_eq!(label_from_dir(Path::new("/home/me/café.rs")), Some("caf--rs".to_string()));
+        assert_eq!(label_from_dir(Path::new("/home/me/a:b|c")), Some("a-b-c".to_string()));
+        assert_eq!(label_from_dir(Path::new("/")), None);
+        assert!(is_valid_label(&label_from_dir(Path::new("/tmp/.config")).unwrap()));
+    }
+
+    #[test]
+    fn last_session_is_read_from_the_history() {
+        let path = format!("{}/stats.conf", temp_dir("grow-resume-last"));
+        let history = format!("#rusty-forest v2\n00:25/reading/1700000000/{}\n01:30/work/1700003600/{}\n00:05/work/1700010000/{}/killed\n",
+                              tree_line("oak"), tree_line("pine"), tree_line("birch"));
+        fs::write(&path, history).unwrap();
+
+        let last = last_session(get_stats_from(&path).unwrap()).unwrap();
+        assert_eq!(last.duration, 90);
+        assert_eq!(last.label, "work");
+        assert_eq!(last.tree.name, "pine");
+
+        assert!(last_session(Vec::new()).is_none());
+    }
+
+    /// Set to the session grown by `grow_child`.
+    const CHILD_ENV: &str = "RUSTY_FOREST_GROW_CHILD";
+
+    fn options(stats: MemoryStats) -> GrowOptions {
+        GrowOptions {
+            nogui: true,
+            status: false,
+            status_env: false,
+            scale: 1,
+            time_format: TimeFormat::Hms,
+            on_start: None,
+            on_complete: None,
+            require_hooks: false,
+            session: false,
+            grown: 0,
+            timer_only: false,
+            stopwatch: false,
+            min_session: 0,
+            cancel_messages: Vec::new(),
+            positive_messages: Vec::new(),
+            quiet: true,
+            record_failures: true,
+            notify: false,
+            bell: false,
+            sound: None,
+            stats: Box::new(stats),
+        }
+    }
+
+    /// Grow a session that ends right away: a timer, or a tree that is not in the
+    /// collection. This only runs in the child processes started by the tests below, since
+    /// growing catches CTRL+C.
+    #[test]
+    fn grow_child() {
+        let session = match env::var(CHILD_ENV) {
+        Ok(x) => { x }
+        Err(_) => { return; }
+        };
+
+        let stats = MemoryStats::default();
+        let mut options = options(stats.clone());
+        let time = GrowthTime { h: 0, m: 0 };
+
+        if session == "timer" {
+            options.timer_only = true;
+            grow_tree(tree("oak"), "work".to_string(), time, options);
+            assert!(stats.stats.borrow().is_empty());
+            return;
+        }
+
+        let dir = temp_dir("grow-given-tree");
+        set_data_dir(dir.clone());
+        let saved = format!("{}\n{}\n", format_header(), tree_line("oak"));
+        fs::write(format!("{}/trees.conf", dir), &saved).unwrap();
+
+        // Like grow --tree-string, the tree is only read
+        let given = Tree::read_tree(tree_line("given")).unwrap();
+        grow_tree(given, "work".to_string(), time, options);
+
+        let recorded = stats.stats.borrow();
+        assert_eq!(recorded.len(), 1);
+        assert_eq!(recorded[0].tree.name, "given");
+        assert_eq!(fs::read_to_string(format!("{}/trees.conf", dir)).unwrap(), saved);
+        let names: Vec<String> = TreeCollection::load().collection.into_iter().map(|x| x.name).collect();
+        assert!(!names.contains(&"given".to_string()));
+    }
+
+    fn run_child(session: &str) -> std::process::Output {
+        Command::new(env::current_exe().unwrap())
+            .args(["--exact", "grow::tests::grow_child", "--nocapture", "--test-threads=1"])
+            .env(CHILD_ENV, session)
+            .stdin(Stdio::null())
+            .output()
+            .unwrap()
+    }
+
+    #[test]
+    fn timer_records_nothing() {
+        let output = run_child("timer");
+
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+        assert!(String::from_utf8_lossy(&output.stdout).contains("Started the timer!"));
+    }
+
+    #[test]
+    fn cancel_messages_are_read_from_the_configuration() {
+        assert_eq!(parse_cancel_messages("Too bad | Try again|"), vec!["Too bad", "Try again"]);
+        assert!(parse_cancel_messages("").is_empty());
+        assert!(parse_cancel_messages(" | ").is_empty());
+    }
+
+    #[test]
+    fn cancel_message_is_one_of_the_messages() {
+        use rand::SeedableRng;
+        let mut rng = rand::rngs::StdRng::seed_from_u64(7);
+        let messages: Vec<String> = CANCEL.iter().map(|x| x.to_string()).collect();
+
+        let mut picked = HashSet::new();
+        for _ in 0..100 {
+            let message = pick_message(&mut rng, &messages).unwrap();
+            assert!(CANCEL.contains(&message));
+            picked.insert(message);
+        }
+        assert_eq!(picked.len(), CANCEL.len());
+
+        // Without messages, nothing is displayed
+        assert_eq!(pick_message(&mut rng, &[]), None);
+    }
+
+    #[test]
+    fn growth_time_is_validated() {
+        let time = GrowthTime::from_str("01:00").unwrap();
+        assert_eq!((time.h, time.m, time.to_min()), (1, 0, 60));
+        assert_eq!(GrowthTime::from_str("00:59").unwrap().to_min(), 59);
+
+        assert!(GrowthTime::from_str("00:60").unwrap_err().contains("should be written as hours"));
+        assert!(GrowthTime::from_str("00:90").is_err());
+        assert!(GrowthTime::from_str("00:00").unwrap_err().contains("can't be zero"));
+        assert!(GrowthTime::from_str("0:0").is_err());
+        assert!(GrowthTime::from_str("25").is_err());
+
+        // A killed tree may have grown for less than a minute
+        assert_eq!(GrowthTime::from_record("00:00").unwrap().to_min(), 0);
+
+        // Older versions saved the duration as it was typed
+        let time = GrowthTime::from_record("0:90").unwrap();
+        assert_eq!((time.h, time.m, time.to_min()), (1, 30, 90));
+    }
+
+    #[test]
+    fn given_tree_is_grown_without_changing_the_collection() {
+        let output = run_child("given");
+
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+        assert!(String::from_utf8_lossy(&output.stdout).contains("Started growing your tree!"));
+    }
+}
@@ -0,0 +1,121 @@
+//! Run the commands chosen by the user when a tree starts or finishes growing, for
+//! instance to mute the notifications or to update a status bar. The commands are run by
+//! the shell, with the details of the session in the environment variables
+//! `RUSTY_FOREST_TREE`, `RUSTY_FOREST_LABEL` and `RUSTY_FOREST_DURATION` (in minutes).
+
+use std::process::{Child, Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How long a command may run before it is killed.
+const HOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The details of a session that are passed to the commands.
+pub struct HookEnv {
+    /// The name of the grown tree.
+    pub tree: String,
+    /// The label of the session.
+    pub label: String,
+    /// The duration of the session, in minutes.
+    pub duration: u64,
+}
+
+/// Build the command that runs the given line in the shell.
+#[cfg(unix)]
+fn shell_command(line: &str) -> Command {
+    let mut command = Command::new("sh");
+    command.arg("-c").arg(line);
+    command
+}
+
+#[cfg(not(unix))]
+fn shell_command(line: &str) -> Command {
+    let mut command = Command::new("cmd");
+    command.arg("/C").arg(line);
+    command
+}
+
+/// Run a command with the details of the session, and wait for it to finish. The output
+/// of the command is discarded, and the command is killed if it runs for too long.
+pub fn run_hook(line: &str, env: &HookEnv) -> Result<(), String> {
+    let child = match shell_command(line)
+        .env("RUSTY_FOREST_TREE", &env.tree)
+        .env("RUSTY_FOREST_LABEL", &env.label)
+        .env("RUSTY_FOREST_DURATION", env.duration.to_string())
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .spawn() {
+    Ok(x) => { x }
+    Err(x) => { return Err(format!("Failed to run {}: {}", line, x)); }
+    };
+
+    wait_with_timeout(child, line)
+}
+
+/// Wait for a command to finish, and kill it if it runs for too long. `line` is how the
+/// command is named in the errors.
+pub fn wait_with_timeout(child: Child, line: &str) -> Result<(), String> {
+    wait_for(child, line, HOOK_TIMEOUT)
+}
+
+/// Wait for a command to finish, and kill it if it runs for longer than `timeout`.
+fn wait_for(mut child: Child, line: &str, timeout: Duration) -> Result<(), String> {
+    let start = Instant::now();
+    loop {
+        match child.try_wait() {
+        Ok(Some(status)) if status.success() => { return Ok(()); }
+        Ok(Some(status)) => { return Err(format!("{} failed with {}", line, status)); }
+        Ok(None) => {}
+        Err(x) => { return Err(format!("Failed to wait for {}: {}", line, x)); }
+        }
+
+        if start.elapsed() >= timeout {
+            // The command may have finished in the meantime, so failing to kill it is fine
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(format!("{} took more than {} seconds and was stopped", line, timeout.as_secs()));
+        }
+
+        thread::sleep(Duration::from_millis(50));
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::fs;
+    use crate::testing::temp_dir;
+
+    fn env() -> HookEnv {
+        HookEnv {
+            tree: "oak".to_string(),
+            label: "work".to_string(),
+            duration: 25,
+        }
+    }
+
+    #[test]
+    fn hook_gets_the_session_details() {
+        let path = format!("{}/env", temp_dir("hooks-env"));
+        let line = format!("echo \"$RUSTY_FOREST_TREE $RUSTY_FOREST_LABEL $RUSTY_FOREST_DURATION\" > {}", path);
+
+        assert_eq!(run_hook(&line, &env()), Ok(()));
+        assert_eq!(fs::read_to_string(&path).unwrap(), "oak work 25\n");
+    }
+
+    #[test]
+    fn failed_hook_is_an_error() {
+        let err = run_hook("exit 3", &env()).unwrap_err();
+        assert!(err.starts_with("exit 3 failed with"));
+    }
+
+    #[test]
+    fn slow_hook_is_stopped() {
+        let child = shell_command("sleep 5").spawn().unwrap();
+        let start = Instant::now();
+        let err = wait_for(child, "sleep 5", Duration::from_millis(200)).unwrap_err();
+
+        assert!(err.contains("was stopped"));
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+}
@@ -0,0 +1,104 @@
+//! Export trees as PNG images, for sharing them outside the terminal. This is only
+//! available with the `image` feature.
+
+use std::fs::File;
+use std::io::BufWriter;
+use crate::tree::Tree;
+
+/// Write the tree as a PNG image. Each cell of the tree becomes a block of pixel_size x
+/// pixel_size pixels with the background color of the cell. If draw_symbols is true, the
+/// cells that have a symbol get a smaller square in the middle, with the foreground color.
+pub fn write_png(tree: &Tree, path: &str, pixel_size: usize, draw_symbols: bool) -> Result<(), String> {
+    let size = 5 * pixel_size;
+    let mut data: Vec<u8> = Vec::with_capacity(size * size * 3);
+
+    // The symbol square takes the middle half of the cell
+    let (symbol_start, symbol_end) = (pixel_size / 4, pixel_size - pixel_size / 4);
+
+    for y in 0..size {
+        for x in 0..size {
+            let cell = tree.cells[y / pixel_size][x / pixel_size];
+            let (in_x, in_y) = (x % pixel_size, y % pixel_size);
+
+            let in_symbol = symbol_start <= in_x && in_x < symbol_end &&
+                            symbol_start <= in_y && in_y < symbol_end;
+
+            let (r, g, b) = if draw_symbols && cell.symbol != ' ' && in_symbol {
+                cell.fg
+            } else {
+                cell.bg
+            };
+
+            data.extend_from_slice(&[r, g, b]);
+        }
+    }
+
+    let file = match File::create(path) {
+    Ok(x) => { x }
+    Err(x) => { return Err(format!("Failed to create {}: {}", path, x)); }
+    };
+
+    let mut encoder = png::Encoder::new(BufWriter::new(file), size as u32, size as u32);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    let res = encoder.write_header()
+        .and_then(|mut writer| writer.write_image_data(&data));
+
+    match res {
+    Ok(_) => { Ok(()) }
+    Err(x) => { Err(format!("Failed to write PNG: {}", x)) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+    use crate::tree::Cell;
+    use crate::testing::temp_dir;
+
+    fn read_png(path: &str) -> (png::OutputInfo, Vec<u8>) {
+        let mut reader = png::Decoder::new(File::open(path).unwrap()).read_info().unwrap();
+        let mut data = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut data).unwrap();
+        data.truncate(info.buffer_size());
+        (info, data)
+    }
+
+    #[test]
+    fn png_has_a_block_for_each_cell() {
+        let mut tree = Tree::default();
+        tree.cells[0][0] = Cell::bg(255, 0, 0);
+        tree.cells[4][4] = Cell::new(0, 0, 255, 255, 255, 255, 'o');
+
+        let path = Path::new(&temp_dir("png")).join("tree.png").to_string_lossy().into_owned();
+        write_png(&tree, &path, 8, true).unwrap();
+
+        let (info, data) = read_png(&path);
+        assert_eq!((info.width, info.height), (40, 40));
+        assert_eq!(info.color_type, png::ColorType::Rgb);
+
+        let pixel = |x: usize, y: usize| { (data[(y * 40 + x) * 3], data[(y * 40 + x) * 3 + 1], data[(y * 40 + x) * 3 + 2]) };
+        assert_eq!(pixel(0, 0), (255, 0, 0));
+        assert_eq!(pixel(7, 7), (255, 0, 0));
+        assert_eq!(pixel(8, 0), (0, 0, 0));
+
+        // The symbol is a square in the middle of its cell
+        assert_eq!(pixel(32, 32), (0, 0, 255));
+        assert_eq!(pixel(36, 36), (255, 255, 255));
+    }
+
+    #[test]
+    fn png_without_symbols_has_only_the_backgrounds() {
+        let mut tree = Tree::default();
+        tree.cells[2][2] = Cell::new(0, 255, 0, 255, 255, 255, '*');
+
+        let path = Path::new(&temp_dir("png-no-symbols")).join("tree.png").to_string_lossy().into_owned();
+        write_png(&tree, &path, 3, false).unwrap();
+
+        let (info, data) = read_png(&path);
+        assert_eq!((info.width, info.height), (15, 15));
+        assert_eq!(&data[(7 * 15 + 7) * 3..(7 * 15 + 8) * 3], &[0, 255, 0]);
+    }
+}
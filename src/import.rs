@@ -0,0 +1,231 @@
+//! Handle the output of the import subcommand. The reports that check a file of trees
+//! without importing anything give each line of the file a tab-separated report line, so
+//! the output can be read by scripts.
+
+use std::io::{self, Write};
+use crate::tree::{Tree, TreeCollection, TreeDiff, check_name_len};
+use crate::list::write_tree;
+
+/// Read a tree from a line of a file, like it would be imported.
+fn read_line(line: &str) -> Result<Tree, String> {
+    let tree = Tree::read_tree(line.to_string())?;
+    check_name_len(&tree.name)?;
+    Ok(tree)
+}
+
+/// Get the lines of the file that may have trees, with their numbers. The header of a
+/// saved collection has no tree.
+fn tree_lines(content: &str) -> impl Iterator<Item = (usize, &str)> {
+    content.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.starts_with('#'))
+        .map(|(idx, line)| (idx + 1, line))
+}
+
+/// Write whether each tree from the content is valid, followed by the number of valid
+/// and invalid trees.
+pub fn write_lint<W: Write>(out: &mut W, content: &str) -> io::Result<()> {
+    let (mut valid, mut invalid) = (0, 0);
+    for (line, tree) in tree_lines(content) {
+        match read_line(tree) {
+        Ok(tree) => {
+            writeln!(out, "{}\tok\t{}", line, tree.name)?;
+            valid += 1;
+        }
+        Err(x) => {
+            writeln!(out, "{}\terror\t{}", line, x)?;
+            invalid += 1;
+        }
+        }
+    }
+
+    writeln!(out, "summary\t{}\t{}", valid, invalid)
+}
+
+/// Write how each tree from the content compares with the collection: new, with the name
+/// of another tree, or looking the same as another tree. The trees are drawn below their
+/// line if preview is set. Nothing is changed.
+pub fn write_diff<W: Write>(out: &mut W, trees: &TreeCollection, content: &str, preview: bool) -> io::Result<()> {
+    let (mut new, mut collisions, mut duplicates, mut invalid) = (0, 0, 0, 0);
+    for (line, tree) in tree_lines(content) {
+        let tree = match read_line(tree) {
+        Ok(tree) => { tree }
+        Err(x) => {
+            writeln!(out, "{}\terror\t{}", line, x)?;
+            invalid += 1;
+            continue;
+        }
+        };
+
+        match trees.diff(&tree) {
+        TreeDiff::New => {
+            writeln!(out, "{}\tnew\t{}", line, tree.name)?;
+            new += 1;
+        }
+        TreeDiff::NameCollision => {
+            writeln!(out, "{}\tcollision\t{}", line, tree.name)?;
+            collisions += 1;
+        }
+        TreeDiff::Duplicate(existing) => {
+            writeln!(out, "{}\tduplicate\t{}\t{}", line, tree.name, existing)?;
+            duplicates += 1;
+        }
+        }
+
+        if preview {
+            write_tree(out, &tree, 1)?;
+        }
+    }
+
+    writeln!(out, "summary\t{}\t{}\t{}\t{}", new, collisions, duplicates, invalid)
+}
+
+/// Add the trees to the collection and write the names of the added ones. The trees that
+/// can't be added are reported to `errors` if `write_errors` is set, never to `out`.
+pub fn write_import<W: Write, E: Write>(out: &mut W, errors: &mut E, trees: &mut TreeCollection,
+                                        content: Vec<String>, duped: bool, write_errors: bool) -> io::Result<()> {
+    let mut tree_name: Vec<String> = Vec::new();
+
+    for tree in content {
+        match trees.add_tree(tree, duped) {
+        Err(x) => {
+            if write_errors {
+                writeln!(errors, "Failed to add tree: {}", x)?;
+            }
+        }
+        Ok(x) => { tree_name.push(x.name); }
+        }
+    }
+
+    writeln!(out, "Loaded {} trees in total:", tree_name.len())?;
+    for new_name in tree_name {
+        writeln!(out, "{}", new_name)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use crate::color::ColorMode;
+    use crate::testing::{color_mode, temp_dir, tree_line, MemoryTrees};
+    use crate::tree::BUILTIN_TREES;
+
+    fn lint(content: &str) -> Vec<String> {
+        let mut out = Vec::new();
+        write_lint(&mut out, content).unwrap();
+        String::from_utf8(out).unwrap().lines().map(|x| x.to_string()).collect()
+    }
+
+    #[test]
+    fn lint_reports_each_line() {
+        let long_name = "x".repeat(100);
+        let content = format!("#rusty-forest v2\n{}\nnot a tree\n{}\n\n{}\n",
+                              tree_line("oak"), tree_line(&long_name), tree_line("pine"));
+        let lines = lint(&content);
+
+        assert_eq!(lines.len(), 6);
+        assert_eq!(lines[0], "2\tok\toak");
+        assert!(lines[1].starts_with("3\terror\t"));
+        assert!(lines[2].starts_with("4\terror\tThe name is too long"));
+        assert!(lines[3].starts_with("5\terror\t"));
+        assert_eq!(lines[4], "6\tok\tpine");
+        assert_eq!(lines[5], "summary\t2\t3");
+    }
+
+    #[test]
+    fn lint_of_empty_file_has_only_the_summary() {
+        assert_eq!(lint(""), vec!["summary\t0\t0"]);
+    }
+
+    #[test]
+    fn bad_tree_is_reported_only_to_the_errors() {
+        let mut trees = TreeCollection::builtin();
+        let count = trees.collection.len();
+        let (mut out, mut errors) = (Vec::new(), Vec::new());
+        write_import(&mut out, &mut errors, &mut trees, vec!["not a tree".to_string()], false, true).unwrap();
+
+        assert_eq!(String::from_utf8(out).unwrap(), "Loaded 0 trees in total:\n");
+        assert!(String::from_utf8(errors).unwrap().starts_with("Failed to add tree: "));
+        assert_eq!(trees.collection.len(), count);
+    }
+
+    #[test]
+    fn imported_trees_are_listed() {
+        let mut trees = TreeCollection::builtin();
+        let (mut out, mut errors) = (Vec::new(), Vec::new());
+        let content = vec![tree_line("oak"), "not a tree".to_string(), tree_line("pine")];
+        write_import(&mut out, &mut errors, &mut trees, content, false, false).unwrap();
+
+        assert_eq!(String::from_utf8(out).unwrap(), "Loaded 2 trees in total:\noak\npine\n");
+        assert!(errors.is_empty());
+    }
+
+    #[test]
+    fn trees_saved_during_an_import_are_kept() {
+        let store = MemoryTrees::default();
+        let mut trees = TreeCollection::load_from(&store);
+
+        // Another process saves a tree after the collection was loaded
+        let mut other = TreeCollection::load_from(&store);
+        other.add_tree(tree_line("oak"), false).unwrap();
+        other.add_tree(tree_line("pine"), false).unwrap();
+        other.save_to(&store).unwrap();
+
+        let (mut out, mut errors) = (Vec::new(), Vec::new());
+        let content = vec![tree_line("birch"), blank_line("pine")];
+        write_import(&mut out, &mut errors, &mut trees, content, false, false).unwrap();
+
+        assert_eq!(trees.merge_from(&store), Ok(1));
+        trees.save_to(&store).unwrap();
+
+        // Both trees are saved, and the imported pine is kept over the other one
+        let saved = store.trees.borrow();
+        let names: Vec<&str> = saved.iter().map(|x| x.name.as_str()).collect();
+        assert_eq!(names, ["birch", "pine", "oak"]);
+        assert_eq!(saved[1].to_string(), blank_line("pine"));
+    }
+
+    /// Get a blank tree with the given name, which looks like none of the built-in trees.
+    fn blank_line(name: &str) -> String {
+        Tree { name: name.to_string(), ..Tree::default() }.to_string()
+    }
+
+    #[test]
+    fn diff_sorts_each_tree_of_a_file() {
+        let path = format!("{}/trees.conf", temp_dir("import-diff"));
+        let fixture = format!("#rusty-forest v2\n{}\n{}\n{}\nnot a tree\n",
+                              blank_line("blank"), blank_line(BUILTIN_TREES[1].0), tree_line("copy"));
+        fs::write(&path, fixture).unwrap();
+
+        let trees = TreeCollection::builtin();
+        let mut out = Vec::new();
+        write_diff(&mut out, &trees, &fs::read_to_string(&path).unwrap(), false).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+
+        assert_eq!(lines.len(), 5);
+        assert_eq!(lines[0], "2\tnew\tblank");
+        assert_eq!(lines[1], format!("3\tcollision\t{}", BUILTIN_TREES[1].0));
+        assert_eq!(lines[2], format!("4\tduplicate\tcopy\t{}", BUILTIN_TREES[0].0));
+        assert!(lines[3].starts_with("5\terror\t"));
+        assert_eq!(lines[4], "summary\t1\t1\t1\t1");
+
+        // Nothing is added to the collection
+        assert_eq!(trees.collection.len(), TreeCollection::builtin().collection.len());
+    }
+
+    #[test]
+    fn diff_preview_draws_each_tree() {
+        let _guard = color_mode(ColorMode::None);
+        let trees = TreeCollection::builtin();
+        let mut out = Vec::new();
+        write_diff(&mut out, &trees, &blank_line("blank"), true).unwrap();
+        let text = String::from_utf8(out).unwrap();
+
+        // The line of the tree, its 5 rows and the summary
+        assert_eq!(text.lines().count(), 7);
+        assert!(text.ends_with("summary\t1\t0\t0\t0\n"));
+    }
+}
@@ -0,0 +1,424 @@
+//! Handle the output of the list subcommand. The trees are written to a generic writer,
+//! so they can be printed directly to stdout or rendered in a buffer first (for instance
+//! when paging them).
+
+use std::io::{self, Write};
+use crate::color;
+use rand::{thread_rng, SeedableRng};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use crate::tree::{Tree, distinct_symbols};
+use crate::render::write_tiles;
+
+/// Options that change how the trees are listed.
+pub struct ListOptions {
+    /// Draw the trees, not only their names.
+    pub draw_trees: bool,
+    /// Write the trees in the exportable format.
+    pub exportable: bool,
+    /// Draw the trees side by side, this many on each row.
+    pub columns: Option<usize>,
+    /// Display the time cost of each tree next to its name.
+    pub show_cost: bool,
+    /// Draw each cell of the trees as a block of scale x scale characters.
+    pub scale: usize,
+    /// List the symbols used by each tree after drawing it.
+    pub legend: bool,
+}
+
+/// Get the line that introduces a tree: its position in the collection, its name and, 
+/// optionally, its cost.
+fn title(idx: usize, tree: &Tree, options: &ListOptions) -> String {
+    if options.show_cost {
+        let cost = tree.cost();
+        format!("{}) {} (cost {:02}:{:02})", idx + 1, tree.name, cost / 60, cost % 60)
+    } else {
+        format!("{}) {}", idx + 1, tree.name)
+    }
+}
+
+/// Get the line that lists the symbols used by a tree.
+fn legend(tree: &Tree) -> String {
+    let symbols: Vec<String> = distinct_symbols(tree).iter().map(|x| x.to_string()).collect();
+
+    if symbols.is_empty() {
+        "symbols: none".to_string()
+    } else {
+        format!("symbols: {}", symbols.join(" "))
+    }
+}
+
+/// Draw a single tree, with each cell as a block of scale x scale characters.
+pub fn write_tree<W: Write>(out: &mut W, tree: &Tree, scale: usize) -> io::Result<()> {
+    for l in 0..5 * scale {
+        for c in 0..5 * scale {
+            tree.write_symbol(out, l / scale, c / scale)?;
+        }
+
+        writeln!(out, "{}", color::Reset)?;
+    }
+
+    Ok(())
+}
+
+/// Write the name of each tree on a line.
+pub fn write_names<W: Write>(out: &mut W, collection: &[Tree]) -> io::Result<()> {
+    for tree in collection {
+        writeln!(out, "{}", tree.name)?;
+    }
+
+    Ok(())
+}
+
+/// Select the indices of the trees displayed by the list subprogram, out of a collection 
+/// of len trees. head keeps only the first trees and tail only the last ones; then, 
+/// random picks that many trees at random from the remaining ones. The random trees are 
+/// picked with the given seed, if there is one.
+pub fn select_indices(len: usize, head: Option<usize>, tail: Option<usize>, random: Option<usize>, seed: Option<u64>) -> Vec<usize> {
+    let head = head.unwrap_or(len);
+    let tail = tail.unwrap_or(len);
+
+    let mut indices: Vec<usize> = (0..len)
+        .filter(|&idx| idx < head && idx >= len.saturating_sub(tail))
+        .collect();
+
+    if let Some(count) = random {
+        match seed {
+        Some(seed) => { indices.shuffle(&mut StdRng::seed_from_u64(seed)); }
+        None => { indices.shuffle(&mut thread_rng()); }
+        }
+        indices.truncate(count);
+    }
+
+    indices
+}
+
+/// Get the indices of the trees from the collection that can be grown in the given 
+/// ammount of minutes. If there is no budget, all the trees are kept.
+pub fn affordable_indices(collection: &[Tree], max_cost: Option<u64>) -> Vec<usize> {
+    (0..collection.len())
+        .filter(|&idx| max_cost.is_none_or(|budget| collection[idx].cost() <= budget))
+        .collect()
+}
+
+/// Write the trees from the collection with the given indices.
+pub fn write_list<W: Write>(out: &mut W, collection: &[Tree], indices: &[usize], options: &ListOptions) -> io::Result<()> {
+    if let (Some(columns), true, false) = (options.columns, options.draw_trees, options.exportable) {
+        for row in indices.chunks(columns) {
+            // Each name is written above its tree, cut if it's wider than the tree
+            let width = 5 * options.scale;
+            let names: Vec<String> = row.iter()
+                .map(|&idx| {
+                    let name: String = title(idx, &collection[idx], options).chars().take(width).collect();
+                    format!("{:<width$}", name, width = width)
+                })
+                .collect();
+            writeln!(out, "{}", names.join(" ").trim_end())?;
+
+            // The last row may be shorter, so it's drawn only as wide as needed
+            let tiles: Vec<Option<&Tree>> = row.iter().map(|&idx| Some(&collection[idx])).collect();
+            write_tiles(out, &[tiles], options.scale)?;
+
+            if options.legend {
+                for &idx in row {
+                    writeln!(out, "{}) {}", idx + 1, legend(&collection[idx]))?;
+                }
+            }
+        }
+        return Ok(());
+    }
+
+    for &idx in indices {
+        let tree = &collection[idx];
+        if options.exportable {
+            writeln!(out, "{}", tree.to_string())?;
+        } else {
+            writeln!(out, "{}", title(idx, tree, options))?;
+            if options.draw_trees {
+                write_tree(out, tree, options.scale)?;
+
+                if options.legend {
+                    writeln!(out, "{}", legend(tree))?;
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::ColorMode;
+    use crate::testing::{color_mode, tree};
+
+    fn options(columns: Option<usize>, scale: usize) -> ListOptions {
+        ListOptions {
+            draw_trees: true,
+            exportable: false,
+            columns,
+            show_cost: false,
+            scale,
+            legend: false,
+        }
+    }
+
+    fn render(collection: &[Tree], options: &ListOptions) -> Vec<String> {
+        let indices: Vec<usize> = (0..collection.len()).collect();
+        let mut out = Vec::new();
+        write_list(&mut out, collection, &indices, options).unwrap();
+
+        // Only the layout is checked, so the colors are removed
+        let text = String::from_utf8(out).unwrap();
+        regex::Regex::new("\x1b\\[[0-9;]*m").unwrap().replace_all(&text, "").lines().map(|x| x.to_string()).collect()
+    }
+
+    #[test]
+    fn columns_draw_the_trees_side_by_side() {
+        let collection = vec![tree("a"), tree("b"), tree("c"), tree("d"), tree("e")];
+        let lines = render(&collection, &options(Some(2), 2));
+
+        // Two full rows and a partial one, each with its names and ten lines of tiles
+        assert_eq!(lines.len(), 3 * 11);
+        assert_eq!(lines[0], "1) a       2) b");
+        assert_eq!(lines[1].chars().count(), 21);
+        assert_eq!(lines[1].chars().nth(10), Some('|'));
+        assert_eq!(lines[11], "3) c       4) d");
+        assert_eq!(lines[22], "5) e");
+        assert_eq!(lines[23].chars().count(), 10);
+    }
+
+    #[test]
+    fn names_are_cut_to_the_width_of_the_trees() {
+        let collection = vec![tree("a long name"), tree("b")];
+        let lines = render(&collection, &options(Some(2), 1));
+
+        assert_eq!(lines[0], "1) a  2) b");
+    }
+
+    #[test]
+    fn cost_is_displayed_next_to_the_name() {
+        let mut red = Tree { name: "red".to_string(), ..Tree::default() };
+        red.cells = vec![vec![crate::tree::Cell::bg(255, 0, 0); 5]; 5];
+        assert_eq!(red.cost(), 75);
+
+        let mut with_cost = options(None, 1);
+        with_cost.draw_trees = false;
+        with_cost.show_cost = true;
+        assert_eq!(title(2, &red, &with_cost), "3) red (cost 01:15)");
+
+        let builtin = tree("oak");
+        let cost = builtin.cost();
+        let mut out = Vec::new();
+        write_list(&mut out, &[builtin], &[0], &with_cost).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), format!("1) oak (cost {:02}:{:02})\n", cost / 60, cost % 60));
+    }
+
+    #[test]
+    fn cost_is_not_displayed_with_no_cost() {
+        let mut without_cost = options(None, 1);
+        without_cost.draw_trees = false;
+
+        let mut out = Vec::new();
+        write_list(&mut out, &[tree("oak")], &[0], &without_cost).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "1) oak\n");
+    }
+
+    /// Get a tree that costs 15 minutes, plus 5 for each twelfth of the cells with a red
+    /// background.
+    fn tree_with_cost(name: &str, red_cells: usize) -> Tree {
+        let mut tree = Tree { name: name.to_string(), ..Tree::default() };
+        for idx in 0..red_cells {
+            tree.cells[idx / 5][idx % 5] = crate::tree::Cell::bg(255, 0, 0);
+        }
+        tree
+    }
+
+    #[test]
+    fn max_cost_keeps_the_affordable_trees() {
+        let collection = vec![tree_with_cost("cheap", 0), tree_with_cost("pricey", 25), tree_with_cost("medium", 5)];
+        assert_eq!(collection.iter().map(|x| x.cost()).collect::<Vec<_>>(), vec![15, 75, 25]);
+
+        assert_eq!(affordable_indices(&collection, None), vec![0, 1, 2]);
+        assert_eq!(affordable_indices(&collection, Some(25)), vec![0, 2]);
+        assert_eq!(affordable_indices(&collection, Some(24)), vec![0]);
+        assert_eq!(affordable_indices(&collection, Some(75)), vec![0, 1, 2]);
+        assert_eq!(affordable_indices(&collection, Some(10)), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn head_and_tail_select_the_ends() {
+        assert_eq!(select_indices(5, None, None, None, None), vec![0, 1, 2, 3, 4]);
+        assert_eq!(select_indices(5, Some(2), None, None, None), vec![0, 1]);
+        assert_eq!(select_indices(5, None, Some(2), None, None), vec![3, 4]);
+        assert_eq!(select_indices(5, Some(4), Some(3), None, None), vec![2, 3]);
+        assert_eq!(select_indices(5, Some(2), Some(2), None, None), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn out_of_range_counts_are_clamped() {
+        assert_eq!(select_indices(5, Some(0), None, None, None), Vec::<usize>::new());
+        assert_eq!(select_indices(5, None, Some(0), None, None), Vec::<usize>::new());
+        assert_eq!(select_indices(5, Some(9), None, None, None), vec![0, 1, 2, 3, 4]);
+        assert_eq!(select_indices(5, None, Some(9), None, None), vec![0, 1, 2, 3, 4]);
+        assert_eq!(select_indices(5, None, None, Some(0), None), Vec::<usize>::new());
+        assert_eq!(select_indices(5, None, None, Some(9), Some(3)).len(), 5);
+        assert_eq!(select_indices(5, Some(usize::MAX), Some(usize::MAX), Some(usize::MAX), None).len(), 5);
+    }
+
+    #[test]
+    fn empty_collection_selects_nothing() {
+        assert_eq!(select_indices(0, None, None, None, None), Vec::<usize>::new());
+        assert_eq!(select_indices(0, Some(3), Some(3), Some(3), Some(1)), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn selection_matches_head_tail_and_random_for_all_small_inputs() {
+        let counts: Vec<Option<usize>> = std::iter::once(None).chain((0..8).map(Some)).collect();
+
+        for len in 0..6 {
+            for &head in &counts {
+                for &tail in &counts {
+                    // The trees kept by both head and tail
+                    let kept: Vec<usize> = (0..len)
+                        .filter(|&x| head.is_none_or(|h| x < h) && tail.is_none_or(|t| x + t >= len))
+                        .collect();
+                    assert_eq!(select_indices(len, head, tail, None, None), kept);
+
+                    for &random in &counts[1..] {
+                        let mut indices = select_indices(len, head, tail, random, Some(7));
+                        assert_eq!(indices.len(), kept.len().min(random.unwrap()));
+
+                        // Each tree is picked at most once, and only out of the kept ones
+                        indices.sort();
+                        indices.dedup();
+                        assert_eq!(indices.len(), kept.len().min(random.unwrap()));
+                        assert!(indices.iter().all(|x| kept.contains(x)));
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn random_picks_that_many_distinct_trees() {
+        let mut indices = select_indices(10, None, None, Some(3), None);
+        assert_eq!(indices.len(), 3);
+
+        indices.sort();
+        indices.dedup();
+        assert_eq!(indices.len(), 3);
+        assert!(indices.iter().all(|&x| x < 10));
+    }
+
+    #[test]
+    fn random_picks_from_the_trees_kept_by_head_and_tail() {
+        for seed in 0..20 {
+            let indices = select_indices(10, Some(5), None, Some(3), Some(seed));
+            assert_eq!(indices.len(), 3);
+            assert!(indices.iter().all(|&x| x < 5));
+
+            let indices = select_indices(10, None, Some(4), Some(3), Some(seed));
+            assert_eq!(indices.len(), 3);
+            assert!(indices.iter().all(|&x| x >= 6));
+        }
+
+        // Picking more trees than head keeps only shuffles them
+        let mut indices = select_indices(10, Some(2), None, Some(3), Some(1));
+        indices.sort();
+        assert_eq!(indices, vec![0, 1]);
+    }
+
+    #[test]
+    fn random_with_a_seed_is_repeatable() {
+        assert_eq!(select_indices(20, None, None, Some(5), Some(42)), select_indices(20, None, None, Some(5), Some(42)));
+    }
+
+    #[test]
+    fn tree_at_scale_two_draws_each_cell_as_a_block() {
+        let _mode = color_mode(ColorMode::None);
+        let mut tree = Tree::default();
+        for l in 0..5 {
+            for c in 0..5 {
+                tree.cells[l][c].symbol = (b'a' + (5 * l + c) as u8) as char;
+            }
+        }
+
+        let mut out = Vec::new();
+        write_tree(&mut out, &tree, 2).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+
+        assert_eq!(lines.len(), 10);
+        assert_eq!(lines[0], "aabbccddee");
+        assert_eq!(lines[1], "aabbccddee");
+        assert_eq!(lines[2], "ffgghhiijj");
+        assert_eq!(lines[9], "uuvvwwxxyy");
+    }
+
+    #[test]
+    fn tiles_at_scale_two_are_twice_as_big() {
+        let _mode = color_mode(ColorMode::None);
+        let tree = Tree { cells: vec![vec![crate::tree::Cell::default().change_symbol('#'); 5]; 5], name: "a".to_string() };
+
+        let mut out = Vec::new();
+        write_tiles(&mut out, &[vec![Some(&tree), None], vec![Some(&tree), Some(&tree)]], 2).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+
+        assert_eq!(lines.len(), 21);
+        assert_eq!(lines[0], "##########|          ");
+        assert_eq!(lines[10], "----------+----------");
+        assert_eq!(lines[11], "##########|##########");
+    }
+
+    #[test]
+    fn legend_lists_the_symbols_after_each_tree() {
+        let mut tree = Tree { name: "decorated".to_string(), ..Tree::default() };
+        tree.cells[0][0].symbol = '@';
+        tree.cells[1][1].symbol = '.';
+        assert_eq!(legend(&tree), "symbols: @ .");
+        assert_eq!(legend(&Tree::default()), "symbols: none");
+
+        let mut with_legend = options(None, 1);
+        with_legend.legend = true;
+        let lines = render(&[tree], &with_legend);
+        assert_eq!(lines.len(), 7);
+        assert_eq!(lines[6], "symbols: @ .");
+    }
+
+    #[test]
+    fn writing_to_a_closed_pipe_fails_with_broken_pipe() {
+        let (reader, mut writer) = io::pipe().unwrap();
+        drop(reader);
+
+        let collection: Vec<Tree> = (0..50).map(|x| tree(&x.to_string())).collect();
+        let indices: Vec<usize> = (0..collection.len()).collect();
+        let err = write_list(&mut writer, &collection, &indices, &options(Some(3), 1)).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::BrokenPipe);
+
+        let err = write_tree(&mut writer, &collection[0], 1).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::BrokenPipe);
+    }
+
+    #[test]
+    fn tree_names_include_the_defaults() {
+        let mut trees = crate::tree::TreeCollection::builtin();
+        trees.add_tree(crate::testing::tree_line("oak"), false).unwrap();
+
+        let mut out = Vec::new();
+        write_names(&mut out, &trees.collection).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "default-1\ndefault-2\ndefault-3\noak\n");
+    }
+
+    #[test]
+    fn without_columns_the_trees_are_stacked() {
+        let collection = vec![tree("a"), tree("b")];
+        let lines = render(&collection, &options(None, 1));
+
+        assert_eq!(lines.len(), 12);
+        assert_eq!(lines[0], "1) a");
+        assert_eq!(lines[6], "2) b");
+    }
+}
@@ -0,0 +1,322 @@
+//! Advisory locks used to keep sessions from racing on the saved data. A lock is a file
+//! in `~/.rusty-forest` that holds the pid of the process that took it. The file is
+//! removed when the lock is dropped. Locks left behind by processes that are not running
+//! anymore are considered stale and are cleaned up.
+//!
+//! The lock file is written under a temporary name and then linked in place, so it never
+//! replaces another lock and it's never seen without its pid. A stale lock is removed only
+//! while holding the cleanup lock, a file next to it locked with `flock`, so two processes
+//! can't both decide that it's stale and remove the lock that one of them took since.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::ErrorKind;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+use crate::tree::{check_directories, data_file};
+use crate::status::process_alive;
+
+/// Name of the lock taken while growing a tree.
+pub const GROW_LOCK: &str = "grow.lock";
+
+/// Name of the lock taken while the tree collection is changed.
+pub const TREES_LOCK: &str = "trees.lock";
+
+/// How long to wait for the lock of the tree collection.
+pub const TREES_LOCK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How often a held lock is tried again, while waiting for it.
+const RETRY_INTERVAL: Duration = Duration::from_millis(100);
+
+/// How long a lock without a pid is considered held. Such a lock was not written by
+/// rusty-forest, or its process stopped right after creating it.
+const UNREADABLE_GRACE: Duration = Duration::from_secs(10);
+
+/// Counts the temporary files, so the threads of a process don't share one.
+static TMP_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// The reason a lock couldn't be taken.
+#[derive(Debug)]
+pub enum LockError {
+    /// The lock is held by the running process with the given pid.
+    Held(u32),
+    /// The lock file has no pid yet, so it's held by a process that is taking it.
+    Busy,
+    /// The lock file couldn't be read or written.
+    Io(String),
+}
+
+/// Who holds a lock, found from the lock file.
+#[derive(Debug, PartialEq, Eq)]
+enum Holder {
+    /// There is no lock file.
+    Nobody,
+    /// The running process with the given pid.
+    Alive(u32),
+    /// A file without a pid, written recently.
+    Unknown,
+    /// A process that is not running anymore, or a file without a pid written long ago.
+    Stale,
+}
+
+/// A taken lock. The lock is released when this is dropped.
+#[derive(Debug)]
+pub struct LockFile {
+    path: String,
+}
+
+impl LockFile {
+    /// Try to take the lock with the given name. Stale locks are removed.
+    pub fn acquire(name: &str) -> Result<LockFile, LockError> {
+        match data_file(name) {
+        Ok(x) => { LockFile::acquire_path(&x) }
+        Err(x) => { Err(LockError::Io(x)) }
+        }
+    }
+
+    /// Try to take the lock at the given path. Stale locks are removed.
+    pub fn acquire_path(path: &str) -> Result<LockFile, LockError> {
+        let tmp_path = format!("{}.{}-{}.tmp", path, std::process::id(), TMP_COUNTER.fetch_add(1, Ordering::SeqCst));
+        if let Err(x) = fs::write(&tmp_path, std::process::id().to_string()) {
+            return Err(LockError::Io(format!("Failed to write lock: {}", x)));
+        }
+
+        let res = LockFile::link(&tmp_path, path);
+        let _ = fs::remove_file(&tmp_path);
+        res
+    }
+
+    /// Link the written lock in place, unless there is another lock that is not stale.
+    fn link(tmp_path: &str, path: &str) -> Result<LockFile, LockError> {
+        // The second attempt is done after removing a stale lock
+        for _ in 0..2 {
+            match fs::hard_link(tmp_path, path) {
+            Ok(_) => { return Ok(LockFile { path: path.to_string() }); }
+            Err(x) if x.kind() == ErrorKind::AlreadyExists => { remove_stale(path)?; }
+            Err(x) => { return Err(LockError::Io(format!("Failed to create lock: {}", x))); }
+            }
+        }
+
+        Err(LockError::Io("Failed to remove stale lock".to_string()))
+    }
+
+    /// Take the lock with the given name, waiting for the process that holds it to release
+    /// it. If it's still held after the timeout, the error says which process holds it.
+    pub fn acquire_timeout(name: &str, timeout: Duration) -> Result<LockFile, LockError> {
+        if let Err(x) = check_directories() {
+            return Err(LockError::Io(x));
+        }
+
+        match data_file(name) {
+        Ok(x) => { LockFile::acquire_path_timeout(&x, timeout) }
+        Err(x) => { Err(LockError::Io(x)) }
+        }
+    }
+
+    /// Take the lock at the given path, waiting for the process that holds it.
+    pub fn acquire_path_timeout(path: &str, timeout: Duration) -> Result<LockFile, LockError> {
+        let start = Instant::now();
+        loop {
+            match LockFile::acquire_path(path) {
+            Err(LockError::Held(_)) | Err(LockError::Busy) if start.elapsed() < timeout => { thread::sleep(RETRY_INTERVAL); }
+            res => { return res; }
+            }
+        }
+    }
+
+    /// Find who holds the lock at the given path.
+    fn holder(path: &str) -> Holder {
+        let content = match fs::read_to_string(path) {
+        Ok(x) => { x }
+        Err(x) if x.kind() == ErrorKind::NotFound => { return Holder::Nobody; }
+        Err(_) => { String::new() }
+        };
+
+        match content.trim().parse() {
+        Ok(pid) if process_alive(pid) => { Holder::Alive(pid) }
+        Ok(_) => { Holder::Stale }
+        Err(_) => {
+            let age = fs::metadata(path)
+                .and_then(|x| x.modified())
+                .ok()
+                .and_then(|x| SystemTime::now().duration_since(x).ok());
+            match age {
+            Some(x) if x >= UNREADABLE_GRACE => { Holder::Stale }
+            _ => { Holder::Unknown }
+            }
+        }
+        }
+    }
+}
+
+/// Remove the lock at the given path if it's stale. Fails if it's held.
+fn remove_stale(path: &str) -> Result<(), LockError> {
+    let _guard = cleanup_guard(path)?;
+
+    // Checked while holding the guard, since another process may have replaced the lock
+    match LockFile::holder(path) {
+    Holder::Nobody => { Ok(()) }
+    Holder::Alive(pid) => { Err(LockError::Held(pid)) }
+    Holder::Unknown => { Err(LockError::Busy) }
+    Holder::Stale => {
+        match fs::remove_file(path) {
+        Ok(_) => { Ok(()) }
+        Err(x) if x.kind() == ErrorKind::NotFound => { Ok(()) }
+        Err(x) => { Err(LockError::Io(format!("Failed to remove stale lock: {}", x))) }
+        }
+    }
+    }
+}
+
+/// Take the cleanup lock of the lock at the given path, waiting for it. It's released when
+/// the returned file is closed.
+#[cfg(unix)]
+fn cleanup_guard(path: &str) -> Result<File, LockError> {
+    use std::os::unix::io::AsRawFd;
+
+    let file = match OpenOptions::new().write(true).create(true).truncate(false).open(path.to_string() + ".cleanup") {
+    Ok(x) => { x }
+    Err(x) => { return Err(LockError::Io(format!("Failed to create cleanup lock: {}", x))); }
+    };
+
+    if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX) } != 0 {
+        return Err(LockError::Io(format!("Failed to take cleanup lock: {}", std::io::Error::last_os_error())));
+    }
+
+    Ok(file)
+}
+
+#[cfg(not(unix))]
+fn cleanup_guard(_path: &str) -> Result<(), LockError> {
+    Ok(())
+}
+
+/// Returns true if the lock with the given name was left by a process that is not running
+/// anymore. The lock is not removed.
+pub fn is_stale(name: &str) -> bool {
+    match data_file(name) {
+    Ok(path) if Path::new(&path).exists() => { LockFile::holder(&path) == Holder::Stale }
+    _ => { false }
+    }
+}
+
+impl Drop for LockFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Barrier};
+    use std::sync::atomic::AtomicBool;
+    use crate::testing::temp_dir;
+
+    fn lock_path(test: &str) -> String {
+        Path::new(&temp_dir(test)).join("test.lock").to_string_lossy().into_owned()
+    }
+
+    /// Get the pid of a process that is not running anymore.
+    fn dead_pid() -> u32 {
+        let mut child = std::process::Command::new("true").spawn().unwrap();
+        child.wait().unwrap();
+        child.id()
+    }
+
+    #[test]
+    fn held_lock_is_not_taken_again() {
+        let path = lock_path("lock-held");
+
+        let lock = LockFile::acquire_path(&path).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), std::process::id().to_string());
+        assert!(matches!(LockFile::acquire_path(&path), Err(LockError::Held(pid)) if pid == std::process::id()));
+
+        drop(lock);
+        assert!(!Path::new(&path).exists());
+        assert!(LockFile::acquire_path(&path).is_ok());
+    }
+
+    #[test]
+    fn stale_lock_is_replaced() {
+        let path = lock_path("lock-stale");
+        fs::write(&path, dead_pid().to_string()).unwrap();
+        assert_eq!(LockFile::holder(&path), Holder::Stale);
+
+        let lock = LockFile::acquire_path(&path).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), std::process::id().to_string());
+        assert_eq!(LockFile::holder(&path), Holder::Alive(std::process::id()));
+
+        drop(lock);
+        assert_eq!(LockFile::holder(&path), Holder::Nobody);
+    }
+
+    #[test]
+    fn lock_without_pid_is_busy_until_the_grace_period() {
+        let path = lock_path("lock-busy");
+        let file = File::create(&path).unwrap();
+
+        assert!(matches!(LockFile::acquire_path(&path), Err(LockError::Busy)));
+
+        file.set_modified(SystemTime::now() - UNREADABLE_GRACE - Duration::from_secs(1)).unwrap();
+        assert!(LockFile::acquire_path(&path).is_ok());
+    }
+
+    #[test]
+    fn waiting_gives_up_after_the_timeout() {
+        let path = lock_path("lock-timeout");
+        let _lock = LockFile::acquire_path(&path).unwrap();
+
+        let start = Instant::now();
+        assert!(matches!(LockFile::acquire_path_timeout(&path, Duration::from_millis(300)), Err(LockError::Held(_))));
+        assert!(start.elapsed() >= Duration::from_millis(300));
+    }
+
+    #[test]
+    fn contended_lock_is_held_by_one_thread_at_a_time() {
+        let path = Arc::new(lock_path("lock-contended"));
+        let inside = Arc::new(AtomicBool::new(false));
+        let entered = Arc::new(AtomicUsize::new(0));
+
+        let threads: Vec<_> = (0..8).map(|_| {
+            let (path, inside, entered) = (path.clone(), inside.clone(), entered.clone());
+            thread::spawn(move || {
+                for _ in 0..5 {
+                    let lock = LockFile::acquire_path_timeout(&path, Duration::from_secs(30)).unwrap();
+                    assert!(!inside.swap(true, Ordering::SeqCst), "two threads hold the lock");
+                    thread::sleep(Duration::from_millis(2));
+                    inside.store(false, Ordering::SeqCst);
+                    entered.fetch_add(1, Ordering::SeqCst);
+                    drop(lock);
+                }
+            })
+        }).collect();
+
+        for x in threads {
+            x.join().unwrap();
+        }
+        assert_eq!(entered.load(Ordering::SeqCst), 40);
+    }
+
+    #[test]
+    fn stale_lock_is_removed_by_only_one_thread() {
+        let path = Arc::new(lock_path("lock-stale-race"));
+        fs::write(&*path, dead_pid().to_string()).unwrap();
+        let barrier = Arc::new(Barrier::new(8));
+
+        let threads: Vec<_> = (0..8).map(|_| {
+            let (path, barrier) = (path.clone(), barrier.clone());
+            thread::spawn(move || {
+                barrier.wait();
+                let lock = LockFile::acquire_path(&path);
+                // The threads that got the lock keep it until all of them tried
+                barrier.wait();
+                lock.is_ok()
+            })
+        }).collect();
+
+        let taken = threads.into_iter().map(|x| x.join().unwrap()).filter(|x| *x).count();
+        assert_eq!(taken, 1);
+    }
+}
@@ -0,0 +1,102 @@
+//! Print diagnostic messages, like the lines that can't be loaded from the saved files.
+//! They are written to stderr, so they don't mix with the output of the commands, and
+//! only if the verbosity chosen by the user allows it.
+
+use std::sync::atomic::{AtomicU8, Ordering};
+
+/// How many diagnostic messages are printed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    /// Don't print any diagnostic message.
+    Quiet,
+    /// Print only the warnings.
+    Normal,
+    /// Print the warnings and details about what the program does.
+    Verbose,
+}
+
+/// The verbosity used by the whole program, as the index of the variant.
+static LEVEL: AtomicU8 = AtomicU8::new(1);
+
+/// Set the verbosity used by the whole program.
+pub fn set_level(level: Level) {
+    let idx = match level {
+    Level::Quiet => { 0 }
+    Level::Normal => { 1 }
+    Level::Verbose => { 2 }
+    };
+
+    LEVEL.store(idx, Ordering::Relaxed);
+}
+
+/// Get the verbosity used by the whole program.
+pub fn level() -> Level {
+    match LEVEL.load(Ordering::Relaxed) {
+    0 => { Level::Quiet }
+    2 => { Level::Verbose }
+    _ => { Level::Normal }
+    }
+}
+
+/// Print a warning, unless the program is quiet.
+pub fn warn(message: &str) {
+    if level() >= Level::Normal {
+        eprintln!("{}", message);
+    }
+}
+
+/// Print a detail about what the program does, only if the program is verbose.
+pub fn info(message: &str) {
+    if level() >= Level::Verbose {
+        eprintln!("{}", message);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+    use std::process::Command;
+    use crate::testing::{temp_dir, tree_line};
+    use crate::tree::read_trees;
+
+    /// Set to the file loaded by `load_child`.
+    const CHILD_ENV: &str = "RUSTY_FOREST_LOG_CHILD";
+
+    /// Load the trees from the file in CHILD_ENV while the program is quiet. This only runs
+    /// in the child process started by `quiet_mode_hides_the_load_warnings`.
+    #[test]
+    fn load_child() {
+        let path = match env::var(CHILD_ENV) {
+        Ok(x) => { x }
+        Err(_) => { return; }
+        };
+
+        set_level(Level::Quiet);
+        assert_eq!(read_trees(&path).len(), 1);
+    }
+
+    #[test]
+    fn quiet_mode_hides_the_load_warnings() {
+        let dir = temp_dir("log-quiet");
+        let path = format!("{}/trees.conf", dir);
+        fs::write(&path, format!("not a tree\n{}\n", tree_line("kept"))).unwrap();
+
+        let output = Command::new(env::current_exe().unwrap())
+            .args(["--exact", "log::tests::load_child", "--nocapture", "--test-threads=1"])
+            .env(CHILD_ENV, &path)
+            .output()
+            .unwrap();
+
+        assert!(output.status.success());
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(!stderr.contains("Failed to load tree"));
+    }
+
+    #[test]
+    fn levels_are_kept_in_order() {
+        assert!(Level::Quiet < Level::Normal);
+        assert!(Level::Normal < Level::Verbose);
+    }
+}
@@ -17,6 +17,111 @@
 //!
 //! > rusty-forest --help
 //!
+//! ## Data directory
+//!
+//! Your trees, stats and configuration are saved in `$XDG_DATA_HOME/rusty-forest`, or in
+//! `~/.local/share/rusty-forest` if `XDG_DATA_HOME` is not set. If `~/.rusty-forest` already
+//! exists, because it was made by an older version, it's still used instead. On Windows,
+//! the data is saved in `%APPDATA%\rusty-forest`. This document calls the data directory
+//! `~/.rusty-forest`; `rusty-forest paths` prints where it is.
+//!
+//! `trees.conf` and `stats.conf` start with the line `#rusty-forest v2`, the version of their
+//! format. The files saved by older versions have no such line; they are still read as they
+//! are. Reading a file never changes it: the line is added above all the lines of
+//! `stats.conf` the next time a grown tree is recorded, and `trees.conf` is written in the
+//! current version the next time the tree collection is saved.
+//!
+//! ## Global options
+//!
+//! These options are given before the subcommand, for instance
+//! `rusty-forest --color-depth 256 list`.
+//!
+//! * --color-depth DEPTH
+//!   * The colors used to draw the trees: truecolor, 256, 16 or none. With 256 or 16, each color
+//!     is replaced by the nearest color of the palette. Use this if your terminal doesn't
+//!     display the trees properly. By default, the depth is detected from the environment:
+//!     truecolor if `COLORTERM` is `truecolor` or `24bit`, 256 if `TERM` ends with
+//!     `-256color`, and 16 otherwise.
+//! * --ascii-art
+//!   * Draw the trees without any color, using only their symbols. This is the same as
+//!     `--color-depth none`, and it's useful for logs, emails or monochrome terminals.
+//! * --fill CHAR
+//!   * The character drawn in the empty cells of the trees when there are no colors.
+//!     The default is a space.
+//! * --dir DIR
+//!   * Keep the saved data in DIR instead of the data directory. This applies to every file
+//!     mentioned in this document.
+//! * --no-onboarding
+//!   * Do not display the welcome message when rusty-forest runs for the first time (when
+//!     the data directory doesn't exist yet). The message is written to stderr, so it
+//!     doesn't mix with the output of the subcommand.
+//! * -V, --verbose
+//!   * Print details about what rusty-forest does, like the files it reads, to stderr.
+//! * --quiet
+//!   * Do not print any warning, like the lines of the saved files that can't be loaded.
+//!     The warnings are printed to stderr.
+//! * --no-lock
+//!   * `import`, `erase` and `edit` take a lock in `~/.rusty-forest/trees.lock` while they
+//!     change the tree collection, so two of them running at the same time don't overwrite
+//!     each other's trees. Each one waits up to 10 seconds for the other to finish. With
+//!     this option, the collection is changed without taking the lock.
+//! * --json-errors
+//!   * Print the errors to stderr as single-line JSON objects, like
+//!     `{"code":1,"error":"Failed to find chosen tree!"}`, so they can be read by other
+//!     programs. The exit code is the same.
+//!
+//! ## Configuration
+//!
+//! Some defaults can be changed in `~/.rusty-forest/config.conf`. Each line of the file
+//! has a setting in `key=value` format; lines starting with `#` are ignored. The command
+//! line options take precedence over the configuration file.
+//!
+//! * cancel_messages
+//!   * The messages displayed when you cancel a growing tree with CTRL+C, separated by
+//!     `|`; one of them is picked at random. Leave it empty to display no message. By
+//!     default, a few built-in messages are used.
+//! * record_failures
+//!   * Whether a cancelled tree is recorded in the stats as killed, with the minutes it grew,
+//!     so `stats --kills` can show how often you gave up. It's true or false; the default
+//!     is false. A cancelled stopwatch is never recorded.
+//! * notify
+//!   * Whether a desktop notification is posted when a tree finishes growing; see
+//!     `grow --notify`. It's true or false; the default is false.
+//! * color_depth
+//!   * The default for `--color-depth`.
+//! * stats_backend
+//!   * Where the grown trees are kept: `file`, the default, keeps them in `stats.conf`, and
+//!     `sqlite` keeps them in the SQLite database `stats.db`, which is faster to search when
+//!     there are many of them. The first time `stats.db` is used, the grown trees from
+//!     `stats.conf` are copied into it; `stats.conf` is not changed after that. `sqlite` is
+//!     available only if rusty-forest was installed with the `sqlite` feature
+//!     (`cargo install rusty-forest --features sqlite`). `stats.db` is not included in the
+//!     backups, and `doctor` doesn't check it.
+//! * min_session_minutes
+//!   * The minimum duration of a session, in minutes. `grow` refuses to grow trees for
+//!     less time than this, and a stopwatch stopped earlier is not recorded. The default
+//!     is 0, meaning there is no minimum.
+//! * daily_goal_minutes, weekly_goal_minutes, monthly_goal_minutes
+//!   * The number of minutes you want to grow each day, week or month. The progress is
+//!     displayed by `stats --goals`. The goals that are not set are not displayed.
+//! * day_start_hour
+//!   * The hour (0-23) when a new day starts. Trees grown after midnight but before this
+//!     hour count for the previous day in `stats --time today`, `stats --time yesterday`,
+//!     the daily goal and the daily graph. The default is 0.
+//! * on_complete
+//!   * A command run when a tree finishes growing; see `grow --on-complete`. By default,
+//!     no command is run.
+//! * on_start
+//!   * A command run right before a tree starts growing; see `grow --on-start`. By default,
+//!     no command is run.
+//!
+//! ## Exit codes
+//!
+//! rusty-forest exits with 0 when the command succeeds, 1 when the command fails (for
+//! instance, a file can't be read or a tree doesn't exist) and 2 when the command line is
+//! wrong (an unknown option, an invalid argument or a missing argument). The error
+//! messages are printed to stderr.
+//!
 //! ## Subcommands
 //!
 //! ### grow
@@ -26,47 +131,177 @@
 //! You can set a custom duration for the tree. By doing so, you can plant more colorful 
 //! trees.
 //!
+//! While the tree is growing, you can press p to pause it and press p again to resume.
+//! The paused time is not counted. With `--no-display`, type p and press Enter instead.
+//! The tree grows with the session: its cells appear from the bottom row to the top one, so
+//! the whole tree is there when the time is up. The bottom border fills up too, like a
+//! progress bar. With `--no-display`, a progress bar is printed on its own line instead,
+//! and it's updated each minute.
+//!
+//! The session is saved in `~/.rusty-forest/session.conf` every few seconds, so it is not
+//! lost if the terminal is closed or the machine stops. The next time you grow a tree,
+//! you are asked whether to resume the unfinished session. The file is removed when the
+//! tree is grown or when you cancel it with CTRL+C.
+//!
+//! Every 5 minutes, an encouragement message is displayed. You can add your own messages to
+//! the built-in ones in `~/.rusty-forest/messages.txt`, one on each line; one of them is
+//! picked at random. Empty lines are ignored.
+//!
 //! Options:
 //!
 //! * -d, --duration TIME
 //!   * set a custom duration for the tree in HH:MM format. For instance, having
 //!     "-d 01:20" means that the tree will take 1 hour and 20 minutes to grow.
-//!     The default duration is 20 minutes.
+//!     The default duration is 20 minutes. The minutes must be less than 60, and the
+//!     duration can't be 00:00.
 //! * -l, --label LABEL
 //!   * set a custom label for the growing tree. This is useful for instance
 //!     if you want to monitor how much time you're spending on each activity.
 //!     For example, if you have "-l coding", that means that this tree is dedicated 
 //!     for coding. This is useful for stats. The default label is "standard".
+//! * --label-from-dir
+//!   * use the name of the current directory as the label, for instance "rusty-forest"
+//!     when growing in `~/code/rusty-forest`. The characters that can't be used in labels
+//!     are replaced with "-". A label given with `-l` takes precedence.
+//! * --resume-last
+//!   * grow with the same duration, label and tree as the last grown tree. The options given
+//!     with it take precedence, so "--resume-last -d 00:45" changes only the duration. If
+//!     no tree was grown yet, the usual defaults are used.
 //! * -t, --tree TREE
 //!   * Grow a custom tree from your tree collection. TREE should be the name.
-//!     The default tree used is called "default".
+//!     The default tree used is called "default-1"; if it's missing, the first tree of
+//!     your collection is grown.
+//! * --tree-string TREE
+//!   * Grow a tree given in its shareable format (or as JSON), for instance one that someone
+//!     sent you, without adding it to your collection. It's recorded in the stats like any
+//!     other tree, and its cost is checked as usual.
+//! * --tree-file FILE
+//!   * Like `--tree-string`, but the tree is read from the first line of FILE.
+//! * --record-failures, --no-record-failures
+//!   * Record the tree as killed if you cancel it, or don't record anything. This overrides
+//!     `record_failures` from the config file; by default, nothing is recorded.
+//! * --notify, --no-notify
+//!   * Post a desktop notification when the tree finishes growing, with its label and
+//!     duration, or don't post it. This overrides `notify` from the config file; by
+//!     default, nothing is posted. The notification is posted by `notify-send` on Linux
+//!     and by `osascript` on macOS; if that fails, a warning is displayed.
+//! * -q, --quiet
+//!   * Do not display the encouragement messages while the tree grows, nor a message when
+//!     you cancel it. This is not the same as the global `--quiet`, which hides the warnings.
+//! * --bell
+//!   * Ring the terminal bell when the tree finishes growing.
+//! * --sound FILE
+//!   * Play the sound FILE when the tree finishes growing. The sound is played by `paplay`
+//!     or `aplay`, or by `afplay` on macOS, and it's stopped after 10 seconds. If it can't
+//!     be played, a warning is displayed.
+//! * --stopwatch
+//!   * count up instead of down, and stop when you press Enter. The tree is recorded with
+//!     the whole minutes that passed, if they are enough for the cost of the tree and
+//!     for `min_session_minutes`. CTRL+C cancels the stopwatch without recording
+//!     anything. The `status` subcommand displays the time that passed.
+//! * --timer-only TIME
+//!   * only count down TIME, in HH:MM format, like a focus timer. No tree is grown, so
+//!     the tree and the cost don't matter, and nothing is recorded in the stats. The
+//!     `status` subcommand displays it as a timer.
 //! * -n, --no-display
 //!   * do not display the growing menu, just get messages through stdout.
+//! * -a, --allow-concurrent
+//!   * grow the tree even if another tree is already growing. Normally, only one tree can
+//!     grow at a time; the running session holds a lock in `~/.rusty-forest/grow.lock`.
+//!     Locks left behind by sessions that are not running anymore are cleaned up automatically.
+//! * -s, --no-status
+//!   * do not write the status file of the growing tree. By default, the tree writes its
+//!     status (the tree, label and remaining time) in `~/.rusty-forest/current.json`
+//!     every second, so it can be queried with the `status` subcommand.
+//! * --status-env
+//!   * also write the status in `~/.rusty-forest/current.env`, as `KEY='value'` lines that
+//!     can be sourced by a shell: `RUSTY_FOREST_TREE`, `RUSTY_FOREST_LABEL` and
+//!     `RUSTY_FOREST_REMAINING` (in seconds). The file is removed when the tree is grown.
+//! * --scale N
+//!   * draw each cell of the tree as a block of NxN characters, to make the tree bigger.
+//!     The default scale is 1. The screen needs to be larger for bigger scales.
+//! * --time-format FORMAT
+//!   * display the remaining time as hms (01:05:30, the default), hm (01:05) or compact
+//!     (1h5m30s). With `--no-display`, the remaining time is printed before each message.
+//! * --list-trees
+//!   * print the names of the trees from your collection, one on each line, and exit.
+//! * --list-themes
+//!   * print the names of the themes that can be used with `import --theme`, and exit.
+//! * --on-complete COMMAND
+//!   * run COMMAND in the shell when the tree finishes growing, for instance to update a
+//!     status bar. The details of the session are in the environment variables
+//!     `RUSTY_FOREST_TREE`, `RUSTY_FOREST_LABEL` and `RUSTY_FOREST_DURATION` (in minutes).
+//!     The output of the command is discarded, and the command is stopped after 10 seconds.
+//!     The default is the `on_complete` setting of the configuration file.
+//! * --on-start COMMAND
+//!   * run COMMAND in the shell right before the tree starts growing, for instance to mute
+//!     your notifications. The command gets the same environment variables as
+//!     `--on-complete`. If the command fails, a warning is displayed and the tree grows
+//!     anyway. The default is the `on_start` setting of the configuration file.
+//! * --require-hooks
+//!   * do not grow the tree if the `--on-start` command fails.
 //!
 //! ### import
 //!
 //! With this command, you can add more trees to your collection, by either creating them, 
 //! with the tree editor, or by importing from other people.
 //!
+//! Several `import` can run at the same time: each one waits for the lock of the tree
+//! collection (see `--no-lock`), and reads `trees.conf` again right before saving it, adding
+//! the trees that were saved there in the meantime. So, even with `--no-lock`, an import
+//! doesn't drop the trees imported by another one, unless both save at the very same moment.
+//! `erase` and `edit` don't read the file again: only the lock keeps them from undoing each
+//! other's changes.
+//!
 //! Arguments:
 //!
 //! If you do not use `-c` or `-f`, then you should put multiple trees in their shareable
 //! format.
 //!
+//! The names of the trees can have at most 64 characters. The trees with longer names are
+//! not added, and the editor doesn't accept more characters.
+//!
 //! Options:
 //!
 //! * -f, --file FILE
 //!   * Import all the trees from a file. They should be each on a separate line, 
-//!     in their format
+//!     in their format or as JSON (like `export --format json` writes them)
 //! * -c, --create
 //!   * Use the tree editor to create a tree. It will be directly added to the collection.
 //!     Using this 
+//! * --from TREE
+//!   * With `-c`, start the editor from a copy of TREE instead of a blank tree. The copy
+//!     keeps the name of TREE, so change it or use `-n`.
+//! * -g, --generate N
+//!   * Generate N random trees and add them to the collection. They are named "generated",
+//!     "generated-1", "generated-2" and so on.
+//! * -s, --seed SEED
+//!   * Generate the random trees using the given seed, so the same trees are generated 
+//!     every time.
+//! * -T, --theme THEME
+//!   * Generate the random trees with the colors of a season: "summer" (greens, the 
+//!     default), "spring" (light greens and flowers), "autumn" (oranges and reds) or
+//!     "winter" (blues and whites).
 //! * -n, --name-change
 //!   * Rename the trees if they have the same name. For instance, if there is a tree called 
 //!     "tree", and you want to add another tree named "tree", the second one will be renamed 
 //!     to "tree-1"
 //! * -e, --error
 //!   * Display error messages about loading trees in stderr
+//! * -L, --lint FILE
+//!   * Check all the trees from a file without importing anything. Each line of the file 
+//!     gets a tab-separated report line: the line number, followed by `ok` and the tree 
+//!     name, or by `error` and the reason. The last line is `summary`, followed by the 
+//!     number of valid and invalid trees.
+//! * --diff FILE
+//!   * Compare the trees from a file with your collection without importing anything.
+//!     Each line of the file gets a tab-separated report line: the line number, followed
+//!     by `new` and the tree name, by `collision` and the tree name if another tree
+//!     already has that name, by `duplicate`, the tree name and the name of the tree that
+//!     looks the same, or by `error` and the reason. The last line is `summary`, followed
+//!     by the number of new, colliding, duplicate and invalid trees.
+//! * --preview
+//!   * With `--diff`, draw each tree under its report line.
 //!
 //! ### export
 //!
@@ -85,10 +320,32 @@
 //!   * Open the tree editor and export the created tree.
 //! * -a, --all
 //!   * Export all the trees from your collection.
+//! * -C, --collage FILE
+//!   * Draw all the trees from your collection in a single grid, and write it to the given
+//!     file. The file contains the colored text of the grid, so it can be displayed with
+//!     `cat FILE` in a terminal.
+//! * --columns N
+//!   * Use N trees on each row of the collage. By default, the collage is roughly square.
+//! * --png
+//!   * Draw a tree in a PNG image. Using this, the arguments should be the name of the tree
+//!     and the file of the image. This is available only if rusty-forest was installed with
+//!     the `image` feature (`cargo install rusty-forest --features image`).
+//! * --pixel-size N
+//!   * Draw each cell of the tree in the PNG image as a square of NxN pixels. The default
+//!     is 16.
+//! * --no-symbols
+//!   * Do not draw the symbols in the PNG image. By default, each symbol is drawn as a 
+//!     smaller square with the foreground color in the middle of its cell.
+//! * --format FORMAT
+//!   * Write the trees in their format (`tree`, the default) or as JSON (`json`), one tree
+//!     on each line. The JSON object of a tree has its `name` and its `cells`, as 5 lines of
+//!     5 cells, where each cell has the `bg` and `fg` colors as `[r, g, b]` and the `symbol`.
+//!     The trees exported as JSON can be imported back.
 //!
 //! ### list
 //!
-//! Display all the trees from your collection that you can choose to grow.
+//! Display all the trees from your collection that you can choose to grow, together
+//! with how much time they need to grow.
 //!
 //! Options:
 //!
@@ -97,9 +354,35 @@
 //! * -T, --tail COUNT
 //!   * Display the last COUNT trees from your collection.
 //! * -r, --random COUNT
-//!   * display COUNT random trees from your collection.
+//!   * display COUNT random trees from your collection. This composes with -H and -T:
+//!     they select a range of the collection first, then COUNT random trees are picked
+//!     from that range. For instance, "-H 10 -r 3" displays 3 random trees out of the 
+//!     first 10.
+//! * -s, --seed SEED
+//!   * pick the random trees using the given seed, so the same trees are picked every time.
 //! * -n, --no-draw
 //!   * just list the name of the trees, without actually drawing them.
+//! * -c, --columns N
+//!   * draw the trees side by side, N trees on each row, with their names above them.
+//! * --scale N
+//!   * draw each cell of the trees as a block of NxN characters. The default scale is 1.
+//! * -p, --page
+//!   * scroll through the trees in a pager, even if they fit on the screen. By default, the
+//!     pager is used only when the trees don't fit on the screen. Scroll with the arrow keys,
+//!     PageUp, PageDown, Home and End, and exit with q. The pager is never used if the output
+//!     is not a terminal.
+//! * -P, --no-page
+//!   * never use the pager, just print the trees.
+//! * -C, --no-cost
+//!   * do not display the time cost of each tree next to its name. By default, each tree
+//!     is listed like "1) oak (cost 00:20)".
+//! * -L, --legend
+//!   * after drawing each tree, list the distinct symbols drawn on it, like "symbols: o *".
+//!     Useful when the symbols have a meaning for you.
+//! * -m, --max-cost TIME
+//!   * display only the trees that can grow in the given time, in HH:MM format. This is 
+//!     applied before the other options, so "-m 00:25 -H 3" displays the first 3 trees 
+//!     that can grow in 25 minutes.
 //! * -e, --export
 //!   * display the selected trees in an exportable format
 //!
@@ -110,11 +393,36 @@
 //! Arguments:
 //!
 //! Put all the names of the trees that you want to delete from your collection.
+//! The built-in trees (default-1, default-2 and default-3) can't be erased.
+//!
+//! Options:
+//!
+//! * -m, --match PATTERN
+//!   * Erase all the trees whose names match PATTERN. In the pattern, `*` matches any
+//!     sequence of characters and `?` matches a single character; a pattern without them
+//!     matches the names that contain it. The matching trees are listed, and you are asked
+//!     for confirmation. The built-in trees (default-1, default-2 and default-3) are never
+//!     erased this way.
+//! * --dry-run
+//!   * With `--match`, only list the trees that would be erased.
+//! * -y, --yes
+//!   * do not ask for confirmation.
+//!
+//! ### edit
+//!
+//! Open a tree from your collection in the tree editor, to change its cells or its name.
+//! When you finish, the edited tree replaces the original one. The built-in trees can't be
+//! edited, but you can start a new tree from them with `import -c --from`.
+//!
+//! Arguments:
+//!
+//! The name of the tree to edit.
 //!
 //! ### stats
 //!
 //! Display stats about trees that you've grown. If you do not use -g or -G, then
-//! this will just display the trees that you've grown.
+//! this will just display the trees that you've grown, followed by a summary line with
+//! the number of trees, the total time grown and the average session.
 //!
 //! Options:
 //!
@@ -122,6 +430,9 @@
 //!   * Display the trees in a fixed grid size. The size should be in RxC format, for 
 //!     instance "3x4" for 3 rows and 4 columns. Additionally, you can use "whole" to
 //!     use a grid as big as the screen.
+//! * --scale N
+//!   * Draw each cell of the trees in the grid as a block of NxN characters. The default
+//!     scale is 1. With "whole", fewer trees fit on the screen.
 //! * -G, --graph UNIT
 //!   * Display a graph of the relevant time unit. The possible time windows are
 //!     daily, weekly, monthly and yearly.
@@ -134,32 +445,205 @@
 //!     "today", "yesterday", "this-week", "this-month" and "this-year".
 //! * -F, --format FORMAT
 //!   * Display the dates in a custom format; the default is "%d-%m-%Y %H:%M"
+//! * -w, --watch [SECONDS]
+//!   * Keep the chosen view (list, grid or graph) open in a separate screen and refresh it
+//!     every SECONDS seconds, so you can follow your stats while growing trees in another
+//!     terminal. The default is 5 seconds. Press q to exit.
+//! * --goals
+//!   * Display a bar for each goal set in the config file, showing how much you have grown
+//!     today, this week and this month compared to the goal.
+//! * --top N
+//!   * Display the N labels with the most time grown, each with its total. The filters
+//!     (label, time and count) are applied first.
+//! * --by KIND
+//!   * With `--top`, rank the labels or the trees instead. The options are label and tree;
+//!     the default is label.
+//! * --daily-table
+//!   * Display a row for each day with trees, oldest first, with the number of sessions
+//!     and the minutes grown. The days start at `day_start_hour`, like in the graph, and the
+//!     filters (label, time and count) are applied first.
+//! * --output FORMAT
+//!   * With `--daily-table`, write the table as text, csv or json. The default is text.
+//!     Without another view, `--output csv` writes the list of grown trees as comma-separated
+//!     values, to open in a spreadsheet: a line for each tree with the timestamp, the date
+//!     in RFC 3339 format, the label, the minutes grown and the name of the tree. The
+//!     filters (label, time and count) are applied first.
+//! * --summary-only
+//!   * Display only the summary line, without the trees. The filters (label, time and count)
+//!     are applied first.
+//! * --avg
+//!   * Display the mean and median length of the sessions, and the shortest and longest
+//!     one. The filters (label, time and count) are applied first.
+//! * --compare PERIOD
+//!   * Compare the time grown this week or month with the previous week or month. The
+//!     possible periods are week and month.
+//! * --tsv FILE
+//!   * Write the grown trees to FILE as tab-separated values, to open them in a spreadsheet.
+//!     Each line has the label, the date in ISO 8601 format, the minutes grown, the name
+//!     of the tree and a note. The note is always empty for now, since the grown trees
+//!     don't have notes. The filters (label, time and count) are applied.
+//! * --file PATH
+//!   * Read the grown trees from PATH instead of `~/.rusty-forest/stats.conf`, for instance
+//!     to look at an archived stats file. All the views and filters can be used with it.
+//! * --total
+//!   * Display the total time grown. The total is kept up to date in
+//!     `~/.rusty-forest/state.json`, so it's displayed instantly.
+//! * --recompute
+//!   * Recompute the total time grown from all the grown trees, in case it got out of sync.
+//! * --kills
+//!   * Display the trees that were killed by cancelling them, how long they grew before
+//!     that and the total time lost. The killed trees are kept in the stats file, but
+//!     they are left out of every other view and of the total time grown. The label,
+//!     time and count filters can be used with it.
+//! * --merge FILE...
+//!   * Add the grown trees from other stats files (for instance, from your other computers)
+//!     to your stats. The trees that are already in your stats are skipped, and all the
+//!     trees are sorted by the time they were grown.
+//! * --dedupe
+//!   * Remove the grown trees that are exact duplicates (same duration, label, time and
+//!     tree), for instance after merging the same file twice. Since the time is saved with
+//!     a precision of one second, two identical sessions ending in the same second can't be
+//!     told apart, and one of them is removed as well.
+//! * --dry-run
+//!   * With `--dedupe`, only display how many duplicates would be removed.
+//!
+//! ### status
+//!
+//! Display the tree that is growing right now, its label and how much time is left. This
+//! is useful when the tree is growing in another terminal. If the session that wrote the 
+//! status is not running anymore, the leftover status is removed.
+//!
+//! Options:
+//!
+//! * --shell
+//!   * print the status as `export` statements for the variables `RUSTY_FOREST_TREE`,
+//!     `RUSTY_FOREST_LABEL` and `RUSTY_FOREST_REMAINING` (in seconds), or an `unset`
+//!     statement if no tree is growing. This is useful for shell prompts, with
+//!     `eval "$(rusty-forest status --shell)"`.
+//!
+//! ### reset
+//!
+//! Remove all your saved data: the tree collection, the grown trees and the configuration
+//! file. The files that will be removed are listed, and you are asked for confirmation.
+//!
+//! Options:
+//!
+//! * -y, --yes
+//!   * do not ask for confirmation.
+//! * -t, --trees-only
+//!   * remove only the tree collection.
+//! * -s, --stats-only
+//!   * remove only the grown trees.
+//!
+//! ### backup
+//!
+//! Save all your data (the tree collection, the grown trees and the configuration file) in a
+//! single file, given as argument. The backup is a text file that starts with the line
+//! `rusty-forest-backup 1`. Each saved file follows, as a line `file NAME LENGTH` and then
+//! the LENGTH bytes of the file.
+//!
+//! ### restore
+//!
+//! Restore your data from a backup file, given as argument. The files from the backup replace
+//! your current ones; you are asked for confirmation first.
+//!
+//! Options:
+//!
+//! * -y, --yes
+//!   * do not ask for confirmation.
+//!
+//! ### paths
+//!
+//! Print where your data is saved: the data directory, the tree collection, the grown trees
+//! and the configuration file, each on a line after its name and a tab. The paths follow
+//! `--dir`, so `rusty-forest --dir DIR paths` prints the files inside DIR.
+//!
+//! ### doctor
+//!
+//! Look for problems in your saved data, without changing anything. It checks that the data
+//! directory can be written, reports the lines of `trees.conf` and `stats.conf` that can't
+//! be read, the trees with the same name or the same appearance, and the lock, status and
+//! session files left by a tree that is not growing anymore. Each result is printed on a
+//! line as `ok`, `warning` or `problem`, followed by what was checked and the details,
+//! separated by tabs. The last line is `summary`, followed by the number of warnings and
+//! problems. The exit code is 1 if there are problems.
+//!
+//! Options:
+//!
+//! * --fix
+//!   * Before the checks, remove the lines that can't be read from `trees.conf` and
+//!     `stats.conf`. Each removed line is printed as `removed`, the file and the line. The
+//!     original file is kept next to it with the `.bak` extension, printed as `backup`. The
+//!     older backups are never replaced, so the next ones are named `.bak.1`, `.bak.2` and
+//!     so on.
 
 extern crate getopts;
-use getopts::Options;
+use getopts::{Options, Matches, ParsingStyle};
 use std::env;
+use std::path::Path;
 use std::fs::{self, OpenOptions};
-use crate::tree::{TreeCollection, Tree, get_stats};
+use crate::error::{ForestError, fail};
+use crate::tree::{TreeCollection, Tree, GrownTree, is_builtin, get_stats_from, data_dir, data_lines, set_data_dir};
+use crate::store::{FileTrees, StatsBackend, set_stats_backend, stats_store};
 use crate::editor::run_tree_editor;
-use crate::grow::{GrowthTime, grow_tree};
-use std::io::{Write, stdout};
+use crate::generate::{THEMES, find_theme, generate_themed_tree};
+use crate::grow::{CANCEL, check_min_session, parse_cancel_messages, load_positive_messages, GrowthTime, GrowOptions, TimeFormat, format_remaining, grow_tree, is_valid_label, label_from_dir, last_session, resume_interrupt};
+use crate::session::Session;
+use crate::status::{GrowStatus, shell_exports};
+use crate::color::ColorMode;
+use crate::config::Config;
+use crate::state::State;
+use crate::lock::{LockFile, LockError, GROW_LOCK, TREES_LOCK, TREES_LOCK_TIMEOUT};
+use crate::stats::{StatsFilter, StatsView, Goal};
+use crate::doctor::Health;
+use crate::list::{ListOptions, affordable_indices, select_indices, write_list, write_names};
+use crate::render::write_collage;
+use crate::import::{write_diff, write_import, write_lint};
+use crate::backup::{ResetKind, existing_files, reset_files};
+use std::io::{self, ErrorKind, Write, stdout};
 use std::str::FromStr;
-use std::cmp;
-use termion::{color, terminal_size};
-use rand::thread_rng;
-use rand::seq::SliceRandom;
-use regex::Regex;
-use chrono::{Local, TimeZone, Datelike, DurationRound, Duration, DateTime};
-use itertools::Itertools;
+use termion::terminal_size;
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+use chrono::Local;
 
 pub mod tree;
 pub mod editor;
 pub mod display;
 pub mod grow;
+pub mod stats;
+pub mod status;
+pub mod lock;
+pub mod render;
+pub mod list;
+pub mod pager;
+pub mod generate;
+pub mod color;
+pub mod config;
+pub mod state;
+pub mod onboarding;
+pub mod backup;
+pub mod doctor;
+pub mod log;
+pub mod error;
+pub mod hooks;
+pub mod notify;
+pub mod session;
+pub mod store;
+pub mod import;
+#[cfg(feature = "image")]
+pub mod image;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+#[cfg(test)]
+pub mod testing;
 
 /// rusty-forest version number.
 const VERSION: &str = "0.1.2";
 
+/// The subcommands that only look at the data, so they must not create the data directory.
+const LOOK_ONLY_SUBCOMMANDS: [&str; 4] = ["status", "backup", "paths", "doctor"];
+
 /// Print the entire usage of the program.
 fn print_whole_usage(program: &str, opts: Options) {
     let brief = format!(r"
@@ -171,7 +655,14 @@ Commands: grow          grow a tree
           export        export trees to share with other people
           list          list all created/imported trees
           stats         display stats about all grown trees
-          erase         erase a tree from the collection", program, program);
+          status        display the tree that is growing right now
+          edit          edit a tree from the collection
+          erase         erase a tree from the collection
+          reset         remove all the saved data
+          backup        save all the data in a single file
+          restore       restore the data from a backup
+          paths         print where the data is saved
+          doctor        look for problems in the saved data", program, program);
 
     print!("{}", opts.usage(&brief));
 }
@@ -186,6 +677,15 @@ fn build_default_opts() -> Options {
     let mut opts = Options::new();
     opts.optflag("h", "help", "display the help menu");
     opts.optflag("v", "version", "display the version number");
+    opts.optopt("", "dir", "keep the saved data in DIR instead of ~/.rusty-forest", "DIR");
+    opts.optflag("", "no-onboarding", "do not display the welcome message on the first run");
+    opts.optflag("V", "verbose", "print details about what the program does to stderr");
+    opts.optflag("", "quiet", "do not print any warning, like the lines that can't be loaded");
+    opts.optflag("", "no-lock", "change the tree collection without waiting for the other processes that change it");
+    opts.optflag("", "json-errors", "print the errors to stderr as JSON objects with the message and the exit code");
+    opts.optopt("", "color-depth", "colors used to draw the trees: truecolor, 256, 16 or none; default is detected from the terminal", "DEPTH");
+    opts.optflag("", "ascii-art", "draw the trees without any color, only with their symbols; same as --color-depth none");
+    opts.optopt("", "fill", "character drawn in the empty cells of the trees when there are no colors; default is space", "CHAR");
     opts
 }
 
@@ -203,8 +703,15 @@ fn build_import_opts() -> Options {
     opts.optflag("h", "help", "display this help menu");
     opts.optopt("f", "file", "import trees from the file; using this, TREE should be omitted", "FILE");
     opts.optflag("c", "create", "open the tree editor; using this, TREE should be omitted");
+    opts.optopt("", "from", "with --create, start the editor from a copy of the tree, renamed if it keeps the name", "TREE");
     opts.optflag("n", "name-change", "change names to avoid duplicate names; without this, duplicate names are ignored");
     opts.optflag("e", "error", "display error messages when importing trees");
+    opts.optopt("L", "lint", "check the trees from the file without importing them", "FILE");
+    opts.optopt("", "diff", "compare the trees from the file with the collection without importing them", "FILE");
+    opts.optflag("", "preview", "with --diff, draw each tree under its line");
+    opts.optopt("g", "generate", "generate N random trees; using this, TREE should be omitted", "N");
+    opts.optopt("s", "seed", "seed used to generate the random trees", "SEED");
+    opts.optopt("T", "theme", "colors of the generated trees: summer, spring, autumn or winter", "THEME");
     opts
 }
 
@@ -222,8 +729,16 @@ fn build_list_opts() -> Options {
     opts.optflag("h", "help", "display this help menu");
     opts.optopt("H", "head", "display the first N trees", "COUNT");
     opts.optopt("T", "tail", "display the last N trees", "COUNT");
-    opts.optopt("r", "random", "display N random trees", "COUNT");
+    opts.optopt("r", "random", "display N random trees; picked from the trees selected by head and tail", "COUNT");
+    opts.optopt("s", "seed", "seed used to pick the random trees", "SEED");
     opts.optflag("n", "no-draw", "do not draw the trees themselves");
+    opts.optopt("c", "columns", "draw the trees side by side, N on each row", "N");
+    opts.optopt("", "scale", "draw each cell of the trees as an NxN block; default is 1", "N");
+    opts.optflag("p", "page", "scroll through the trees in a pager, even if they fit on the screen");
+    opts.optflag("P", "no-page", "never scroll through the trees in a pager");
+    opts.optflag("C", "no-cost", "do not display the time cost of the trees");
+    opts.optflag("L", "legend", "list the symbols used by each tree after drawing it");
+    opts.optopt("m", "max-cost", "display only the trees that can grow in this time; format is H:M", "TIME");
     opts.optflag("e", "export", "display the trees in an exportable format");
     opts
 }
@@ -235,6 +750,20 @@ fn print_export_usage(program: &str, opts: Options) {
     print!("{}", opts.usage(&brief));
 } 
 
+/// Draw the tree in a PNG image.
+#[cfg(feature = "image")]
+fn export_png(tree: &Tree, file_name: &str, pixel_size: usize, draw_symbols: bool) {
+    if let Err(x) = image::write_png(tree, file_name, pixel_size, draw_symbols) {
+        fail(ForestError::Runtime(x.to_string()));
+    }
+}
+
+/// Draw the tree in a PNG image; this needs the image feature.
+#[cfg(not(feature = "image"))]
+fn export_png(_tree: &Tree, _file_name: &str, _pixel_size: usize, _draw_symbols: bool) {
+    fail(ForestError::Runtime("PNG export is not available: rusty-forest was built without the image feature".to_string()));
+}
+
 /// Build the opts for the export subprogram.
 fn build_export_opts() -> Options {
     let mut opts = Options::new();
@@ -243,6 +772,12 @@ fn build_export_opts() -> Options {
     opts.optopt("f", "to-file", "export trees to file", "FILE");
     opts.optflag("c", "create", "open the tree editor; using this, NAME should be omitted");
     opts.optflag("a", "all", "export all the trees");
+    opts.optopt("C", "collage", "draw all the trees in a grid, in the given file", "FILE");
+    opts.optopt("", "columns", "number of trees on each row of the collage", "N");
+    opts.optflag("", "png", "draw a tree in a PNG image; using this, the arguments are NAME FILE");
+    opts.optopt("", "pixel-size", "size in pixels of each cell of the PNG image; default is 16", "N");
+    opts.optflag("", "no-symbols", "do not draw the symbols in the PNG image");
+    opts.optopt("", "format", "write the trees in the tree format or as JSON, one on each line; the options are tree and json, and the default is tree", "FORMAT");
     opts
 }
 
@@ -259,15 +794,38 @@ fn build_grow_opts() -> Options {
     opts.optflag("h", "help", "display this help menu");
     opts.optopt("d", "duration", "set custom growth time; format is H:M; if omitted, the default is 20m", "TIME");
     opts.optopt("l", "label", "set a custom label for this tree", "LABEL");
+    opts.optflag("", "label-from-dir", "use the name of the current directory as the label");
+    opts.optflag("", "resume-last", "grow with the duration, label and tree of the last session");
     opts.optopt("t", "tree", "grow a custom tree", "TREE");
+    opts.optflag("", "record-failures", "record the tree as killed in the stats if it's cancelled");
+    opts.optflag("", "no-record-failures", "do not record anything if the tree is cancelled; this is the default");
+    opts.optflag("", "notify", "post a desktop notification when the tree finishes growing");
+    opts.optflag("", "no-notify", "do not post a desktop notification; this is the default");
+    opts.optflag("q", "quiet", "do not display the encouragement messages, nor a message when the tree is cancelled");
+    opts.optflag("", "bell", "ring the terminal bell when the tree finishes growing");
+    opts.optopt("", "sound", "play the sound FILE when the tree finishes growing", "FILE");
+    opts.optopt("", "tree-string", "grow a tree given in its shareable format, without adding it to the collection", "TREE");
+    opts.optopt("", "tree-file", "grow the tree from the first line of the file, without adding it to the collection", "FILE");
+    opts.optflag("", "stopwatch", "count up until Enter is pressed, and grow the tree for the time that passed");
+    opts.optopt("", "timer-only", "only count down the given time, without a tree and without recording it in the stats", "TIME");
     opts.optflag("n", "no-display", "do not display the growing menu");
+    opts.optflag("s", "no-status", "do not write the status file that can be queried with the status command");
+    opts.optflag("a", "allow-concurrent", "grow even if another tree is already growing");
+    opts.optflag("", "status-env", "also write the status in a file that can be sourced by a shell");
+    opts.optopt("", "scale", "draw each cell of the tree as an NxN block; default is 1", "N");
+    opts.optopt("", "time-format", "display the remaining time as hms, hm or compact; default is hms", "FORMAT");
+    opts.optflag("", "list-trees", "print the names of the trees that can be grown and exit");
+    opts.optflag("", "list-themes", "print the names of the themes for generated trees and exit");
+    opts.optopt("", "on-start", "run COMMAND in the shell right before the tree starts growing", "COMMAND");
+    opts.optopt("", "on-complete", "run COMMAND in the shell when the tree finishes growing", "COMMAND");
+    opts.optflag("", "require-hooks", "do not grow the tree if the on-start command fails");
 
     opts
 }
 
 /// Print the instructions for the stats subprogram.
 fn print_stats_usage(program: &str, opts: Options) {
-    let brief = format!("Usage: {} stats", program);
+    let brief = format!("Usage: {} stats [--merge FILE...]", program);
     print!("{}", opts.usage(&brief));
 }
 
@@ -278,18 +836,52 @@ fn build_stats_opts() -> Options {
     opts.optflag("h", "help", "display this help menu");
     opts.optopt("g", "grid", "display the trees in a fixed grid size; the grid size is RxC format", "GRID");
     opts.optflag("n", "no-forest", "do not display the trees in a grid");
+    opts.optopt("", "scale", "draw each cell of the trees in the grid as an NxN block; default is 1", "N");
     opts.optopt("G", "graph", "display a graph of the relevant time unit (DAILY, WEEKLY, MONTHLY, YEARLY)", "UNIT");
     opts.optopt("f", "filter", "filter grown trees by label", "LABEL");
     opts.optopt("c", "count", "display only the most recent trees", "AMOUNT");
     opts.optopt("t", "time", "get information only from a certain time period", "TIME");
     opts.optopt("F", "format", "display dates in a custom format; default is %d-%m-%Y %H:%M", "FORMAT");
+    opts.optflagopt("w", "watch", "refresh the chosen view every few seconds; default is 5; press q to exit", "SECONDS");
+    opts.optopt("", "compare", "compare the time grown in this period with the previous one (WEEK, MONTH)", "PERIOD");
+    opts.optflag("", "goals", "display the progress towards the daily, weekly and monthly goals from the config file");
+    opts.optopt("", "file", "read the grown trees from another file instead of ~/.rusty-forest/stats.conf", "PATH");
+    opts.optopt("", "tsv", "write the grown trees to a file as tab-separated values, for spreadsheets", "FILE");
+    opts.optflag("", "total", "display the total time grown");
+    opts.optopt("", "top", "display the N labels or trees with the most time grown", "N");
+    opts.optopt("", "by", "with --top, rank the labels or the trees; the options are label and tree, and the default is label", "KIND");
+    opts.optflag("", "daily-table", "display the number of sessions and the minutes grown on each day");
+    opts.optopt("", "output", "write the list as text or csv, or the daily table as text, csv or json; default is text", "FORMAT");
+    opts.optflag("", "summary-only", "display only the number of trees, the total time grown and the average session");
+    opts.optflag("", "avg", "display the mean, median, shortest and longest session");
+    opts.optflag("", "kills", "display the trees that were killed by cancelling them, instead of the grown trees");
+    opts.optflag("", "merge", "add the grown trees from other stats files, given as arguments, skipping duplicates");
+    opts.optflag("", "dedupe", "remove the grown trees that are exact duplicates");
+    opts.optflag("", "dry-run", "with --dedupe, only count the duplicates without removing them");
+    opts.optflag("", "recompute", "recompute the total time grown from all the grown trees");
+
+    opts
+}
+
+/// Print the instructions for the status subprogram.
+fn print_status_usage(program: &str, opts: Options) {
+    let brief = format!("Usage: {} status", program);
+    print!("{}", opts.usage(&brief));
+}
+
+/// Build the opts for the status subprogram.
+fn build_status_opts() -> Options {
+    let mut opts = Options::new();
+
+    opts.optflag("h", "help", "display this help menu");
+    opts.optflag("", "shell", "print the status as shell export statements");
 
     opts
 }
 
 /// Print the instructions for the erase subprogram.
 fn print_erase_usage(program: &str, opts: Options) {
-    let brief = format!("Usage: {} erase TREES", program);
+    let brief = format!("Usage: {} erase [TREES]", program);
     print!("{}", opts.usage(&brief));
 }
 
@@ -298,40 +890,390 @@ fn build_erase_opts() -> Options {
     let mut opts = Options::new();
 
     opts.optflag("h", "help", "display this help menu");
+    opts.optopt("m", "match", "erase all the trees whose names match the pattern; * and ? can be used as wildcards", "PATTERN");
+    opts.optflag("", "dry-run", "only list the trees that match the pattern");
+    opts.optflag("y", "yes", "do not ask for confirmation");
 
     opts
 }
 
+/// Print the instructions for the edit subprogram.
+fn print_edit_usage(program: &str, opts: Options) {
+    let brief = format!("Usage: {} edit TREE", program);
+    print!("{}", opts.usage(&brief));
+}
+
+/// Build the opts for the edit subprogram.
+fn build_edit_opts() -> Options {
+    let mut opts = Options::new();
+
+    opts.optflag("h", "help", "display this help menu");
+
+    opts
+}
+
+/// Print the instructions for the reset subprogram.
+fn print_reset_usage(program: &str, opts: Options) {
+    let brief = format!("Usage: {} reset", program);
+    print!("{}", opts.usage(&brief));
+}
+
+/// Build the opts for the reset subprogram.
+fn build_reset_opts() -> Options {
+    let mut opts = Options::new();
+
+    opts.optflag("h", "help", "display this help menu");
+    opts.optflag("y", "yes", "do not ask for confirmation");
+    opts.optflag("t", "trees-only", "remove only the tree collection");
+    opts.optflag("s", "stats-only", "remove only the grown trees");
+
+    opts
+}
+
+/// Print the instructions for the backup subprogram.
+fn print_backup_usage(program: &str, opts: Options) {
+    let brief = format!("Usage: {} backup FILE", program);
+    print!("{}", opts.usage(&brief));
+}
+
+/// Build the opts for the backup subprogram.
+fn build_backup_opts() -> Options {
+    let mut opts = Options::new();
+
+    opts.optflag("h", "help", "display this help menu");
+
+    opts
+}
+
+/// Print the instructions for the restore subprogram.
+fn print_restore_usage(program: &str, opts: Options) {
+    let brief = format!("Usage: {} restore FILE", program);
+    print!("{}", opts.usage(&brief));
+}
+
+/// Build the opts for the restore subprogram.
+fn build_restore_opts() -> Options {
+    let mut opts = Options::new();
+
+    opts.optflag("h", "help", "display this help menu");
+    opts.optflag("y", "yes", "do not ask for confirmation");
+
+    opts
+}
+
+/// Print the instructions for the paths subprogram.
+fn print_paths_usage(program: &str, opts: Options) {
+    let brief = format!("Usage: {} paths", program);
+    print!("{}", opts.usage(&brief));
+}
+
+/// Build the opts for the paths subprogram.
+fn build_paths_opts() -> Options {
+    let mut opts = Options::new();
+
+    opts.optflag("h", "help", "display this help menu");
+
+    opts
+}
+
+/// Print the instructions for the doctor subprogram.
+fn print_doctor_usage(program: &str, opts: Options) {
+    let brief = format!("Usage: {} doctor", program);
+    print!("{}", opts.usage(&brief));
+}
+
+/// Build the opts for the doctor subprogram.
+fn build_doctor_opts() -> Options {
+    let mut opts = Options::new();
+
+    opts.optflag("h", "help", "display this help menu");
+    opts.optflag("", "fix", "remove the lines that can't be read from trees.conf and stats.conf, keeping a backup of the files");
+
+    opts
+}
+
+/// Ask the user a yes or no question. Anything other than y counts as no.
+fn confirm(question: &str) -> bool {
+    print!("{} [y/N] ", question);
+    stdout().flush().expect("Failed to flush");
+
+    let mut answer = String::new();
+    if std::io::stdin().read_line(&mut answer).is_err() {
+        return false;
+    }
+
+    answer.trim().eq_ignore_ascii_case("y")
+}
+
+/// Parse the scale option, which is shared by the subprograms that draw trees. The
+/// default scale is 1.
+fn parse_scale(matches: &Matches) -> usize {
+    match matches.opt_str("scale") {
+    Some(x) => {
+        match x.parse::<usize>() {
+        Ok(val) if val > 0 => { val }
+        _ => {
+            fail(ForestError::Usage("Failed to parse scale: it should be a positive number".to_string()));
+        }
+        }
+    }
+    None => { 1 }
+    }
+}
+
+/// Parse the options of a subprogram. A wrong option ends the program with a usage error.
+fn parse_args(opts: &Options, args: &[String]) -> Matches {
+    match opts.parse(args) {
+    Ok(x) => { x }
+    Err(x) => { fail(ForestError::Usage(x.to_string())); }
+    }
+}
+
+/// Read the tree given with `--tree-string` or `--tree-file`. A file is read like `import -f`
+/// reads it, and only its first tree is used.
+fn read_given_tree(tree_string: Option<String>, tree_file: Option<String>) -> Result<Option<Tree>, ForestError> {
+    let line = match (tree_string, tree_file) {
+    (Some(_), Some(_)) => {
+        return Err(ForestError::Usage("--tree-string can't be used with --tree-file".to_string()));
+    }
+    (Some(x), None) => { x }
+    (None, Some(path)) => {
+        match fs::read_to_string(&path) {
+        Ok(x) => { data_lines(&x).find(|line| !line.trim().is_empty()).unwrap_or("").to_string() }
+        Err(err) => {
+            return Err(ForestError::Runtime(format!("Failed to read {}: {}", path, err)));
+        }
+        }
+    }
+    (None, None) => { return Ok(None); }
+    };
+
+    match Tree::read_tree(line.trim().to_string()) {
+    Ok(tree) => { Ok(Some(tree)) }
+    Err(x) => { Err(ForestError::Usage(format!("Failed to read the tree: {}", x))) }
+    }
+}
+
+/// Write where the data directory and the files inside it are, one on each line.
+fn write_paths<W: Write>(out: &mut W, dir: &str) -> io::Result<()> {
+    writeln!(out, "data\t{}", dir)?;
+    for (name, file) in [("trees", "trees.conf"), ("stats", "stats.conf"), ("config", "config.conf")] {
+        writeln!(out, "{}\t{}", name, Path::new(dir).join(file).display())?;
+    }
+    Ok(())
+}
+
+/// Exit after failing to write the output. A broken pipe only means that the reader
+/// doesn't want more output (for instance `rusty-forest list | head`), so it's not
+/// an error.
+fn exit_on_write_error(err: io::Error) -> ! {
+    if err.kind() == ErrorKind::BrokenPipe {
+        std::process::exit(0);
+    }
+
+    fail(ForestError::Runtime(format!("Failed to write: {}", err)))
+}
+
+/// Restore the default behaviour of SIGPIPE, which Rust ignores. This way, writing to a
+/// closed pipe (for instance `rusty-forest export -a | head -1`) quietly ends the program
+/// instead of making `println!` panic.
+#[cfg(unix)]
+fn reset_sigpipe() {
+    unsafe {
+        libc::signal(libc::SIGPIPE, libc::SIG_DFL);
+    }
+}
+
+#[cfg(not(unix))]
+fn reset_sigpipe() {}
+
 fn main() {
-    let args: Vec<String> = env::args().collect();
+    run(env::args().collect());
+}
+
+/// Run the program with the given arguments, starting with the program name.
+fn run(args: Vec<String>) {
+    reset_sigpipe();
+
     let program = args[0].clone();
-    let default_opts = build_default_opts();
+    let mut default_opts = build_default_opts();
+
+    // The global options come before the subcommand, the rest belongs to it
+    default_opts.parsing_style(ParsingStyle::StopAtFirstFree);
+    let global = match default_opts.parse(&args[1..]) {
+    Ok(x) => { x }
+    Err(x) => {
+        fail(ForestError::Usage(x.to_string()));
+    }
+    };
 
-    if args.len() < 2 {
+    if global.opt_present("h") {
         print_whole_usage(&program, default_opts);
         return;
+    } else if global.opt_present("v") {
+        print_version(&program);
+        return;
+    }
+
+    error::set_json_errors(global.opt_present("json-errors"));
+
+    if global.opt_present("quiet") {
+        log::set_level(log::Level::Quiet);
+    } else if global.opt_present("V") {
+        log::set_level(log::Level::Verbose);
+    }
+
+    if let Some(x) = global.opt_str("dir") {
+        set_data_dir(x);
+    }
+
+    // Onboarding creates the data directory, so it's skipped for the help and for the
+    // subcommands that only look at the data
+    let looks_only = match global.free.first() {
+    Some(x) => { LOOK_ONLY_SUBCOMMANDS.contains(&x.as_str()) || global.free[1..].iter().any(|x| x == "-h" || x == "--help") }
+    None => { false }
+    };
+
+    if !global.opt_present("no-onboarding") && !looks_only && onboarding::is_first_run() {
+        onboarding::run();
+    }
+
+    if global.free.is_empty() {
+        print_whole_usage(&program, default_opts);
+        std::process::exit(2);
+    }
+
+    let config = match Config::load() {
+    Ok(x) => { x }
+    Err(x) => {
+        fail(ForestError::Runtime(x.to_string()));
+    }
+    };
+
+    if let Some(x) = config.get("stats_backend") {
+        match StatsBackend::from_str(x) {
+        Ok(backend) => { set_stats_backend(backend); }
+        Err(x) => { fail(ForestError::Runtime(x)); }
+        }
+    }
+
+    let color_depth = if global.opt_present("ascii-art") {
+        Some("none".to_string())
+    } else {
+        match global.opt_str("color-depth") {
+        Some(x) => { Some(x) }
+        None => { config.get("color_depth").map(|x| x.to_string()) }
+        }
+    };
+
+    match color_depth {
+    Some(x) => {
+        match ColorMode::from_str(&x) {
+        Ok(mode) => { color::set_mode(mode); }
+        Err(x) => {
+            fail(ForestError::Runtime(x.to_string()));
+        }
+        }
+    }
+    None => {
+        let colorterm = env::var("COLORTERM").ok();
+        let term = env::var("TERM").ok();
+        color::set_mode(color::detect_mode(colorterm.as_deref(), term.as_deref()));
+    }
+    }
+
+    if let Some(x) = global.opt_str("fill") {
+        let mut chars = x.chars();
+        match (chars.next(), chars.next()) {
+        (Some(chr), None) => { color::set_fill(chr); }
+        _ => {
+            fail(ForestError::Usage("Failed to parse fill: it should be a single character".to_string()));
+        }
+        }
     }
 
-    let subprogram = args[1].clone();
+    let subprogram = global.free[0].clone();
+    let sub_args = &global.free[1..];
 
-    let mut trees = TreeCollection::load();
+    // The subcommands that change the tree collection wait for each other, so one doesn't
+    // overwrite the trees saved by another
+    let _trees_lock = if ["import", "erase", "edit"].contains(&subprogram.as_str()) && !global.opt_present("no-lock") {
+        match LockFile::acquire_timeout(TREES_LOCK, TREES_LOCK_TIMEOUT) {
+        Ok(x) => { Some(x) }
+        Err(LockError::Held(pid)) => {
+            fail(ForestError::Runtime(format!("The tree collection is being changed by another process (pid {}); use --no-lock to change it anyway", pid)));
+        }
+        Err(LockError::Busy) => {
+            fail(ForestError::Runtime("The tree collection is being changed by another process; use --no-lock to change it anyway".to_string()));
+        }
+        Err(LockError::Io(x)) => {
+            fail(ForestError::Runtime(x));
+        }
+        }
+    } else {
+        None
+    };
+
+    // These subcommands don't use the tree collection. Some of them only look at the data,
+    // so they must not even create the data directory
+    let mut trees = if LOOK_ONLY_SUBCOMMANDS.contains(&subprogram.as_str()) || ["reset", "restore"].contains(&subprogram.as_str()) {
+        TreeCollection::builtin()
+    } else {
+        TreeCollection::load()
+    };
 
     match subprogram.as_str() {
     "grow" => {
         let opts = build_grow_opts();
 
-        let matches = opts.parse(&args[2..]).unwrap();
+        let matches = parse_args(&opts, sub_args);
 
         if matches.opt_present("h") {
             print_grow_usage(&program, opts);
             return;
         }
 
+        if matches.opt_present("list-trees") {
+            if let Err(x) = write_names(&mut stdout(), &trees.collection) {
+                exit_on_write_error(x);
+            }
+            return;
+        }
+
+        if matches.opt_present("list-themes") {
+            for theme in &THEMES {
+                println!("{}", theme.name);
+            }
+            return;
+        }
+
         let nogui = matches.opt_present("n");
 
-        let duration_str = match matches.opt_str("d") {
+        // The last session only changes the defaults, so the options given now still win
+        let last_session = if matches.opt_present("resume-last") {
+            match stats_store().load() {
+            Ok(x) => { last_session(x) }
+            Err(x) => { fail(ForestError::Runtime(x)); }
+            }
+        } else {
+            None
+        };
+
+        let timer_only = matches.opt_present("timer-only");
+        let stopwatch = matches.opt_present("stopwatch");
+
+        if timer_only && stopwatch {
+            fail(ForestError::Usage("--stopwatch can't be used with --timer-only".to_string()));
+        }
+
+        let duration_str = match matches.opt_str("timer-only").or_else(|| matches.opt_str("d")) {
         Some(x) => { x }
-        None    => { "00:20".to_string() }
+        None    => {
+            match last_session {
+            Some(ref last) => { format!("{:02}:{:02}", last.duration / 60, last.duration % 60) }
+            None => { "00:20".to_string() }
+            }
+        }
         };
         
         let growth_time = GrowthTime::from_str(&duration_str);
@@ -339,133 +1281,467 @@ fn main() {
         let growth_time = match growth_time {
         Ok(x) => { x }
         Err(x) => {
-            println!("{}", x);
-            return;
+            fail(ForestError::Usage(x.to_string()));
+        }
+        };
+
+        let time_format = match matches.opt_str("time-format") {
+        Some(x) => {
+            match TimeFormat::from_str(&x) {
+            Ok(x) => { x }
+            Err(x) => {
+                fail(ForestError::Usage(x.to_string()));
+            }
+            }
         }
+        None => { TimeFormat::Hms }
         };
 
         let label = match matches.opt_str("l") {
         Some(x) => { x }
-        None    => { "standard".to_string() }
+        None if matches.opt_present("label-from-dir") => {
+            env::current_dir().ok()
+                .and_then(|dir| label_from_dir(&dir))
+                .unwrap_or_else(|| "standard".to_string())
+        }
+        None    => {
+            match last_session {
+            Some(ref last) => { last.label.clone() }
+            None => { "standard".to_string() }
+            }
+        }
         };
 
-        let regex = Regex::new("^[-_ a-zA-Z0-9]+$").unwrap();
-        if !regex.is_match(&label) {
-            println!("Illegal characters in label name");
-            std::process::exit(1);
+        if !is_valid_label(&label) {
+            fail(ForestError::Usage("Illegal characters in label name".to_string()));
+        }
+
+        // A tree given by the user is grown once, without adding it to the collection
+        let given = matches.opt_present("tree-string") || matches.opt_present("tree-file");
+        if given && (matches.opt_present("t") || timer_only) {
+            fail(ForestError::Usage("A tree string or file can't be used with --tree or --timer-only".to_string()));
         }
 
+        let given_tree = match read_given_tree(matches.opt_str("tree-string"), matches.opt_str("tree-file")) {
+        Ok(x) => { x }
+        Err(x) => { fail(x); }
+        };
+
+        // Without a tree name, the default tree is grown
         let tree_name = match matches.opt_str("t") {
-        Some(x) => { x }
-        None    => { "default-1".to_string() }
+        Some(x) => { Some(x) }
+        None    => { last_session.as_ref().map(|last| last.tree.name.clone()) }
         };
     
         let mut chosen_tree: Option<Tree> = None;
 
-        for tree in trees.collection {
-            if tree.name == tree_name {
-                chosen_tree = Some(tree);
+        // A timer doesn't need a tree, so it is grown with a blank one that is never shown
+        if timer_only {
+            chosen_tree = Some(Tree::default());
+        } else if given_tree.is_some() {
+            chosen_tree = given_tree;
+        } else if let Some(name) = &tree_name {
+            for tree in &trees.collection {
+                if &tree.name == name {
+                    chosen_tree = Some(tree.clone());
+                }
+            }
+        } else {
+            chosen_tree = trees.default_tree().cloned();
+        }
+
+        // The last tree may have been erased since, but it's saved with the session
+        if let Some(last) = last_session {
+            if chosen_tree.is_none() && Some(&last.tree.name) == tree_name.as_ref() {
+                chosen_tree = Some(last.tree);
             }
         }
 
         let chosen_tree = match chosen_tree {
         Some(x) => { x }
+        None if trees.collection.is_empty() => {
+            fail(ForestError::Runtime("Your tree collection is empty, so there is no tree to grow. Create one with `import -c`, or import some with `import -f FILE`".to_string()));
+        }
         None    => {
-            println!("Failed to find chosen tree!");
-            return;
+            fail(ForestError::Runtime("Failed to find chosen tree!".to_string()));
         }
         };
         
-        let tree_cost = chosen_tree.cost();
-        if growth_time.to_min() < tree_cost {
-            println!("This tree is too expsensive. It needs more time ({:02}:{:02}) to grow.", tree_cost / 60, tree_cost % 60);
-            return;
+        let min_session = match config.get_number("min_session_minutes") {
+        Ok(x) => { x.unwrap_or(0) }
+        Err(x) => {
+            fail(ForestError::Runtime(x.to_string()));
         }
+        };
 
-        grow_tree(chosen_tree, label, growth_time, nogui);
-    }
-    "import" => { // TODO: display loaded trees data
-        let opts = build_import_opts();
-        
-        let matches = opts.parse(&args[2..]).unwrap();
-        
-        // Display help menu
-        if matches.opt_present("h") {
-            print_import_usage(&program, opts);
-            return;
+        // The duration of the stopwatch is only known when it stops, so grow_tree checks it
+        if !timer_only && !stopwatch {
+            if let Err(x) = check_min_session(growth_time.to_min(), min_session) {
+                fail(ForestError::Runtime(format!("{}.", x)));
+            }
         }
-        
-        let duped = matches.opt_present("n");
-        
+
+        let tree_cost = chosen_tree.cost();
+        if !timer_only && !stopwatch && growth_time.to_min() < tree_cost {
+            fail(ForestError::Runtime(format!("This tree is too expsensive. It needs more time ({:02}:{:02}) to grow.", tree_cost / 60, tree_cost % 60)));
+        }
+
+        // Keep the lock until the tree is grown
+        let lock = match LockFile::acquire(GROW_LOCK) {
+        Ok(x) => { Some(x) }
+        Err(LockError::Held(pid)) => {
+            if !matches.opt_present("a") {
+                fail(ForestError::Runtime(format!("Another tree is already growing (pid {}). Use --allow-concurrent to grow anyway.", pid)));
+            }
+            log::warn(&format!("Warning: another tree is already growing (pid {})", pid));
+            None
+        }
+        Err(LockError::Busy) => {
+            if !matches.opt_present("a") {
+                fail(ForestError::Runtime("Another tree is starting to grow. Use --allow-concurrent to grow anyway.".to_string()));
+            }
+            log::warn("Warning: another tree is starting to grow");
+            None
+        }
+        Err(LockError::Io(x)) => {
+            fail(ForestError::Runtime(x.to_string()));
+        }
+        };
+
+        // Only the session holding the lock can resume or replace the saved session
+        let unfinished = if lock.is_some() && !timer_only && !stopwatch {
+            match Session::load() {
+            Ok(x) => { x }
+            Err(x) => {
+                log::warn(&format!("{}; starting a new session", x));
+                None
+            }
+            }
+        } else {
+            None
+        };
+
+        let resumed = match unfinished {
+        Some(x) if termion::is_tty(&io::stdin()) => {
+            println!("An unfinished session was found: {} ({}), {} left.", x.tree.name, x.label,
+                     format_remaining(x.remaining(), time_format));
+            if confirm("Resume it?") { Some(x) } else { None }
+        }
+        Some(x) => {
+            log::warn(&format!("Warning: the unfinished session of {} was replaced", x.tree.name));
+            None
+        }
+        None => { None }
+        };
+
+        let (chosen_tree, label, growth_time, grown) = match resumed {
+        Some(x) => { (x.tree, x.label, GrowthTime { h: x.duration / 60, m: x.duration % 60 }, x.grown) }
+        None => { (chosen_tree, label, growth_time, 0) }
+        };
+
+        let quiet = matches.opt_present("q");
+
+        let cancel_messages = if quiet {
+            Vec::new()
+        } else {
+            match config.get("cancel_messages") {
+            Some(x) => { parse_cancel_messages(x) }
+            None => { CANCEL.iter().map(|x| x.to_string()).collect() }
+            }
+        };
+
+        // The killed trees are only recorded if the config or the options say so
+        let record_failures = if matches.opt_present("no-record-failures") {
+            false
+        } else if matches.opt_present("record-failures") {
+            true
+        } else {
+            match config.get_bool("record_failures") {
+            Ok(x) => { x.unwrap_or(false) }
+            Err(x) => {
+                fail(ForestError::Runtime(x.to_string()));
+            }
+            }
+        };
+
+        let notify = if matches.opt_present("no-notify") {
+            false
+        } else if matches.opt_present("notify") {
+            true
+        } else {
+            match config.get_bool("notify") {
+            Ok(x) => { x.unwrap_or(false) }
+            Err(x) => {
+                fail(ForestError::Runtime(x.to_string()));
+            }
+            }
+        };
+
+        // The tree may grow for a long time, so a wrong file is reported now
+        let sound = matches.opt_str("sound");
+        if let Some(x) = &sound {
+            if !Path::new(x).is_file() {
+                fail(ForestError::Runtime(format!("Failed to find the sound file {}", x)));
+            }
+        }
+
+        let options = GrowOptions {
+            nogui,
+            status: !matches.opt_present("s"),
+            status_env: matches.opt_present("status-env"),
+            scale: parse_scale(&matches),
+            time_format,
+            on_start: matches.opt_str("on-start").or_else(|| config.get("on_start").map(|x| x.to_string())),
+            on_complete: matches.opt_str("on-complete").or_else(|| config.get("on_complete").map(|x| x.to_string())),
+            require_hooks: matches.opt_present("require-hooks"),
+            cancel_messages,
+            positive_messages: load_positive_messages(),
+            quiet,
+            record_failures,
+            notify,
+            bell: matches.opt_present("bell"),
+            sound,
+            stats: stats_store(),
+            session: lock.is_some() && !timer_only && !stopwatch,
+            grown,
+            timer_only,
+            stopwatch,
+            min_session,
+        };
+
+        grow_tree(chosen_tree, label, growth_time, options);
+
+        // Release the lock before ending like CTRL+C would have
+        drop(lock);
+        resume_interrupt();
+    }
+    "import" => { // TODO: display loaded trees data
+        let opts = build_import_opts();
+        
+        let matches = parse_args(&opts, sub_args);
+        
+        // Display help menu
+        if matches.opt_present("h") {
+            print_import_usage(&program, opts);
+            return;
+        }
+        
+        if let Some(x) = matches.opt_str("L") {
+            let fs = match fs::read_to_string(&x) {
+            Ok(x) => { x }
+            Err(err) => {
+                fail(ForestError::Runtime(format!("Failed to read {}: {}", x, err)));
+            }
+            };
+
+            if let Err(x) = write_lint(&mut stdout(), &fs) {
+                exit_on_write_error(x);
+            }
+            return;
+        }
+
+        if let Some(x) = matches.opt_str("diff") {
+            let fs = match fs::read_to_string(&x) {
+            Ok(x) => { x }
+            Err(err) => {
+                fail(ForestError::Runtime(format!("Failed to read {}: {}", x, err)));
+            }
+            };
+
+            if let Err(x) = write_diff(&mut stdout(), &trees, &fs, matches.opt_present("preview")) {
+                exit_on_write_error(x);
+            }
+            return;
+        }
+
+        let generate = match matches.opt_str("g") {
+        Some(x) => {
+            match x.parse::<usize>() {
+            Ok(val) => { Some(val) }
+            Err(x) => {
+                fail(ForestError::Usage(format!("Failed to parse generate argument: {}", x)));
+            }
+            }
+        }
+        None => { None }
+        };
+
+        // generated trees all have the same name, so they must be renamed, and so must a copy
+        // that kept the name of the tree it started from
+        let duped = matches.opt_present("n") || generate.is_some() || matches.opt_present("from");
+        
         let write_errors = matches.opt_present("e");
 
         // get the content to import
         let content = if let Some(x) = matches.opt_str("f") {
-            let fs = fs::read_to_string(x).unwrap();
+            let fs = match fs::read_to_string(&x) {
+            Ok(fs) => { fs }
+            Err(err) => { fail(ForestError::Runtime(format!("Failed to read {}: {}", x, err))); }
+            };
+
+            data_lines(&fs).map(|x| { x.to_string() }).collect()
+        } else if let Some(count) = generate {
+            let mut rng = match matches.opt_str("s") {
+            Some(x) => {
+                match x.parse::<u64>() {
+                Ok(seed) => { StdRng::seed_from_u64(seed) }
+                Err(x) => {
+                    fail(ForestError::Usage(format!("Failed to parse seed argument: {}", x)));
+                }
+                }
+            }
+            None => { StdRng::from_entropy() }
+            };
 
-            fs.lines().map(|x| { x.to_string() }).collect()
+            let theme = match matches.opt_str("T") {
+            Some(x) => {
+                match find_theme(&x) {
+                Some(theme) => { theme }
+                None => {
+                    fail(ForestError::Usage(format!("Unknown theme: {}", x)));
+                }
+                }
+            }
+            None => { &THEMES[0] }
+            };
+
+            (0..count).map(|_| { generate_themed_tree(&mut rng, "generated".to_string(), theme).to_string() }).collect()
         } else if matches.opt_present("c") {
-            vec![run_tree_editor().to_string()]
+            let start = match matches.opt_str("from") {
+            Some(name) => {
+                match trees.collection.iter().find(|x| { x.name == name }) {
+                Some(x) => { x.clone() }
+                None => { fail(ForestError::Runtime(format!("There is no tree named {}", name))); }
+                }
+            }
+            None => { Tree::default() }
+            };
+
+            match run_tree_editor(start) {
+            Some(tree) => { vec![tree.to_string()] }
+            None => {
+                println!("The tree was discarded");
+                return;
+            }
+            }
         } else {
             if matches.free.is_empty() {
                 print_import_usage(&program, opts);
-                return;
+                std::process::exit(2);
             }
             matches.free
         };
         
-        let mut loaded: usize = 0;
-        let mut tree_name: Vec<String> = Vec::new();
-
-        for tree in content {
-            let res = trees.add_tree(tree.clone(), duped);
-            match res {
-            Err(x) => {
-                if write_errors {
-                    eprintln!("Failed to add tree: {}", x);
-                }
-            }
-            Ok(x) => {
-                tree_name.push(x.name);
-                loaded = loaded + 1;
-            }
-            }
+        if let Err(x) = write_import(&mut stdout(), &mut io::stderr(), &mut trees, content, duped, write_errors) {
+            exit_on_write_error(x);
         }
-        
-        println!("Loaded {} trees in total:", loaded);
-        for new_name in tree_name {
-            println!("{}", new_name);
+
+        // With --no-lock, another process may have saved trees since they were loaded
+        match trees.merge_from(&FileTrees) {
+        Ok(0) => {}
+        Ok(x) => { log::info(&format!("Kept {} trees saved by another process", x)); }
+        Err(x) => { log::warn(&format!("Failed to read the saved trees again: {}", x)); }
         }
 
-        trees.save()
-            .expect("Failed to save trees");
+        if let Err(x) = trees.save() {
+            fail(ForestError::Runtime(format!("Failed to save trees: {}", x)));
+        }
     }
     "export" => {
         let opts = build_export_opts();
 
-        let matches = opts.parse(&args[2..]).unwrap();
+        let matches = parse_args(&opts, sub_args);
 
         if matches.opt_present("h") {
             print_export_usage(&program, opts);
             return;
         }
     
+        if matches.opt_present("png") {
+            if matches.free.len() != 2 {
+                print_export_usage(&program, opts);
+                std::process::exit(2);
+            }
+
+            let pixel_size = match matches.opt_str("pixel-size") {
+            Some(x) => {
+                match x.parse::<usize>() {
+                Ok(val) if val > 0 => { val }
+                _ => {
+                    fail(ForestError::Usage("Failed to export PNG: the pixel size should be a positive number".to_string()));
+                }
+                }
+            }
+            None => { 16 }
+            };
+
+            let tree = match trees.collection.iter().find(|tree| tree.name == matches.free[0]) {
+            Some(x) => { x }
+            None => {
+                fail(ForestError::Runtime(format!("Failed to find tree {}", matches.free[0])));
+            }
+            };
+
+            export_png(tree, &matches.free[1], pixel_size, !matches.opt_present("no-symbols"));
+            return;
+        }
+
+        if let Some(file_name) = matches.opt_str("C") {
+            let columns = match matches.opt_str("columns") {
+            Some(x) => {
+                match x.parse::<usize>() {
+                Ok(val) if val > 0 => { val }
+                _ => {
+                    fail(ForestError::Usage("Failed to export collage: columns should be a positive number".to_string()));
+                }
+                }
+            }
+            // make the collage roughly square
+            None => { (1..).find(|&x| x * x >= trees.collection.len()).unwrap() }
+            };
+
+            let mut collage: Vec<u8> = Vec::new();
+            write_collage(&mut collage, &trees.collection, columns).expect("Failed to write");
+
+            if let Err(x) = fs::write(file_name, collage) {
+                fail(ForestError::Runtime(format!("Error exporting to file: {}", x)));
+            }
+            return;
+        }
+
         let to_export_trees = matches.free.clone();
         let export_all = matches.opt_present("a");
 
+        let json = match matches.opt_str("format").as_deref() {
+        Some("json") => { true }
+        Some("tree") | None => { false }
+        Some(x) => {
+            fail(ForestError::Usage(format!("Unknown export format: {}; it should be tree or json", x)));
+        }
+        };
+        let write_tree = |tree: &Tree| { if json { tree.to_json() } else { tree.to_string() } };
+
+        // The trees that were found are still exported, and the missing ones are reported after
+        let mut missing: Vec<String> = Vec::new();
         let exported = if matches.opt_present("c") { // we should use the tree editor
-            vec![run_tree_editor().to_string()]
+            match run_tree_editor(Tree::default()) {
+            Some(tree) => { vec![write_tree(&tree)] }
+            None => {
+                println!("The tree was discarded");
+                return;
+            }
+            }
         } else { // we should search for the tree
            if export_all {
                 let mut found: Vec<String> = Vec::new();
                 for tree in &trees.collection {
-                    found.push(tree.to_string());
+                    found.push(write_tree(tree));
                 }
                 found
             } else {
                 if to_export_trees.is_empty() {
                     print_export_usage(&program, opts);
-                    return;
+                    std::process::exit(2);
                 }
 
                 let mut res = Vec::new();
@@ -473,7 +1749,7 @@ fn main() {
                     let mut found: Option<String> = None;
                     for tree in &trees.collection {
                         if tree.name == export_tree {
-                            found = Some(tree.to_string());
+                            found = Some(write_tree(tree));
                         }
                     }
                 
@@ -481,7 +1757,7 @@ fn main() {
                     Some(x) => {
                         res.push(x);
                     }
-                    None => {}
+                    None => { missing.push(export_tree); }
                     }
                 }
                 res
@@ -497,8 +1773,7 @@ fn main() {
 
             let mut file_res = match file {
             Err(x) => { 
-                println!("Error exporting to file: {}", x);
-                std::process::exit(1);
+                fail(ForestError::Runtime(format!("Error exporting to file: {}", x)));
             }
             Ok(x)  => { x }
             };
@@ -514,11 +1789,15 @@ fn main() {
             }
         }
         }
+
+        if !missing.is_empty() {
+            fail(ForestError::Runtime(format!("Failed to find tree {}", missing.join(", "))));
+        }
     }
     "list" => {
         let opts = build_list_opts();
 
-        let matches = opts.parse(&args[2..]).unwrap();
+        let matches = parse_args(&opts, sub_args);
         
         if matches.opt_present("h") {
             print_list_usage(&program, opts);
@@ -528,400 +1807,897 @@ fn main() {
         let draw_trees = !matches.opt_present("n");
         let exportable = matches.opt_present("e");
         
-        let mut head = match matches.opt_str("H") {
+        let head = match matches.opt_str("H") {
         Some(x) => { 
             match x.parse::<usize>() {
-            Ok(val) => { val }
+            Ok(val) => { Some(val) }
             Err(x) => { 
-                println!("Failed to list string: head argument parsing error: {}", x);
-                std::process::exit(1);
+                fail(ForestError::Usage(format!("Failed to list string: head argument parsing error: {}", x)));
             }
             }
         }
-        None =>    { trees.collection.len() }
+        None =>    { None }
         };
 
         let tail = match matches.opt_str("T") {
         Some(x) => { 
             match x.parse::<usize>() {
-            Ok(val) => { val }
+            Ok(val) => { Some(val) }
             Err(x) => { 
-                println!("Failed to list string: tail argument parsing error: {}", x);
-                std::process::exit(1);
+                fail(ForestError::Usage(format!("Failed to list string: tail argument parsing error: {}", x)));
             }
             }
         }
-        None =>    { trees.collection.len() }
+        None =>    { None }
         };
-        
-        let mut trees_order: Vec<usize> = (0..trees.collection.len()).collect();
-       
-        match matches.opt_str("r") {
+
+        let random = match matches.opt_str("r") {
         Some(x) => { 
-            head = match x.parse::<usize>() {
-            Ok(val) => { val }
+            match x.parse::<usize>() {
+            Ok(val) => { Some(val) }
             Err(x) => {
-                println!("Failed to list string: random parsing argument error: {}", x);
-                std::process::exit(1);
+                fail(ForestError::Usage(format!("Failed to list string: random parsing argument error: {}", x)));
+            }
             }
-            };
-            let mut rng = thread_rng();
-            trees_order.shuffle(&mut rng);
         }
-        _ => {}
+        None => { None }
         };
 
+        let seed = match matches.opt_str("s") {
+        Some(x) => { 
+            match x.parse::<u64>() {
+            Ok(val) => { Some(val) }
+            Err(x) => {
+                fail(ForestError::Usage(format!("Failed to list string: seed parsing argument error: {}", x)));
+            }
+            }
+        }
+        None => { None }
+        };
+        
+        let max_cost = match matches.opt_str("m") {
+        Some(x) => {
+            match GrowthTime::from_str(&x) {
+            Ok(val) => { Some(val.to_min()) }
+            Err(x) => {
+                fail(ForestError::Usage(format!("Failed to list string: max cost parsing error: {}", x)));
+            }
+            }
+        }
+        None => { None }
+        };
 
-        for cnt in 0..trees.collection.len() {
-            if cnt < head && cnt >= trees_order.len() - tail {
-                let tree = &trees.collection[trees_order[cnt]];
-                if exportable {
-                    println!("{}", tree.to_string());
-                } else {
-                    println!("{}) {}", trees_order[cnt] + 1, tree.name);
-                    if draw_trees {
-                        for l in 0..5 {
-                            for c in 0..5 {
-                                tree.display_symbol(l, c);
-                            }
-                            
-                            write!(stdout(), "{}{}\n", color::Bg(color::Reset), color::Fg(color::Reset))
-                                .expect("Failed to write");
-                        }
-                    }
-                }
+        // The other filters only see the trees that are cheap enough
+        let candidates = affordable_indices(&trees.collection, max_cost);
+        let trees_order: Vec<usize> = select_indices(candidates.len(), head, tail, random, seed)
+            .into_iter()
+            .map(|idx| candidates[idx])
+            .collect();
+
+        let columns = match matches.opt_str("c") {
+        Some(x) => { 
+            match x.parse::<usize>() {
+            Ok(val) if val > 0 => { Some(val) }
+            _ => {
+                fail(ForestError::Usage("Failed to list string: columns should be a positive number".to_string()));
+            }
+            }
+        }
+        None => { None }
+        };
+
+        let options = ListOptions {
+            draw_trees,
+            exportable,
+            columns,
+            show_cost: !matches.opt_present("C"),
+            scale: parse_scale(&matches),
+            legend: matches.opt_present("L"),
+        };
+
+        // Paging needs the whole output, so render it first if it may be paged
+        if termion::is_tty(&stdout()) && !matches.opt_present("P") {
+            let mut buffer: Vec<u8> = Vec::new();
+            write_list(&mut buffer, &trees.collection, &trees_order, &options).expect("Failed to write");
+            let text = String::from_utf8_lossy(&buffer);
+
+            let (_, height) = terminal_size().unwrap();
+            if matches.opt_present("p") || (height > 0 && text.lines().count() > height as usize) {
+                pager::page(&text);
+            } else {
+                print!("{}", text);
+            }
+        } else {
+            if let Err(x) = write_list(&mut stdout(), &trees.collection, &trees_order, &options) {
+                exit_on_write_error(x);
             }
         }
     }
     
     "stats" => {
         let opts = build_stats_opts();
-        let matches = opts.parse(&args[2..]).unwrap();
+        let matches = parse_args(&opts, sub_args);
         
         if matches.opt_present("h") {
             print_stats_usage(&program, opts);
             return;
         }
-        
-        let mut stats = match get_stats() {
-        Ok(x) => { x }
-        Err(x) => { println!("{}", x); return; }
-        };
 
-        match matches.opt_str("f") {
-        Some(label) => {
-            stats.retain(|x| { x.label == label } )
-        }
-        None => {}
-        }
-
-        match matches.opt_str("t") {
-        Some(t) => {
-            let now = Local::now();
-            
-            t.to_lowercase();
-
-            match t.as_str() {
-            "today" => {
-                stats.retain(|tree| {
-                    let date = Local.timestamp(tree.timestamp, 0);
-                    date.num_days_from_ce() == now.num_days_from_ce()
-                });
-            }
-            "yesterday" => {
-                stats.retain(|tree| {
-                    let date = Local.timestamp(tree.timestamp, 0);
-                    date.num_days_from_ce() + 1 == now.num_days_from_ce()
-                })
-            }
-            "this-week" => {
-                stats.retain(|tree| {
-                    let date = Local.timestamp(tree.timestamp, 0);
-                    date.iso_week().year() == now.iso_week().year() &&
-                    date.iso_week().week() == now.iso_week().week()
-                })
-            }
-            "this-month" => {
-                stats.retain(|tree| {
-                    let date = Local.timestamp(tree.timestamp, 0);
-                    date.year()  == now.year() &&
-                    date.month() == date.month()
-                })
-            }
-            "this-year" => {
-                stats.retain(|tree| {
-                    let date = Local.timestamp(tree.timestamp, 0);
-                    date.year() == now.year()
-                })
+        let store = stats_store();
+
+        if matches.opt_present("merge") {
+            if matches.free.is_empty() {
+                print_stats_usage(&program, opts);
+                std::process::exit(2);
             }
-            _ => {
-                println!("Unknown time period");
-                return;
+
+            let existing = match store.load() {
+            Ok(x) => { x }
+            Err(x) => { fail(ForestError::Runtime(x.to_string())); }
+            };
+
+            let mut incoming: Vec<GrownTree> = Vec::new();
+            for path in &matches.free {
+                match get_stats_from(path) {
+                Ok(x) => { incoming.extend(x); }
+                Err(x) => {
+                    fail(ForestError::Runtime(x.to_string()));
+                }
+                }
             }
+
+            let (merged, added, duplicates) = stats::merge_stats(existing, incoming);
+
+            if let Err(x) = store.save(&merged) {
+                fail(ForestError::Runtime(x.to_string()));
+            }
+            if let Err(x) = State::recompute(&merged).save() {
+                eprintln!("{}", x);
             }
-        }
-        None => {}
-        }
 
-        match matches.opt_str("c") {
-        Some(x) => {
-            let count = x.parse::<usize>();
+            println!("Merged {} files: {} trees added, {} duplicates skipped", matches.free.len(), added, duplicates);
+            return;
+        }
 
-            let count = match count {
+        if matches.opt_present("dedupe") {
+            let stats = match store.load() {
             Ok(x) => { x }
-            Err(x) => {
-                println!("Failed to parse count argument: {}", x);
+            Err(x) => { fail(ForestError::Runtime(x.to_string())); }
+            };
+
+            let (unique, removed) = stats::dedupe_stats(stats);
+
+            if matches.opt_present("dry-run") {
+                println!("{} duplicates would be removed", removed);
                 return;
             }
+
+            if removed > 0 {
+                if let Err(x) = store.save(&unique) {
+                    fail(ForestError::Runtime(x.to_string()));
+                }
+                if let Err(x) = State::recompute(&unique).save() {
+                    eprintln!("{}", x);
+                }
+            }
+
+            println!("{} duplicates removed", removed);
+            return;
+        }
+
+        if matches.opt_present("recompute") {
+            let stats = match store.load() {
+            Ok(x) => { x }
+            Err(x) => { fail(ForestError::Runtime(x.to_string())); }
             };
-        
-            if count < stats.len() {
-                stats.rotate_right(count);
-                stats.truncate(count);
+
+            let state = State::recompute(&stats);
+            if let Err(x) = state.save() {
+                fail(ForestError::Runtime(x.to_string()));
             }
+            println!("Total time grown: {:02}:{:02}", state.total_minutes / 60, state.total_minutes % 60);
+            return;
+        }
+
+        if matches.opt_present("total") {
+            match State::load() {
+            Ok(state) => { println!("Total time grown: {:02}:{:02}", state.total_minutes / 60, state.total_minutes % 60); }
+            Err(x) => { fail(ForestError::Runtime(format!("{}; use --recompute to fix it", x))); }
+            }
+            return;
         }
-        None => {}
+
+        let stats_file = matches.opt_str("file");
+        if let Some(path) = &stats_file {
+            if !Path::new(path).is_file() {
+                fail(ForestError::Runtime(format!("The stats file {} does not exist", path)));
+            }
         }
-        
-        match matches.opt_str("g") {
+
+        let period = matches.opt_str("t");
+
+        let count = match matches.opt_str("c") {
         Some(x) => {
-            x.to_lowercase();
+            match x.parse::<usize>() {
+            Ok(x) => { Some(x) }
+            Err(x) => {
+                fail(ForestError::Usage(format!("Failed to parse count argument: {}", x)));
+            }
+            }
+        }
+        None => { None }
+        };
 
-            let (n, m) = if x == "whole" {
-                // Try to make the grid as big as possible
-                let (width, height) = terminal_size().unwrap();
-                let (width, height) = (width as usize, height as usize);
+        let day_start_hour = match config.get_number("day_start_hour") {
+        Ok(Some(x)) if x < 24 => { x as u32 }
+        Ok(None) => { 0 }
+        _ => {
+            fail(ForestError::Runtime("Invalid value for day_start_hour in config: it should be an hour between 0 and 23".to_string()));
+        }
+        };
 
-                (height / 6, (width + 1) / 6)
-            } else {
-                let numbers: Vec<&str> = x.split("x").collect();
-                
-                if numbers.len() != 2 {
-                    println!("Invalid grid size format");
-                    return;
-                }
-            
-                let n = numbers[0].parse::<usize>();
-                let n = match n {
-                Ok(n)  => { n }
-                Err(x) => {
-                    println!("Invalid grid size: {}", x);
-                    return;
-                }
-                };
-                
-                let m = numbers[1].parse::<usize>();
-                let m = match m {
-                Ok(m)  => { m }
-                Err(x) => {
-                    println!("Invalid grid size: {}", x);
-                    return;
-                }
-                };
-                
-                (n, m)
+        let filter = StatsFilter {
+            label: matches.opt_str("f"),
+            period,
+            count,
+            day_start_hour,
+            killed: matches.opt_present("kills"),
+        };
+
+        let format = match matches.opt_str("F") {
+        Some(x) => { x }
+        None    => { "%d-%m-%Y %H:%M".to_string() }
+        };
+
+        let output = matches.opt_str("output").unwrap_or("text".to_string()).to_lowercase();
+        if output != "text" && output != "csv" && output != "json" {
+            fail(ForestError::Usage(format!("Unknown output format: {}; it should be text, csv or json", output)));
+        }
+
+        let view = if matches.opt_present("kills") {
+            StatsView::Kills(format)
+        } else if let Some(x) = matches.opt_str("g") {
+            StatsView::Grid(x, parse_scale(&matches))
+        } else if let Some(time_option) = matches.opt_str("G") {
+            StatsView::Graph(time_option, day_start_hour)
+        } else if let Some(x) = matches.opt_str("top") {
+            let count = match x.parse::<usize>() {
+            Ok(x) => { x }
+            Err(x) => {
+                fail(ForestError::Usage(format!("Failed to parse top argument: {}", x)));
+            }
             };
-            
-            let mut grid_pos: Vec<(usize, usize)> = Vec::new();
-            let mut grid: Vec<Vec<Option<&Tree>>> = vec![vec![None; m]; n];
 
-            for i in 0..n {
-                for j in 0..m {
-                    grid_pos.push((i, j));
-                }
+            let by = matches.opt_str("by").unwrap_or("label".to_string()).to_lowercase();
+            if by != "label" && by != "tree" {
+                fail(ForestError::Usage(format!("Unknown ranking: {}; it should be label or tree", by)));
             }
-            
-            let mut rng = thread_rng();
-            grid_pos.shuffle(&mut rng);
 
-            for (pos, tree) in stats.iter().enumerate() {
-                if pos < grid_pos.len() {
-                    grid[grid_pos[pos].0][grid_pos[pos].1] = Some(&tree.tree);
+            StatsView::Top(count, by)
+        } else if matches.opt_present("daily-table") {
+            StatsView::DailyTable(day_start_hour, output)
+        } else if matches.opt_present("avg") {
+            StatsView::Average
+        } else if let Some(unit) = matches.opt_str("compare") {
+            StatsView::Compare(unit.to_lowercase())
+        } else if matches.opt_present("goals") {
+            let mut goals: Vec<Goal> = Vec::new();
+
+            for (key, period) in [("daily_goal_minutes", "today"), ("weekly_goal_minutes", "this-week"), ("monthly_goal_minutes", "this-month")] {
+                match config.get_number(key) {
+                Ok(Some(minutes)) => { goals.push(Goal { period: period.to_string(), minutes }); }
+                Ok(None) => {}
+                Err(x) => {
+                    fail(ForestError::Runtime(x.to_string()));
+                }
                 }
             }
 
-            for i in 0..6*n-1 {
-                for j in 0..6*m-1 {
-                    if i % 6 == 5 {
-                        if j % 6 == 5 {    
-                            write!(stdout(), "+").expect("Failed to write");
-                        } else {
-                            write!(stdout(), "-").expect("Failed to write");
-                        }
-                    } else if j % 6 == 5 {
-                        write!(stdout(), "|").expect("Failed to write");
-                    } else {
-                        let tree_line = i / 6;
-                        let tree_col  = j / 6;
-                        
-                        match grid[tree_line][tree_col] {
-                        Some(tree) => { tree.display_symbol(i % 6, j % 6); }
-                        None => {       write!(stdout(), " ").expect("Failed to write"); }
-                        }
+            StatsView::Goals(goals, day_start_hour)
+        } else if output == "csv" {
+            StatsView::Csv
+        } else if matches.opt_present("summary-only") {
+            StatsView::Summary
+        } else if output == "json" {
+            fail(ForestError::Usage("The json output can only be used with --daily-table".to_string()));
+        } else {
+            StatsView::List(format)
+        };
 
-                        write!(stdout(), "{}", termion::color::Fg(termion::color::Reset))
-                            .expect("Failed to write");
-                        write!(stdout(), "{}", termion::color::Bg(termion::color::Reset))
-                            .expect("Failed to write");
-                    }
-                }
-                write!(stdout(), "\n").expect("Failed to write");
+        if let Some(file_name) = matches.opt_str("tsv") {
+            let mut stats = match stats::load_stats(&*store, stats_file.as_deref()) {
+            Ok(x) => { x }
+            Err(x) => { fail(ForestError::Runtime(x.to_string())); }
+            };
+
+            if let Err(x) = filter.apply(&mut stats) {
+                fail(ForestError::Runtime(x.to_string()));
             }
 
+            if let Err(x) = fs::write(file_name, stats::to_tsv(&stats, &Local)) {
+                fail(ForestError::Runtime(format!("Error exporting to file: {}", x)));
+            }
             return;
         }
-        None => {}
-        }
-        
-        match matches.opt_str("G") {
-        Some(time_option) => {
-            let (width, height) = terminal_size().unwrap();
-            let (width, height) = (width as usize, height as usize);
-
-            let cnt_strips = (height - 1) / 3;
-            
-            let (mut strips, mut last_time) = match time_option.as_str() {
-            "daily"   => {
-                 let mut data_grouped: Vec<(DateTime<Local>, u64)> = Vec::new();
-                 for(key, tree) in &stats.into_iter().group_by(|tree| Local.timestamp(tree.timestamp, 0).duration_trunc(Duration::days(1)).unwrap()) {
-                    data_grouped.push((key, tree.map(|tree| tree.duration).sum()));
-                 }
-
-                 (data_grouped, Local::now().duration_trunc(Duration::days(1)).unwrap())
-            }
-            "weekly"  => {
-                 let mut data_grouped: Vec<(DateTime<Local>, u64)> = Vec::new();
-                 for(key, tree) in &stats.into_iter().group_by(|tree| Local.timestamp(tree.timestamp, 0).duration_trunc(Duration::weeks(1)).unwrap()) {
-                    data_grouped.push((key, tree.map(|tree| tree.duration).sum()));
-                 }
-
-                 (data_grouped, Local::now().duration_trunc(Duration::weeks(1)).unwrap())
-            }
-            "monthly" => {
-                 let mut data_grouped: Vec<(DateTime<Local>, u64)> = Vec::new();
-                 for(key, tree) in &stats.into_iter().group_by(|tree| Local.ymd(Local.timestamp(tree.timestamp, 0).year(), Local.timestamp(tree.timestamp, 0).month(), 1)) {
-                    data_grouped.push((key.and_hms(0, 0, 0), tree.map(|tree| tree.duration).sum()));
-                 }
-
-                 (data_grouped, Local.ymd(Local::now().year(), Local::now().month(), 1).and_hms(0, 0, 0))
-            }
-            "yearly"  => {
-                 let mut data_grouped: Vec<(DateTime<Local>, u64)> = Vec::new();
-                 for(key, tree) in &stats.into_iter().group_by(|tree| Local.ymd(Local.timestamp(tree.timestamp, 0).year(), 1, 1)) {
-                    data_grouped.push((key.and_hms(0, 0, 0), tree.map(|tree| tree.duration).sum()));
-                 }
-
-                 (data_grouped, Local.ymd(Local::now().year(), 1, 1).and_hms(0, 0, 0))
-            }
-            _ => {
-                println!("Unknown time frame option");
-                return;
-            }
-            };
 
-            let mut strips_final: Vec<(String, u64)> = Vec::new();
-
-            for _ in 0..cnt_strips {
-                let date_format = match time_option.as_str() {
-                "daily" | "weekly" => { format!("{}", last_time.format("%d-%m")) }
-                "monthly" => { format!("{}", last_time.format("%m")) }
-                "yearly" => { format!("{}", last_time.format("%y")) }
-                _ => { panic!("Unexpected case"); }
-                };
-
-                match strips.pop() {
-                Some(x) => {
-                    if last_time == x.0 {
-                        strips_final.push((date_format, x.1));
-                    } else {
-                        strips_final.push((date_format, 0));
-                        strips.push(x);
-                    }
-                }
-                None => {
-                    strips_final.push((date_format, 0));
-                }
+        if matches.opt_present("w") {
+            let refresh = match matches.opt_str("w") {
+            Some(x) => {
+                match x.parse::<u64>() {
+                Ok(x) if x > 0 => { x }
+                _ => {
+                    fail(ForestError::Usage("Failed to parse watch argument: the refresh interval should be a positive number of seconds".to_string()));
                 }
-            
-                last_time = last_time - Duration::days(1);
-                last_time = match time_option.as_str() {
-                "daily" => { last_time }
-                "weekly" => { last_time.duration_trunc(Duration::weeks(1)).unwrap() }
-                "monthly" => { Local.ymd(last_time.year(), last_time.month(), 1).and_hms(0, 0, 0) }
-                "yearly" => { Local.ymd(last_time.year(), 1, 1).and_hms(0, 0, 0) }
-                _ => { panic!("Unexpected case"); }
                 }
             }
+            None => { 5 }
+            };
 
-            strips_final.reverse();
-            let mut max_time = 1;
-            for stat in &strips_final {
-                max_time = cmp::max(max_time, stat.1);
+            if let Err(x) = stats::watch(&filter, &view, refresh, &*store, stats_file.as_deref()) {
+                fail(ForestError::Runtime(x.to_string()));
             }
+            return;
+        }
+        
+        let mut stats = match stats::load_stats(&*store, stats_file.as_deref()) {
+        Ok(x) => { x }
+        Err(x) => { fail(ForestError::Runtime(x.to_string())); }
+        };
 
-            let max_width = width - 1 - strips_final[0].0.len();
+        if let Err(x) = filter.apply(&mut stats) {
+            fail(ForestError::Runtime(x.to_string()));
+        }
 
-            for stat in &strips_final {
-                write!(stdout(), "\n{}|", stat.0).expect("Failed to write");
-                write!(stdout(), "{}", color::Bg(color::Rgb(0, 0, 0))).expect("Failed to write");
-                let ammount = (max_width as u64) * stat.1 / max_time;
-                for _ in 0..ammount {
-                    write!(stdout(), " ").expect("Failed to write");
-                }
-                write!(stdout(), "{}", color::Bg(color::Reset)).expect("Failed to write");
-                write!(stdout(), "\n\n").expect("Failed to write");
+        if let Err(x) = stats::render(&mut stdout(), &stats, &view) {
+            if x.kind() == ErrorKind::InvalidInput {
+                fail(ForestError::Runtime(x.to_string()));
             }
+            exit_on_write_error(x);
+        }
+    }
 
+    "status" => {
+        let opts = build_status_opts();
+        let matches = parse_args(&opts, sub_args);
+
+        if matches.opt_present("h") {
+            print_status_usage(&program, opts);
             return;
         }
-        None => {}
-        }
 
-        let format = match matches.opt_str("F") {
-        Some(x) => { x }
-        None    => { "%d-%m-%Y %H:%M".to_string() }
+        let status = match GrowStatus::load() {
+        Ok(x) => { x }
+        Err(x) => { fail(ForestError::Runtime(x.to_string())); }
         };
 
-        for tree in stats {
-            println!("{} | {} | {:02}:{:02}", tree.label, Local.timestamp(tree.timestamp, 0).format(&format), tree.duration / 60, tree.duration % 60);
+        if matches.opt_present("shell") {
+            match status {
+            Some(status) if !status.is_stale(Local::now().timestamp()) => {
+                print!("{}", shell_exports(Some(&status)));
+                return;
+            }
+            Some(_) => { GrowStatus::remove(); }
+            None => {}
+            }
+            print!("{}", shell_exports(None));
+            return;
+        }
+
+        match status {
+        Some(status) => {
+            if status.is_stale(Local::now().timestamp()) {
+                println!("The last session (pid {}) is not running anymore", status.pid);
+                GrowStatus::remove();
+                return;
+            }
+
+            let remaining = status.remaining;
+            // A timer is growing when there is no tree
+            let growing = if status.tree.is_empty() { "Timer".to_string() } else { format!("Growing {}", status.tree) };
+            println!("{} ({}): {:02}:{:02}:{:02} {}{}", growing, status.label,
+                     remaining / 3600, remaining / 60 % 60, remaining % 60,
+                     if status.stopwatch { "elapsed" } else { "left" },
+                     if status.paused { " (paused)" } else { "" });
+        }
+        None => {
+            println!("No tree is growing right now");
+        }
         }
     }
 
     "erase" => {
         let opts = build_erase_opts();
-        let matches = opts.parse(&args[2..]).unwrap();
+        let matches = parse_args(&opts, sub_args);
     
         if matches.opt_present("h") {
             print_erase_usage(&program, opts);
             return;
         }
         
-        if matches.free.is_empty() {
+        if matches.free.is_empty() && !matches.opt_present("m") {
             print_erase_usage(&program, opts);
-            return;
+            std::process::exit(2);
+        }
+
+        // The other trees are still erased, and the errors are reported after saving them
+        let mut errors: Vec<String> = Vec::new();
+        let mut to_erase: Vec<String> = Vec::new();
+        for name in &matches.free {
+            // The built-in trees are added back every time the collection is loaded
+            if is_builtin(name) {
+                errors.push(format!("{} is a built-in tree, so it can't be erased", name));
+            } else {
+                to_erase.push(name.clone());
+            }
+        }
+
+        if let Some(pattern) = matches.opt_str("m") {
+            let (mut matched, skipped) = trees.matching_names(&pattern);
+            for name in skipped {
+                log::warn(&format!("Skipping built-in tree {}", name));
+            }
+
+            if matched.is_empty() {
+                println!("No trees match {}", pattern);
+                return;
+            }
+
+            println!("This will erase:");
+            for name in &matched {
+                println!("  {}", name);
+            }
+
+            if matches.opt_present("dry-run") {
+                return;
+            }
+
+            if !matches.opt_present("y") && !confirm("Are you sure?") {
+                println!("Nothing was erased");
+                return;
+            }
+
+            to_erase.append(&mut matched);
         }
 
-        for to_erase in matches.free {
-            trees.collection.retain(|tree| { tree.name != to_erase } );
+        for name in to_erase {
+            if !trees.erase_tree(&name) {
+                errors.push(format!("There is no tree named {}", name));
+            }
         }
     
         match trees.save() {
-        Err(x) => { println!("Failed to save trees: {}", x); return; }
+        Err(x) => { fail(ForestError::Runtime(format!("Failed to save trees: {}", x))); }
         _ => {}
         }
+
+        if !errors.is_empty() {
+            fail(ForestError::Runtime(errors.join("; ")));
+        }
     }
 
-    _ => {
-        let matches = default_opts.parse(&args[1..]).unwrap();
+    "edit" => {
+        let opts = build_edit_opts();
+        let matches = parse_args(&opts, sub_args);
 
         if matches.opt_present("h") {
-            print_whole_usage(&program, default_opts);
+            print_edit_usage(&program, opts);
             return;
-        } else if matches.opt_present("v") {
-            print_version(&program);
+        }
+
+        if matches.free.len() != 1 {
+            print_edit_usage(&program, opts);
+            std::process::exit(2);
+        }
+
+        let name = &matches.free[0];
+
+        // The built-in trees are added back every time the collection is loaded
+        if is_builtin(name) {
+            fail(ForestError::Runtime(format!("{} is a built-in tree, so it can't be edited; use import -c --from {} to start a new tree from it", name, name)));
+        }
+
+        let tree = match trees.collection.iter().find(|x| { &x.name == name }) {
+        Some(x) => { x.clone() }
+        None => { fail(ForestError::Runtime(format!("There is no tree named {}", name))); }
+        };
+
+        let edited = match run_tree_editor(tree) {
+        Some(x) => { x }
+        None => {
+            println!("The tree was discarded");
             return;
         }
+        };
+
+        let new_name = edited.name.clone();
+        if let Err(x) = trees.replace_tree(name, edited) {
+            fail(ForestError::Runtime(format!("Failed to save {}: {}", new_name, x)));
+        }
+
+        if let Err(x) = trees.save() {
+            fail(ForestError::Runtime(format!("Failed to save trees: {}", x)));
+        }
+
+        println!("Saved {}", new_name);
+    }
+
+    "reset" => {
+        let opts = build_reset_opts();
+        let matches = parse_args(&opts, sub_args);
+
+        if matches.opt_present("h") {
+            print_reset_usage(&program, opts);
+            return;
+        }
+
+        let kind = if matches.opt_present("t") {
+            ResetKind::Trees
+        } else if matches.opt_present("s") {
+            ResetKind::Stats
+        } else {
+            ResetKind::All
+        };
+
+        let to_remove = match data_dir() {
+        Ok(x) => { existing_files(&x, &reset_files(kind)) }
+        Err(x) => {
+            fail(ForestError::Runtime(x.to_string()));
+        }
+        };
+
+        if to_remove.is_empty() {
+            println!("Nothing to remove");
+            return;
+        }
+
+        println!("This will remove:");
+        for path in &to_remove {
+            println!("  {}", path);
+        }
+
+        if !matches.opt_present("y") && !confirm("Are you sure?") {
+            println!("Nothing was removed");
+            return;
+        }
+
+        for path in &to_remove {
+            match fs::remove_file(path) {
+            Ok(_) => { println!("Removed {}", path); }
+            Err(x) => { eprintln!("Failed to remove {}: {}", path, x); }
+            }
+        }
+    }
+
+    "backup" => {
+        let opts = build_backup_opts();
+        let matches = parse_args(&opts, sub_args);
+
+        if matches.opt_present("h") {
+            print_backup_usage(&program, opts);
+            return;
+        }
+
+        if matches.free.len() != 1 {
+            print_backup_usage(&program, opts);
+            std::process::exit(2);
+        }
+
+        let content = match backup::create_backup() {
+        Ok(x) => { x }
+        Err(x) => {
+            fail(ForestError::Runtime(x.to_string()));
+        }
+        };
+
+        if let Err(x) = fs::write(&matches.free[0], content) {
+            fail(ForestError::Runtime(format!("Error writing backup: {}", x)));
+        }
+    }
+
+    "restore" => {
+        let opts = build_restore_opts();
+        let matches = parse_args(&opts, sub_args);
+
+        if matches.opt_present("h") {
+            print_restore_usage(&program, opts);
+            return;
+        }
+
+        if matches.free.len() != 1 {
+            print_restore_usage(&program, opts);
+            std::process::exit(2);
+        }
+
+        let files = match fs::read_to_string(&matches.free[0]) {
+        Ok(x) => { backup::parse_backup(&x) }
+        Err(x) => { Err(format!("Failed to read backup: {}", x)) }
+        };
+
+        let files = match files {
+        Ok(x) => { x }
+        Err(x) => {
+            fail(ForestError::Runtime(x.to_string()));
+        }
+        };
+
+        println!("This will replace:");
+        for (name, _) in &files {
+            println!("  {}", name);
+        }
+
+        if !matches.opt_present("y") && !confirm("Are you sure?") {
+            println!("Nothing was restored");
+            return;
+        }
+
+        if let Err(x) = backup::restore_backup(&files) {
+            fail(ForestError::Runtime(x.to_string()));
+        }
+
+        // The backup may not hold the state, so make sure it matches the restored stats
+        if let Err(x) = State::recompute(&stats_store().load().unwrap_or_default()).save() {
+            eprintln!("{}", x);
+        }
+
+        println!("Restored {} files", files.len());
+    }
+    "paths" => {
+        let opts = build_paths_opts();
+        let matches = parse_args(&opts, sub_args);
+
+        if matches.opt_present("h") {
+            print_paths_usage(&program, opts);
+            return;
+        }
+
+        let dir = match data_dir() {
+        Ok(x) => { x }
+        Err(x) => {
+            fail(ForestError::Runtime(format!("Failed to find the data directory: {}", x)));
+        }
+        };
+
+        if let Err(x) = write_paths(&mut stdout(), &dir) {
+            exit_on_write_error(x);
+        }
+    }
+    "doctor" => {
+        let opts = build_doctor_opts();
+        let matches = parse_args(&opts, sub_args);
+
+        if matches.opt_present("h") {
+            print_doctor_usage(&program, opts);
+            return;
+        }
+
+        if matches.opt_present("fix") {
+            let repairs = match doctor::repair() {
+            Ok(x) => { x }
+            Err(x) => {
+                fail(ForestError::Runtime(format!("Failed to repair the data: {}", x)));
+            }
+            };
+
+            for repair in &repairs {
+                for (line, text) in &repair.removed {
+                    println!("removed\t{}\tline {}: {}", repair.name, line, text);
+                }
+                println!("backup\t{}\t{}", repair.name, repair.backup);
+            }
+
+            // The repaired stats may have lost trees, so the total is computed again
+            if repairs.iter().any(|x| x.name == "stats.conf") {
+                if let Err(x) = State::recompute(&stats_store().load().unwrap_or_default()).save() {
+                    eprintln!("{}", x);
+                }
+            }
+        }
+
+        let checks = doctor::diagnose();
+        let (mut warnings, mut problems) = (0, 0);
+        for check in &checks {
+            let health = match check.health {
+            Health::Ok => { "ok" }
+            Health::Warning => { warnings += 1; "warning" }
+            Health::Problem => { problems += 1; "problem" }
+            };
+            println!("{}\t{}\t{}", health, check.subject, check.detail);
+        }
+
+        println!("summary\t{}\t{}", warnings, problems);
+        if problems > 0 {
+            std::process::exit(1);
+        }
+    }
+
+    _ => {
+        print_whole_usage(&program, default_opts);
+        std::process::exit(2);
     }
     }
 }
 
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::io::Read;
+    use std::os::unix::process::ExitStatusExt;
+    use std::process::{Command, Stdio};
+    use crate::testing::{grown, temp_dir, tree, tree_line};
+    use crate::tree::write_trees;
+
+    /// Set to the subcommand whose output is written by `closed_pipe_child`.
+    const CHILD_ENV: &str = "RUSTY_FOREST_PIPE_CHILD";
+
+    /// Write a lot of output like the subcommand from CHILD_ENV does. This only runs in the
+    /// child processes started by `closed_pipe_ends_the_output_quietly`.
+    #[test]
+    fn closed_pipe_child() {
+        let subcommand = match env::var(CHILD_ENV) {
+        Ok(x) => { x }
+        Err(_) => { return; }
+        };
+
+        reset_sigpipe();
+        let collection: Vec<Tree> = (0..2000).map(|x| tree(&x.to_string())).collect();
+        let indices: Vec<usize> = (0..collection.len()).collect();
+
+        let res = match subcommand.as_str() {
+        "list" => {
+            let options = ListOptions { draw_trees: true, exportable: false, columns: None, show_cost: true, scale: 1, legend: false };
+            write_list(&mut stdout(), &collection, &indices, &options)
+        }
+        "export" => {
+            for tree in &collection {
+                println!("{}", tree.to_string());
+            }
+            Ok(())
+        }
+        _ => {
+            let stats: Vec<GrownTree> = (0..2000).map(|x| grown("work", 25, x)).collect();
+            stats::render(&mut stdout(), &stats, &StatsView::List("%Y-%m-%d %H:%M".to_string()))
+        }
+        };
+
+        if let Err(x) = res {
+            exit_on_write_error(x);
+        }
+    }
+
+    #[test]
+    fn closed_pipe_ends_the_output_quietly() {
+        for subcommand in ["list", "export", "stats"] {
+            let mut child = Command::new(env::current_exe().unwrap())
+                .args(["--exact", "tests::closed_pipe_child", "--nocapture", "--test-threads=1"])
+                .env(CHILD_ENV, subcommand)
+                .stdout(Stdio::piped())
+                .stderr(Stdio::piped())
+                .spawn()
+                .unwrap();
+
+            // Read the beginning, then stop reading like `head` does
+            let mut start = [0; 100];
+            child.stdout.take().unwrap().read_exact(&mut start).unwrap();
+
+            let output = child.wait_with_output().unwrap();
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            assert_eq!(output.status.signal(), Some(libc::SIGPIPE), "{}: {}", subcommand, stderr);
+            assert!(!stderr.contains("panicked"), "{}: {}", subcommand, stderr);
+        }
+    }
+
+    /// Set to the arguments run by `cli_child`, separated by tabs.
+    const CLI_ENV: &str = "RUSTY_FOREST_CLI_CHILD";
+
+    /// Run the program with the arguments from CLI_ENV. This only runs in the child
+    /// processes started by `run_cli`.
+    #[test]
+    fn cli_child() {
+        let args = match env::var(CLI_ENV) {
+        Ok(x) => { x }
+        Err(_) => { return; }
+        };
+
+        let args = std::iter::once("rusty-forest").chain(args.split('\t')).map(|x| x.to_string()).collect();
+        run(args);
+    }
+
+    /// Run the program with the given arguments in a child process.
+    fn run_args(args: &[&str]) -> std::process::Output {
+        Command::new(env::current_exe().unwrap())
+            .args(["--exact", "tests::cli_child", "--nocapture", "--test-threads=1"])
+            .env(CLI_ENV, args.join("\t"))
+            .stdin(Stdio::null())
+            .output()
+            .unwrap()
+    }
+
+    /// Run a subcommand with its own data directory, and get its exit code and stderr.
+    fn run_cli(dir: &str, args: &[&str]) -> (Option<i32>, String) {
+        let mut all = vec!["--no-onboarding", "--dir", dir];
+        all.extend_from_slice(args);
+
+        let output = run_args(&all);
+        (output.status.code(), String::from_utf8_lossy(&output.stderr).into_owned())
+    }
+
+    #[test]
+    fn subcommands_exit_with_the_kind_of_error() {
+        let dir = temp_dir("main-exit-codes");
+
+        let (code, stderr) = run_cli(&dir, &["stats", "-c", "abc"]);
+        assert_eq!(code, Some(2), "{}", stderr);
+        assert!(stderr.contains("Failed to parse count argument"));
+
+        for (args, message) in [(["edit", "nosuch"], "There is no tree named nosuch"),
+                                (["erase", "nosuch"], "There is no tree named nosuch"),
+                                (["erase", "default-1"], "default-1 is a built-in tree"),
+                                (["export", "nosuch"], "Failed to find tree nosuch")] {
+            let (code, stderr) = run_cli(&dir, &args);
+            assert_eq!(code, Some(1), "{:?}: {}", args, stderr);
+            assert!(stderr.contains(message), "{:?}: {}", args, stderr);
+        }
+
+        let (code, stderr) = run_cli(&dir, &["export", "default-1"]);
+        assert_eq!(code, Some(0), "{}", stderr);
+    }
+
+    #[test]
+    fn looking_at_a_missing_directory_does_not_create_it() {
+        let dir = format!("{}/missing", temp_dir("main-onboarding"));
+
+        for subcommand in LOOK_ONLY_SUBCOMMANDS {
+            let output = run_args(&["--dir", &dir, subcommand]);
+            assert!(!String::from_utf8_lossy(&output.stderr).contains("Welcome"), "{}", subcommand);
+            assert!(!std::path::Path::new(&dir).exists(), "{}", subcommand);
+        }
+
+        let output = run_args(&["--dir", &dir, "doctor"]);
+        assert_eq!(output.status.code(), Some(1));
+        assert!(String::from_utf8_lossy(&output.stdout).contains("does not exist"));
+
+        // Neither does the help
+        let output = run_args(&["--dir", &dir, "list", "-h"]);
+        assert!(!String::from_utf8_lossy(&output.stderr).contains("Welcome"));
+
+        // The first subcommand that uses the data welcomes the user
+        let output = run_args(&["--dir", &format!("{}-2", dir), "list", "-n"]);
+        assert!(String::from_utf8_lossy(&output.stderr).contains("Welcome"));
+    }
+
+    #[test]
+    fn given_tree_is_read_from_a_saved_file() {
+        let dir = temp_dir("main-given-tree");
+        let path = format!("{}/trees.conf", dir);
+        let saved = vec![Tree::read_tree(tree_line("oak")).unwrap(), Tree::read_tree(tree_line("pine")).unwrap()];
+        write_trees(&path, &saved).unwrap();
+        assert!(fs::read_to_string(&path).unwrap().starts_with("#rusty-forest"));
+
+        // The header is skipped, and the first tree is grown
+        let tree = read_given_tree(None, Some(path.clone())).unwrap().unwrap();
+        assert_eq!(tree.name, "oak");
+
+        let tree = read_given_tree(Some(format!(" {}\n", tree_line("birch"))), None).unwrap().unwrap();
+        assert_eq!(tree.name, "birch");
+        assert!(read_given_tree(None, None).unwrap().is_none());
+
+        assert!(matches!(read_given_tree(Some(tree_line("birch")), Some(path.clone())), Err(ForestError::Usage(_))));
+        assert!(matches!(read_given_tree(Some("not a tree".to_string()), None), Err(ForestError::Usage(_))));
+        assert!(matches!(read_given_tree(None, Some(path + ".missing")), Err(ForestError::Runtime(_))));
+
+        let empty = format!("{}/empty.conf", dir);
+        fs::write(&empty, "#rusty-forest v2\n\n").unwrap();
+        assert!(matches!(read_given_tree(None, Some(empty)), Err(ForestError::Usage(_))));
+    }
+
+    #[test]
+    fn paths_are_inside_the_chosen_directory() {
+        let dir = temp_dir("main-paths");
+        let mut out = Vec::new();
+        write_paths(&mut out, &dir).unwrap();
+
+        let expected = format!("data\t{0}\ntrees\t{0}/trees.conf\nstats\t{0}/stats.conf\nconfig\t{0}/config.conf\n", dir);
+        assert_eq!(String::from_utf8(out).unwrap(), expected);
+    }
+}
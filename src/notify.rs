@@ -0,0 +1,75 @@
+//! Post a desktop notification or play a sound when a tree finishes growing, so it's
+//! noticed even if the terminal is hidden. The notification is posted by `notify-send` on
+//! Linux and the BSDs, and by `osascript` on macOS. The sound is played by `afplay` on
+//! macOS, and by `paplay` or `aplay` elsewhere.
+
+use std::process::{Command, Stdio};
+use crate::hooks::wait_with_timeout;
+
+/// Build the command that posts the notification.
+#[cfg(target_os = "macos")]
+fn notify_command(title: &str, body: &str) -> Option<Command> {
+    let quote = |x: &str| format!("\"{}\"", x.replace('\\', "\\\\").replace('"', "\\\""));
+    let mut command = Command::new("osascript");
+    command.arg("-e").arg(format!("display notification {} with title {}", quote(body), quote(title)));
+    Some(command)
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn notify_command(title: &str, body: &str) -> Option<Command> {
+    let mut command = Command::new("notify-send");
+    command.arg("--app-name=rusty-forest").arg(title).arg(body);
+    Some(command)
+}
+
+#[cfg(not(unix))]
+fn notify_command(_title: &str, _body: &str) -> Option<Command> {
+    None
+}
+
+/// Run the command without its output, and wait until it finishes.
+fn run_quietly(mut command: Command) -> Result<(), String> {
+    let name = command.get_program().to_string_lossy().into_owned();
+    let child = match command
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn() {
+    Ok(x) => { x }
+    Err(x) => { return Err(format!("Failed to run {}: {}", name, x)); }
+    };
+
+    wait_with_timeout(child, &name)
+}
+
+/// Post a desktop notification, and wait until it was posted.
+pub fn notify(title: &str, body: &str) -> Result<(), String> {
+    match notify_command(title, body) {
+    Some(x) => { run_quietly(x) }
+    None => { Err("Desktop notifications are not supported on this system".to_string()) }
+    }
+}
+
+/// The programs that can play a sound file, in the order they are tried.
+#[cfg(target_os = "macos")]
+const SOUND_PLAYERS: [&str; 1] = ["afplay"];
+
+#[cfg(not(target_os = "macos"))]
+const SOUND_PLAYERS: [&str; 2] = ["paplay", "aplay"];
+
+/// Play a sound file with the first player that is installed, and wait until it ends.
+pub fn play_sound(file: &str) -> Result<(), String> {
+    let mut errors = Vec::new();
+
+    for player in SOUND_PLAYERS {
+        let mut command = Command::new(player);
+        command.arg(file);
+
+        match run_quietly(command) {
+        Ok(_) => { return Ok(()); }
+        Err(x) => { errors.push(x); }
+        }
+    }
+
+    Err(errors.join("; "))
+}
@@ -0,0 +1,64 @@
+//! Welcome the users that run rusty-forest for the first time. The first run is detected
+//! by the missing data directory. The users are introduced to the main subcommands and,
+//! if they want, some starter trees are generated for them. Everything is written to
+//! stderr, so the output of the subcommand that is run stays clean.
+
+use std::io::{stdin, stderr, Write};
+use std::path::Path;
+use crate::tree::{TreeCollection, check_directories, data_dir};
+use crate::generate::generate_tree;
+
+/// How many starter trees are offered.
+const STARTER_TREES: usize = 3;
+
+/// Returns true if rusty-forest runs for the first time, so the data directory doesn't
+/// exist yet.
+pub fn is_first_run() -> bool {
+    match data_dir() {
+    Ok(x) => { !Path::new(&x).exists() }
+    Err(_) => { false }
+    }
+}
+
+/// Print a short introduction and create the data directory. If the user can answer,
+/// offer to generate some starter trees.
+pub fn run() {
+    eprintln!("Welcome to rusty-forest!");
+    eprintln!("Grow a tree while you focus with `rusty-forest grow`, draw your own trees with");
+    eprintln!("`rusty-forest import -c` and look at your forest with `rusty-forest stats`.");
+
+    if let Err(x) = check_directories() {
+        eprintln!("Failed to create the data directory: {}", x);
+        return;
+    }
+
+    if !termion::is_tty(&stdin()) {
+        eprintln!();
+        return;
+    }
+
+    eprint!("Do you want {} random starter trees in your collection? [y/N] ", STARTER_TREES);
+    stderr().flush().expect("Failed to flush");
+
+    let mut answer = String::new();
+    if stdin().read_line(&mut answer).is_err() || !answer.trim().eq_ignore_ascii_case("y") {
+        eprintln!();
+        return;
+    }
+
+    let mut trees = TreeCollection::load();
+    let mut rng = rand::thread_rng();
+
+    for _ in 0..STARTER_TREES {
+        let tree = generate_tree(&mut rng, "starter".to_string());
+        if let Err(x) = trees.add_tree(tree.to_string(), true) {
+            eprintln!("Failed to add tree: {}", x);
+        }
+    }
+
+    match trees.save() {
+    Ok(_) => { eprintln!("Added {} starter trees; see them with `rusty-forest list`.", STARTER_TREES); }
+    Err(x) => { eprintln!("Failed to save trees: {}", x); }
+    }
+    eprintln!();
+}
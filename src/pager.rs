@@ -0,0 +1,62 @@
+//! A simple pager that displays text in the alternate screen, for outputs that are
+//! too big to fit on the screen.
+
+use std::io::Read;
+use std::thread;
+use std::time::Duration;
+use termion::async_stdin;
+use termion::event::{Event, Key};
+use termion::terminal_size;
+use crate::display::Display;
+
+/// Display the text and let the user scroll through it with the arrow keys, PageUp, 
+/// PageDown, Home and End. The user can exit by pressing `q` or CTRL+c.
+pub fn page(text: &str) {
+    let lines: Vec<&str> = text.lines().collect();
+
+    let mut stdin = async_stdin().bytes();
+    let mut display = Display::new();
+
+    let mut offset: usize = 0;
+    let mut last_frame: Option<(usize, usize, usize)> = None;
+    let mut exit_program = false;
+
+    while !exit_program {
+        let (_, height) = terminal_size().unwrap();
+        let height = height as usize;
+
+        let mut returned_none = false;
+        while !returned_none {
+            let key = stdin.next();
+            match key {
+            Some(k) => {
+                let e = termion::event::parse_event(k.unwrap(), &mut stdin);
+                match e {
+                Ok(Event::Key(Key::Ctrl('c'))) | Ok(Event::Key(Key::Char('q'))) => { exit_program = true; }
+                Ok(Event::Key(Key::Up))       => { offset = offset.saturating_sub(1); }
+                Ok(Event::Key(Key::Down))     => { offset += 1; }
+                Ok(Event::Key(Key::PageUp))   => { offset = offset.saturating_sub(height); }
+                Ok(Event::Key(Key::PageDown)) => { offset += height; }
+                Ok(Event::Key(Key::Home))     => { offset = 0; }
+                Ok(Event::Key(Key::End))      => { offset = lines.len(); }
+                _ => {}
+                }
+            }
+            None => { returned_none = true; }
+            }
+        }
+
+        // Don't scroll past the last screen, this also handles the terminal resizing
+        offset = offset.min(lines.len().saturating_sub(height));
+
+        // Redraw only when something changed, to avoid flickering
+        let (width, _) = terminal_size().unwrap();
+        let frame = (offset, width as usize, height);
+        if last_frame != Some(frame) && !exit_program {
+            display.display_text(&lines[offset..].join("\n"));
+            last_frame = Some(frame);
+        }
+
+        thread::sleep(Duration::from_millis(50));
+    }
+}
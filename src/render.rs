@@ -0,0 +1,102 @@
+//! Shared helpers used to render trees as colored text.
+
+use std::io::{self, Write};
+use crate::color;
+use crate::tree::Tree;
+
+/// Write the trees tiled in a grid, with borders between them. The empty places of 
+/// the grid are left blank. Each cell of a tree is drawn as a block of scale x scale
+/// characters.
+pub fn write_tiles<W: Write>(out: &mut W, grid: &[Vec<Option<&Tree>>], scale: usize) -> io::Result<()> {
+    let n = grid.len();
+    let m = grid.first().map_or(0, |row| row.len());
+
+    if n == 0 || m == 0 {
+        return Ok(());
+    }
+
+    // Each tile is followed by a border
+    let tile = 5 * scale + 1;
+
+    for i in 0..tile*n-1 {
+        for j in 0..tile*m-1 {
+            if i % tile == tile - 1 {
+                if j % tile == tile - 1 {
+                    write!(out, "+")?;
+                } else {
+                    write!(out, "-")?;
+                }
+            } else if j % tile == tile - 1 {
+                write!(out, "|")?;
+            } else {
+                let tree_line = i / tile;
+                let tree_col  = j / tile;
+
+                match grid[tree_line][tree_col] {
+                Some(tree) => { tree.write_symbol(out, i % tile / scale, j % tile / scale)?; }
+                None => {       write!(out, " ")?; }
+                }
+
+                write!(out, "{}", color::Reset)?;
+            }
+        }
+        writeln!(out)?;
+    }
+
+    Ok(())
+}
+
+/// Write all the trees in a grid with the given number of columns. The grid is filled row
+/// by row, in the order of the trees.
+pub fn write_collage<W: Write>(out: &mut W, trees: &[Tree], columns: usize) -> io::Result<()> {
+    let grid: Vec<Vec<Option<&Tree>>> = trees.chunks(columns)
+        .map(|row| {
+            let mut row: Vec<Option<&Tree>> = row.iter().map(Some).collect();
+            row.resize(columns, None);
+            row
+        })
+        .collect();
+
+    write_tiles(out, &grid, 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::ColorMode;
+    use crate::testing::{color_mode, tree};
+
+    #[test]
+    fn collage_tiles_the_trees_row_by_row() {
+        let _mode = color_mode(ColorMode::TrueColor);
+        let trees = vec![tree("a"), tree("b"), tree("c")];
+
+        let mut out = Vec::new();
+        write_collage(&mut out, &trees, 2).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+
+        // Two rows of five lines, with a border between them
+        assert_eq!(lines.len(), 11);
+        assert_eq!(lines[5], "-----+-----");
+
+        let reset = color::Reset.to_string();
+        for (idx, line) in lines.iter().enumerate().filter(|(idx, _)| *idx != 5) {
+            assert!(line.ends_with(&reset), "line {} doesn't reset the colors", idx);
+        }
+
+        // The third tree is alone on its row, next to an empty tile
+        let empty = format!(" {}", reset).repeat(5);
+        for line in &lines[6..] {
+            assert!(line.ends_with(&format!("|{}", empty)));
+            assert!(line.contains("\x1b[48;2;"));
+        }
+    }
+
+    #[test]
+    fn collage_of_no_trees_is_empty() {
+        let mut out = Vec::new();
+        write_collage(&mut out, &[], 3).unwrap();
+        assert!(out.is_empty());
+    }
+}
@@ -0,0 +1,99 @@
+//! Handle the session file, `~/.rusty-forest/session.conf`. While a tree is growing, the
+//! session is saved in this file every few seconds, so it can be resumed if the terminal
+//! is closed or the machine stops before the tree is grown. The file is removed when the
+//! session ends, either because the tree was grown or because it was cancelled.
+//!
+//! The format is `<duration as hh:mm>/<label>/<start timestamp>/<seconds grown>/<formatted-tree>`.
+
+use std::fmt;
+use std::fs;
+use std::str::FromStr;
+use crate::grow::GrowthTime;
+use crate::tree::{Tree, data_file, write_atomic};
+
+/// Name of the session file inside the data directory.
+const SESSION_FILE: &str = "session.conf";
+
+/// A tree that started growing and was not grown yet.
+#[derive(Debug, Clone)]
+pub struct Session {
+    /// The duration of the session, in minutes.
+    pub duration: u64,
+    pub label: String,
+    /// When the session started.
+    pub start: i64,
+    /// How many seconds the tree has grown, without the pauses.
+    pub grown: u64,
+    pub tree: Tree,
+}
+
+impl FromStr for Session {
+    type Err = String;
+
+    /// Parse this struct from a string that respects the format.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let tokens: Vec<&str> = s.trim_end().split('/').collect();
+
+        if tokens.len() != 5 {
+            return Err("Failed to parse session: wrong number of tokens".to_string());
+        }
+
+        let duration = GrowthTime::from_record(tokens[0])?.to_min();
+        let label = tokens[1].to_string();
+        let start = match i64::from_str(tokens[2]) {
+        Ok(x) => { x }
+        Err(x) => { return Err(format!("Failed to parse session: {}", x)); }
+        };
+        let grown = match u64::from_str(tokens[3]) {
+        Ok(x) => { x }
+        Err(x) => { return Err(format!("Failed to parse session: {}", x)); }
+        };
+
+        let tree = Tree::import_tree(tokens[4].to_string())?;
+
+        Ok( Session {
+            duration,
+            label,
+            start,
+            grown,
+            tree,
+        } )
+    }
+}
+
+impl fmt::Display for Session {
+    /// Convert the session to the content of the session file.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let duration = GrowthTime { h: self.duration / 60, m: self.duration % 60 };
+        write!(f, "{}/{}/{}/{}/{}", duration.to_string(), self.label, self.start, self.grown, self.tree.to_string())
+    }
+}
+
+impl Session {
+    /// Get how many seconds are left until the tree is grown.
+    pub fn remaining(&self) -> u64 {
+        (self.duration * 60).saturating_sub(self.grown)
+    }
+
+    /// Load the unfinished session, if there is one.
+    pub fn load() -> Result<Option<Session>, String> {
+        let path = data_file(SESSION_FILE)?;
+
+        match fs::read_to_string(path) {
+        Ok(x) => { Session::from_str(&x).map(Some) }
+        Err(_) => { Ok(None) }
+        }
+    }
+
+    /// Write the session file.
+    pub fn save(&self) -> Result<(), String> {
+        write_atomic(&data_file(SESSION_FILE)?, &(self.to_string() + "\n"))
+    }
+
+    /// Remove the session file, if it exists.
+    pub fn remove() {
+        if let Ok(path) = data_file(SESSION_FILE) {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
@@ -0,0 +1,259 @@
+//! Keep the grown trees in a SQLite database, `stats.db`, instead of `stats.conf`. This is
+//! used when `stats_backend=sqlite` is set in the config file. The first time the database
+//! is opened, the grown trees are copied from `stats.conf`, which is left as it is.
+
+use std::path::Path;
+use rusqlite::{Connection, params};
+use crate::tree::{Tree, GrownTree, check_directories, data_dir, get_stats_from};
+use crate::log;
+
+/// The version stored in the database once the grown trees were copied from `stats.conf`.
+const MIGRATED_VERSION: i64 = 1;
+
+/// A row of the table: the duration, the label, the timestamp, the tree and whether it was
+/// killed.
+type Row = (i64, String, i64, String, bool);
+
+/// Open `stats.db` from the data directory, creating the table if it doesn't exist yet.
+fn open() -> Result<Connection, String> {
+    check_directories()?;
+    open_in(&data_dir()?)
+}
+
+/// Open `stats.db` from the given directory, creating the table if it doesn't exist yet.
+fn open_in(dir: &str) -> Result<Connection, String> {
+    let path = Path::new(dir).join("stats.db").to_string_lossy().into_owned();
+    let conn = match Connection::open(&path) {
+    Ok(x) => { x }
+    Err(x) => { return Err(format!("Failed to open {}: {}", path, x)); }
+    };
+
+    let res = conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS grown_trees (
+            id INTEGER PRIMARY KEY,
+            duration INTEGER NOT NULL,
+            label TEXT NOT NULL,
+            timestamp INTEGER NOT NULL,
+            tree TEXT NOT NULL,
+            killed INTEGER NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS grown_trees_timestamp ON grown_trees (timestamp);
+        CREATE INDEX IF NOT EXISTS grown_trees_label ON grown_trees (label);");
+    if let Err(x) = res {
+        return Err(format!("Failed to create the tables of {}: {}", path, x));
+    }
+
+    migrate(&conn, dir)?;
+    Ok(conn)
+}
+
+/// Copy the grown trees from the `stats.conf` of the directory, if this wasn't done yet.
+fn migrate(conn: &Connection, dir: &str) -> Result<(), String> {
+    let version: i64 = match conn.query_row("PRAGMA user_version", [], |row| row.get(0)) {
+    Ok(x) => { x }
+    Err(x) => { return Err(format!("Failed to read the version of stats.db: {}", x)); }
+    };
+
+    if version >= MIGRATED_VERSION {
+        return Ok(());
+    }
+
+    let path = Path::new(dir).join("stats.conf").to_string_lossy().into_owned();
+    let stats = if Path::new(&path).exists() {
+        get_stats_from(&path)?
+    } else {
+        Vec::new()
+    };
+
+    insert_all(conn, &stats, &format!("PRAGMA user_version = {}", MIGRATED_VERSION))?;
+    if !stats.is_empty() {
+        log::info(&format!("Copied {} grown trees from {} to stats.db", stats.len(), path));
+    }
+
+    Ok(())
+}
+
+/// Run the statement and insert the grown trees, all in one transaction.
+fn insert_all(conn: &Connection, stats: &[GrownTree], first: &str) -> Result<(), String> {
+    let res = (|| -> rusqlite::Result<()> {
+        conn.execute_batch("BEGIN")?;
+        conn.execute_batch(first)?;
+        for tree in stats {
+            insert(conn, tree)?;
+        }
+        conn.execute_batch("COMMIT")
+    })();
+
+    if let Err(x) = res {
+        let _ = conn.execute_batch("ROLLBACK");
+        return Err(format!("Failed to write stats.db: {}", x));
+    }
+
+    Ok(())
+}
+
+fn insert(conn: &Connection, tree: &GrownTree) -> rusqlite::Result<usize> {
+    conn.execute("INSERT INTO grown_trees (duration, label, timestamp, tree, killed) VALUES (?1, ?2, ?3, ?4, ?5)",
+                 params![tree.duration as i64, tree.label, tree.timestamp, tree.tree.to_string(), tree.killed])
+}
+
+/// Add a grown tree to `stats.db`.
+pub fn append_stats(tree: &GrownTree) -> Result<(), String> {
+    append_to(&open()?, tree)
+}
+
+/// Add a grown tree to the opened database.
+fn append_to(conn: &Connection, tree: &GrownTree) -> Result<(), String> {
+    match insert(conn, tree) {
+    Ok(_) => { Ok(()) }
+    Err(x) => { Err(format!("Failed to write stats.db: {}", x)) }
+    }
+}
+
+/// Replace all the grown trees from `stats.db`.
+pub fn save_stats(stats: &[GrownTree]) -> Result<(), String> {
+    insert_all(&open()?, stats, "DELETE FROM grown_trees")
+}
+
+/// Load all the grown trees from `stats.db`, in the order they were added.
+pub fn get_stats() -> Result<Vec<GrownTree>, String> {
+    load_from(&open()?)
+}
+
+/// Load all the grown trees from the opened database, in the order they were added.
+fn load_from(conn: &Connection) -> Result<Vec<GrownTree>, String> {
+    let res = (|| -> rusqlite::Result<Vec<Row>> {
+        let mut stmt = conn.prepare("SELECT duration, label, timestamp, tree, killed FROM grown_trees ORDER BY id")?;
+        let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?)))?;
+        rows.collect()
+    })();
+
+    let rows = match res {
+    Ok(x) => { x }
+    Err(x) => { return Err(format!("Failed to read stats.db: {}", x)); }
+    };
+
+    let mut stats = Vec::new();
+    for (duration, label, timestamp, tree, killed) in rows {
+        match Tree::import_tree(tree) {
+        Ok(tree) => {
+            stats.push(GrownTree {
+                duration: duration as u64,
+                tree,
+                label,
+                timestamp,
+                killed,
+            });
+        }
+        Err(x) => { log::warn(&format!("Failed to load grown tree: {}", x)); }
+        }
+    }
+
+    log::info(&format!("Loaded {} grown trees from stats.db", stats.len()));
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use crate::store::StatsStore;
+    use crate::tree::{append_stats_to, write_stats};
+    use crate::testing::{temp_dir, grown};
+
+    /// The SQLite backend, kept in a directory instead of the data directory.
+    struct DirSqlite(String);
+
+    impl StatsStore for DirSqlite {
+        fn append(&self, tree: &GrownTree) -> Result<(), String> {
+            append_to(&open_in(&self.0)?, tree)
+        }
+
+        fn load(&self) -> Result<Vec<GrownTree>, String> {
+            load_from(&open_in(&self.0)?)
+        }
+
+        fn save(&self, stats: &[GrownTree]) -> Result<(), String> {
+            insert_all(&open_in(&self.0)?, stats, "DELETE FROM grown_trees")
+        }
+    }
+
+    /// The flat file backend, kept at a path instead of the data directory.
+    struct PathStats(String);
+
+    impl StatsStore for PathStats {
+        fn append(&self, tree: &GrownTree) -> Result<(), String> {
+            append_stats_to(&self.0, tree)
+        }
+
+        fn load(&self) -> Result<Vec<GrownTree>, String> {
+            get_stats_from(&self.0)
+        }
+
+        fn save(&self, stats: &[GrownTree]) -> Result<(), String> {
+            write_stats(&self.0, stats)
+        }
+    }
+
+    fn lines(stats: &[GrownTree]) -> Vec<String> {
+        stats.iter().map(|tree| tree.to_string()).collect()
+    }
+
+    /// Both backends must keep the same grown trees, in the same order.
+    fn check_store(store: &dyn StatsStore) {
+        let mut killed = grown("b", 300, 200);
+        killed.killed = true;
+        let first = vec![grown("a", 1500, 100), killed, grown("c", 600, 300)];
+
+        for tree in &first {
+            store.append(tree).unwrap();
+        }
+        let loaded = store.load().unwrap();
+        assert_eq!(lines(&loaded), lines(&first));
+        assert!(loaded[1].killed);
+        assert!(!loaded[0].killed);
+
+        let second = vec![grown("d", 60, 400)];
+        store.save(&second).unwrap();
+        assert_eq!(lines(&store.load().unwrap()), lines(&second));
+
+        store.save(&[]).unwrap();
+        assert!(store.load().unwrap().is_empty());
+    }
+
+    #[test]
+    fn both_backends_keep_the_same_trees() {
+        let dir = temp_dir("sqlite-store");
+        check_store(&DirSqlite(dir.clone()));
+        check_store(&PathStats(format!("{}/stats.conf", dir)));
+    }
+
+    #[test]
+    fn stats_conf_is_copied_once() {
+        let dir = temp_dir("sqlite-migrate");
+        let old = vec![grown("a", 1500, 100), grown("b", 300, 200)];
+        let conf = format!("{}/stats.conf", dir);
+        write_stats(&conf, &old).unwrap();
+        let before = fs::read_to_string(&conf).unwrap();
+
+        let store = DirSqlite(dir.clone());
+        assert_eq!(lines(&store.load().unwrap()), lines(&old));
+        assert_eq!(fs::read_to_string(&conf).unwrap(), before);
+
+        // Opening it again doesn't copy the trees a second time, even if stats.conf changed.
+        write_stats(&conf, &[grown("c", 60, 300)]).unwrap();
+        store.append(&grown("d", 60, 400)).unwrap();
+        let mut all = old.clone();
+        all.push(grown("d", 60, 400));
+        assert_eq!(lines(&store.load().unwrap()), lines(&all));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn missing_stats_conf_starts_empty() {
+        let dir = temp_dir("sqlite-empty");
+        assert!(DirSqlite(dir.clone()).load().unwrap().is_empty());
+        assert!(Path::new(&dir).join("stats.db").exists());
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,155 @@
+//! Handle the state file, `~/.rusty-forest/state.json`. It holds counters that are kept
+//! up to date as trees are grown, so they can be displayed without reading all the grown
+//! trees. The counters can always be recomputed from `~/.rusty-forest/stats.conf`.
+
+use std::fs;
+use std::io;
+use serde_json::{json, Value};
+use crate::tree::{GrownTree, data_file, write_atomic};
+use crate::store::{StatsStore, stats_store};
+
+/// Name of the state file inside the data directory.
+const STATE_FILE: &str = "state.json";
+
+/// The counters kept in the state file.
+#[derive(Debug, Clone, Default)]
+pub struct State {
+    /// The total number of minutes grown.
+    pub total_minutes: u64,
+}
+
+impl State {
+    /// Convert the state to its JSON representation.
+    pub fn to_json(&self) -> String {
+        json!({
+            "total_minutes": self.total_minutes,
+        }).to_string()
+    }
+
+    /// Parse the state from its JSON representation.
+    pub fn from_json(s: &str) -> Result<State, String> {
+        let value: Value = match serde_json::from_str(s) {
+        Ok(x) => { x }
+        Err(x) => { return Err(format!("Failed to parse state: {}", x)); }
+        };
+
+        Ok(State {
+            total_minutes: value["total_minutes"].as_u64()
+                .ok_or_else(|| "Failed to parse state: missing field total_minutes".to_string())?,
+        })
+    }
+
+    /// Load the state file. If the file is missing (for instance, the trees were grown with
+    /// an older version), the state is computed from the grown trees and saved.
+    pub fn load() -> Result<State, String> {
+        State::load_from(&data_file(STATE_FILE)?, &*stats_store())
+    }
+
+    /// Load the state file from the given path, computing it from the store if the file is
+    /// missing. If the grown trees can't be loaded, nothing is saved.
+    fn load_from(path: &str, store: &dyn StatsStore) -> Result<State, String> {
+        match fs::read_to_string(path) {
+        Ok(x) => { State::from_json(&x) }
+        Err(x) if x.kind() == io::ErrorKind::NotFound => {
+            let state = State::recompute(&store.load()?);
+            write_atomic(path, &state.to_json())?;
+            Ok(state)
+        }
+        Err(x) => { Err(format!("Failed to read {}: {}", path, x)) }
+        }
+    }
+
+    /// Write the state file. The file is first written under a temporary name and then
+    /// renamed, so it is never left partially written.
+    pub fn save(&self) -> Result<(), String> {
+        let path = data_file(STATE_FILE)?;
+        write_atomic(&path, &self.to_json())
+    }
+
+    /// Count a session of the given number of minutes.
+    pub fn add_session(&mut self, minutes: u64) {
+        self.total_minutes = self.total_minutes.saturating_add(minutes);
+    }
+
+    /// Compute the counters from all the grown trees. The trees that were killed don't
+    /// count.
+    pub fn recompute(stats: &[GrownTree]) -> State {
+        let mut state = State::default();
+        for tree in stats.iter().filter(|x| !x.killed) {
+            state.add_session(tree.duration);
+        }
+        state
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{MemoryStats, grown, temp_dir};
+
+    /// A store that can't be read, like a locked `stats.db`.
+    struct BrokenStats;
+
+    impl StatsStore for BrokenStats {
+        fn append(&self, _tree: &GrownTree) -> Result<(), String> { Err("locked".to_string()) }
+        fn load(&self) -> Result<Vec<GrownTree>, String> { Err("locked".to_string()) }
+        fn save(&self, _stats: &[GrownTree]) -> Result<(), String> { Err("locked".to_string()) }
+    }
+
+    #[test]
+    fn missing_state_is_computed_only_from_a_readable_store() {
+        let dir = temp_dir("state-load");
+        let path = format!("{}/{}", dir, STATE_FILE);
+
+        assert_eq!(State::load_from(&path, &BrokenStats).unwrap_err(), "locked");
+        assert!(!std::path::Path::new(&path).exists());
+
+        let store = MemoryStats::default();
+        store.append(&grown("work", 25, 1)).unwrap();
+        store.append(&grown("work", 50, 2)).unwrap();
+        assert_eq!(State::load_from(&path, &store).unwrap().total_minutes, 75);
+
+        // Once saved, the state file is used instead of the store
+        assert_eq!(State::load_from(&path, &BrokenStats).unwrap().total_minutes, 75);
+
+        // Other errors are reported instead of computing the state again
+        assert!(State::load_from(&dir, &store).unwrap_err().starts_with("Failed to read"));
+    }
+
+    #[test]
+    fn sessions_are_added_to_the_total() {
+        let mut state = State::default();
+        state.add_session(25);
+        state.add_session(0);
+        state.add_session(90);
+        assert_eq!(state.total_minutes, 115);
+
+        state.total_minutes = u64::MAX - 1;
+        state.add_session(10);
+        assert_eq!(state.total_minutes, u64::MAX);
+    }
+
+    #[test]
+    fn recomputing_matches_adding_each_session() {
+        let mut stats = vec![grown("work", 25, 1), grown("read", 40, 2), grown("work", 15, 3)];
+        stats[1].killed = true;
+
+        let mut state = State::default();
+        for tree in stats.iter().filter(|x| !x.killed) {
+            state.add_session(tree.duration);
+        }
+
+        assert_eq!(State::recompute(&stats).total_minutes, 40);
+        assert_eq!(state.total_minutes, State::recompute(&stats).total_minutes);
+        assert_eq!(State::recompute(&[]).total_minutes, 0);
+    }
+
+    #[test]
+    fn state_round_trips_through_json() {
+        let state = State { total_minutes: 1234 };
+        assert_eq!(State::from_json(&state.to_json()).unwrap().total_minutes, 1234);
+
+        assert!(State::from_json("{}").is_err());
+        assert!(State::from_json("not json").is_err());
+    }
+}
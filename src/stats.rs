@@ -0,0 +1,1188 @@
+//! Handle the views of the stats subcommand. All the views are written to a generic
+//! writer, so they can be printed directly to stdout or rendered in a buffer first
+//! (for instance when refreshing them in watch mode).
+
+use std::io::{self, Write};
+use std::cmp;
+use std::collections::HashSet;
+use termion::terminal_size;
+use rand::thread_rng;
+use rand::seq::SliceRandom;
+use chrono::{Local, TimeZone, Datelike, DurationRound, Duration, DateTime, NaiveDate, NaiveDateTime};
+use itertools::Itertools;
+use serde_json::json;
+use std::io::Read;
+use std::fs;
+use std::time::{Duration as StdDuration, Instant};
+use termion::async_stdin;
+use termion::event::{Event, Key};
+use crate::tree::{Tree, GrownTree, get_stats_from};
+use crate::store::StatsStore;
+use crate::display::Display;
+use crate::render::write_tiles;
+use crate::color::{self, ColorMode};
+
+/// The way the grown trees are displayed.
+pub enum StatsView {
+    /// Display each tree on a line, with the dates in the given format, followed by the
+    /// summary.
+    List(String),
+    /// Display only the number of trees, the total time grown and the average session.
+    Summary,
+    /// Display the trees in a grid of the given size (RxC or "whole"), with each cell
+    /// of the trees drawn as a block of the given scale.
+    Grid(String, usize),
+    /// Display a graph over the given time unit, with the days starting at the given hour.
+    Graph(String, u32),
+    /// Display the progress towards each goal, with the days starting at the given hour.
+    Goals(Vec<Goal>, u32),
+    /// Compare the current period (week or month) with the previous one.
+    Compare(String),
+    /// Count the killed trees and display each of them on a line, with the dates in the
+    /// given format.
+    Kills(String),
+    /// Display the labels or the trees ("label" or "tree") with the most time grown, at
+    /// most the given number of them.
+    Top(usize, String),
+    /// Display the mean, median, shortest and longest session.
+    Average,
+    /// Display a row for each day, with the days starting at the given hour, written as
+    /// text, csv or json.
+    DailyTable(u32, String),
+    /// Display each tree as a row of comma-separated values.
+    Csv,
+}
+
+/// The typical length of the sessions, in minutes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SessionStats {
+    pub count: usize,
+    pub mean: f64,
+    pub median: f64,
+    pub min: u64,
+    pub max: u64,
+}
+
+/// A target of minutes to grow in a time period.
+#[derive(Debug, Clone)]
+pub struct Goal {
+    /// The time period, as accepted by filter_period (for instance "today").
+    pub period: String,
+    /// The number of minutes that should be grown in the time period.
+    pub minutes: u64,
+}
+
+/// The filters applied to the grown trees before displaying them.
+pub struct StatsFilter {
+    /// Keep only the trees with this label.
+    pub label: Option<String>,
+    /// Keep only the trees grown in this time period.
+    pub period: Option<String>,
+    /// Keep only the most recent trees.
+    pub count: Option<usize>,
+    /// The hour when a new day starts, for the today and yesterday periods.
+    pub day_start_hour: u32,
+    /// Keep only the trees that were killed, instead of the ones that grew.
+    pub killed: bool,
+}
+
+impl StatsFilter {
+    /// Apply all the filters on the grown trees.
+    pub fn apply(&self, stats: &mut Vec<GrownTree>) -> Result<(), String> {
+        stats.retain(|x| { x.killed == self.killed });
+
+        if let Some(label) = &self.label {
+            stats.retain(|x| { &x.label == label } )
+        }
+
+        if let Some(t) = &self.period {
+            filter_period(stats, t, self.day_start_hour)?;
+        }
+
+        if let Some(count) = self.count {
+            if count < stats.len() {
+                stats.rotate_right(count);
+                stats.truncate(count);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Load the grown trees from the given file, or from the store if there is no file.
+pub fn load_stats(store: &dyn StatsStore, file: Option<&str>) -> Result<Vec<GrownTree>, String> {
+    match file {
+    Some(path) => { get_stats_from(path) }
+    None => { store.load() }
+    }
+}
+
+/// Load the grown trees displayed by `watch`. The file may be removed while it's watched,
+/// which means there are no trees, but any other error is returned.
+fn load_watched(store: &dyn StatsStore, file: Option<&str>) -> Result<Vec<GrownTree>, String> {
+    match file {
+    Some(path) if matches!(fs::metadata(path), Err(x) if x.kind() == io::ErrorKind::NotFound) => { Ok(Vec::new()) }
+    _ => { load_stats(store, file) }
+    }
+}
+
+/// Move the timestamp back by the hours before the start of the day, so the trees grown
+/// before that hour count for the previous day.
+pub fn shift_day_start(timestamp: i64, day_start_hour: u32) -> i64 {
+    timestamp - day_start_hour as i64 * 3600
+}
+
+/// Keep only the trees grown in the given time period. For today and yesterday, the days
+/// start at the given hour.
+pub fn filter_period(stats: &mut Vec<GrownTree>, t: &str, day_start_hour: u32) -> Result<(), String> {
+    filter_period_at(stats, t, day_start_hour, Local::now())
+}
+
+/// Keep only the trees grown in the given time period, as seen at the given time. The
+/// period is not case sensitive.
+fn filter_period_at(stats: &mut Vec<GrownTree>, t: &str, day_start_hour: u32, now: DateTime<Local>) -> Result<(), String> {
+    let shifted_now = Local.timestamp_opt(shift_day_start(now.timestamp(), day_start_hour), 0).unwrap();
+
+    match t.to_lowercase().as_str() {
+    "today" => {
+        stats.retain(|tree| {
+            let date = Local.timestamp_opt(shift_day_start(tree.timestamp, day_start_hour), 0).unwrap();
+            date.num_days_from_ce() == shifted_now.num_days_from_ce()
+        });
+    }
+    "yesterday" => {
+        stats.retain(|tree| {
+            let date = Local.timestamp_opt(shift_day_start(tree.timestamp, day_start_hour), 0).unwrap();
+            date.num_days_from_ce() + 1 == shifted_now.num_days_from_ce()
+        })
+    }
+    "this-week" => {
+        stats.retain(|tree| {
+            let date = Local.timestamp(tree.timestamp, 0);
+            date.iso_week().year() == now.iso_week().year() &&
+            date.iso_week().week() == now.iso_week().week()
+        })
+    }
+    "this-month" => {
+        stats.retain(|tree| {
+            let date = Local.timestamp(tree.timestamp, 0);
+            date.year()  == now.year() &&
+            date.month() == now.month()
+        })
+    }
+    "this-year" => {
+        stats.retain(|tree| {
+            let date = Local.timestamp(tree.timestamp, 0);
+            date.year() == now.year()
+        })
+    }
+    _ => {
+        return Err("Unknown time period".to_string());
+    }
+    }
+
+    Ok(())
+}
+
+/// Build the error returned when the options of a view are not valid.
+fn invalid_input<E: ToString>(message: E) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message.to_string())
+}
+
+/// Write the chosen view of the grown trees.
+pub fn render<W: Write>(out: &mut W, stats: &[GrownTree], view: &StatsView) -> io::Result<()> {
+    match view {
+    StatsView::List(format) => {
+        render_list(out, stats, format)?;
+        render_summary(out, stats)
+    }
+    StatsView::Summary => { render_summary(out, stats) }
+    StatsView::Grid(grid, scale) => { render_grid(out, stats, grid, *scale) }
+    StatsView::Graph(unit, day_start_hour) => { render_graph(out, stats, unit, *day_start_hour) }
+    StatsView::Goals(goals, day_start_hour) => { render_goals(out, stats, goals, *day_start_hour) }
+    StatsView::Compare(unit) => { render_compare(out, stats, unit) }
+    StatsView::Kills(format) => { render_kills(out, stats, format) }
+    StatsView::Top(count, by) => { render_top(out, stats, *count, by) }
+    StatsView::Average => { render_average(out, stats) }
+    StatsView::DailyTable(day_start_hour, output) => { render_daily_table(out, stats, *day_start_hour, output) }
+    StatsView::Csv => { write!(out, "{}", to_csv(stats, &Local)) }
+    }
+}
+
+/// Write each grown tree on a line, using the given date format.
+pub fn render_list<W: Write>(out: &mut W, stats: &[GrownTree], format: &str) -> io::Result<()> {
+    for tree in stats {
+        writeln!(out, "{} | {} | {:02}:{:02}", tree.label, Local.timestamp(tree.timestamp, 0).format(format), tree.duration / 60, tree.duration % 60)?;
+    }
+
+    Ok(())
+}
+
+/// Write the number of trees, the total time grown and the average length of a session.
+pub fn render_summary<W: Write>(out: &mut W, stats: &[GrownTree]) -> io::Result<()> {
+    let durations: Vec<u64> = stats.iter().map(|tree| tree.duration).collect();
+    let total: u64 = durations.iter().sum();
+    let mean = session_stats(&durations).mean.round() as u64;
+
+    writeln!(out, "{} trees, {:02}:{:02} in total, {:02}:{:02} on average", stats.len(), total / 60, total % 60, mean / 60, mean % 60)
+}
+
+/// Write how many trees were killed and how long they grew before that, followed by a
+/// line for each killed tree.
+pub fn render_kills<W: Write>(out: &mut W, stats: &[GrownTree], format: &str) -> io::Result<()> {
+    let total: u64 = stats.iter().map(|tree| tree.duration).sum();
+    writeln!(out, "{} trees killed, after growing for {:02}:{:02} in total", stats.len(), total / 60, total % 60)?;
+
+    render_list(out, stats, format)
+}
+
+/// Get the total minutes grown for each label or tree ("label" or "tree"), sorted from the
+/// most time grown to the least. Equal totals are sorted by name.
+pub fn totals_by(stats: &[GrownTree], by: &str) -> Result<Vec<(String, u64)>, String> {
+    let key = match by {
+    "label" => { |tree: &GrownTree| tree.label.clone() }
+    "tree" => { |tree: &GrownTree| tree.tree.name.clone() }
+    _ => { return Err("Unknown grouping; it should be label or tree".to_string()); }
+    };
+
+    let mut totals: Vec<(String, u64)> = Vec::new();
+    for tree in stats {
+        let name = key(tree);
+        match totals.iter_mut().find(|x| { x.0 == name }) {
+        Some(x) => { x.1 += tree.duration; }
+        None => { totals.push((name, tree.duration)); }
+        }
+    }
+
+    totals.sort_by(|a, b| { b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)) });
+    Ok(totals)
+}
+
+/// Write the labels or trees with the most time grown, at most count of them.
+pub fn render_top<W: Write>(out: &mut W, stats: &[GrownTree], count: usize, by: &str) -> io::Result<()> {
+    let mut totals = match totals_by(stats, by) {
+    Ok(x) => { x }
+    Err(x) => { return Err(invalid_input(x)); }
+    };
+    totals.truncate(count);
+
+    for (idx, (name, total)) in totals.iter().enumerate() {
+        writeln!(out, "{}) {} | {:02}:{:02}", idx + 1, name, total / 60, total % 60)?;
+    }
+
+    Ok(())
+}
+
+/// Get the mean, median, shortest and longest of the session durations. With no sessions,
+/// everything is 0.
+pub fn session_stats(durations: &[u64]) -> SessionStats {
+    if durations.is_empty() {
+        return SessionStats { count: 0, mean: 0.0, median: 0.0, min: 0, max: 0 };
+    }
+
+    let mut sorted = durations.to_vec();
+    sorted.sort_unstable();
+
+    let count = sorted.len();
+    let mean = sorted.iter().sum::<u64>() as f64 / count as f64;
+    let median = if count.is_multiple_of(2) {
+        (sorted[count / 2 - 1] + sorted[count / 2]) as f64 / 2.0
+    } else {
+        sorted[count / 2] as f64
+    };
+
+    SessionStats {
+        count,
+        mean,
+        median,
+        min: sorted[0],
+        max: sorted[count - 1],
+    }
+}
+
+/// Write the mean, median, shortest and longest session.
+pub fn render_average<W: Write>(out: &mut W, stats: &[GrownTree]) -> io::Result<()> {
+    let durations: Vec<u64> = stats.iter().map(|tree| tree.duration).collect();
+    let session = session_stats(&durations);
+
+    if session.count == 0 {
+        return writeln!(out, "No trees were grown");
+    }
+
+    let mean = session.mean.round() as u64;
+    let median = session.median.round() as u64;
+
+    writeln!(out, "sessions {}", session.count)?;
+    writeln!(out, "mean     {:02}:{:02}", mean / 60, mean % 60)?;
+    writeln!(out, "median   {:02}:{:02}", median / 60, median % 60)?;
+    writeln!(out, "shortest {:02}:{:02}", session.min / 60, session.min % 60)?;
+    writeln!(out, "longest  {:02}:{:02}", session.max / 60, session.max % 60)?;
+
+    Ok(())
+}
+
+/// Group the grown trees by the day they were grown in, with the days starting at the
+/// given hour. Each day that has trees gets its start, the number of sessions and the
+/// minutes grown.
+pub fn daily_totals(stats: &[GrownTree], day_start_hour: u32) -> Vec<(DateTime<Local>, usize, u64)> {
+    let mut data_grouped: Vec<(DateTime<Local>, usize, u64)> = Vec::new();
+    for(key, tree) in &stats.iter().group_by(|tree| Local.timestamp_opt(shift_day_start(tree.timestamp, day_start_hour), 0).unwrap().duration_trunc(Duration::days(1)).unwrap()) {
+        let durations: Vec<u64> = tree.map(|tree| tree.duration).collect();
+        data_grouped.push((key, durations.len(), durations.iter().sum()));
+    }
+
+    data_grouped
+}
+
+/// Write a row for each day with the number of sessions and the minutes grown, in the
+/// given output format: text, csv or json.
+pub fn render_daily_table<W: Write>(out: &mut W, stats: &[GrownTree], day_start_hour: u32, output: &str) -> io::Result<()> {
+    let mut sorted = stats.to_vec();
+    sorted.sort_by_key(|tree| tree.timestamp);
+    let days = daily_totals(&sorted, day_start_hour);
+
+    match output {
+    "text" => {
+        writeln!(out, "{:<10} | {:>8} | {:>7}", "date", "sessions", "minutes")?;
+        for (day, sessions, minutes) in days {
+            writeln!(out, "{:<10} | {:>8} | {:>7}", day.format("%Y-%m-%d"), sessions, minutes)?;
+        }
+    }
+    "csv" => {
+        writeln!(out, "date,sessions,minutes")?;
+        for (day, sessions, minutes) in days {
+            writeln!(out, "{},{},{}", day.format("%Y-%m-%d"), sessions, minutes)?;
+        }
+    }
+    "json" => {
+        let rows: Vec<serde_json::Value> = days.iter()
+            .map(|(day, sessions, minutes)| json!({
+                "date": day.format("%Y-%m-%d").to_string(),
+                "sessions": sessions,
+                "minutes": minutes,
+            }))
+            .collect();
+        writeln!(out, "{}", serde_json::Value::Array(rows))?;
+    }
+    _ => { return Err(invalid_input("Unknown output format; it should be text, csv or json")); }
+    }
+
+    Ok(())
+}
+
+/// Write the grown trees randomly scattered in a grid. The size of the grid is in RxC
+/// format, or "whole" to use a grid as big as the screen, in any case. Each cell of the
+/// trees is drawn as a block of scale x scale characters.
+pub fn render_grid<W: Write>(out: &mut W, stats: &[GrownTree], x: &str, scale: usize) -> io::Result<()> {
+    let x = x.to_lowercase();
+    let (n, m) = if x == "whole" {
+        // Try to make the grid as big as possible
+        let (width, height) = terminal_size().unwrap();
+        let (width, height) = (width as usize, height as usize);
+
+        // Each tile is followed by a border, except the last ones
+        let tile = 5 * scale + 1;
+        (height / tile, (width + 1) / tile)
+    } else {
+        let numbers: Vec<&str> = x.split("x").collect();
+
+        if numbers.len() != 2 {
+            return Err(invalid_input("Invalid grid size format"));
+        }
+
+        let n = numbers[0].parse::<usize>();
+        let n = match n {
+        Ok(n)  => { n }
+        Err(x) => { return Err(invalid_input(format!("Invalid grid size: {}", x))); }
+        };
+
+        let m = numbers[1].parse::<usize>();
+        let m = match m {
+        Ok(m)  => { m }
+        Err(x) => { return Err(invalid_input(format!("Invalid grid size: {}", x))); }
+        };
+
+        (n, m)
+    };
+
+    let mut grid_pos: Vec<(usize, usize)> = Vec::new();
+    let mut grid: Vec<Vec<Option<&Tree>>> = vec![vec![None; m]; n];
+
+    for i in 0..n {
+        for j in 0..m {
+            grid_pos.push((i, j));
+        }
+    }
+
+    let mut rng = thread_rng();
+    grid_pos.shuffle(&mut rng);
+
+    for (pos, tree) in stats.iter().enumerate() {
+        if pos < grid_pos.len() {
+            grid[grid_pos[pos].0][grid_pos[pos].1] = Some(&tree.tree);
+        }
+    }
+
+    write_tiles(out, &grid, scale)
+}
+
+/// Write a graph of the grown time over the given time unit (daily, weekly, monthly
+/// or yearly, in any case). The graph takes the whole screen. For the daily graph, the days start at the
+/// given hour.
+pub fn render_graph<W: Write>(out: &mut W, stats: &[GrownTree], time_option: &str, day_start_hour: u32) -> io::Result<()> {
+    let (width, height) = terminal_size().unwrap();
+    render_graph_sized(out, stats, time_option, day_start_hour, width as usize, height as usize)
+}
+
+/// Write a graph of the grown time over the given time unit, for a screen of the given size.
+fn render_graph_sized<W: Write>(out: &mut W, stats: &[GrownTree], time_option: &str, day_start_hour: u32,
+                                width: usize, height: usize) -> io::Result<()> {
+    let time_option = time_option.to_lowercase();
+    let time_option = time_option.as_str();
+
+    let cnt_strips = (height - 1) / 3;
+
+    let (mut strips, mut last_time) = match time_option {
+    "daily"   => {
+         let data_grouped: Vec<(DateTime<Local>, u64)> = daily_totals(stats, day_start_hour).into_iter()
+             .map(|(key, _, total)| (key, total))
+             .collect();
+
+         let now = Local.timestamp_opt(shift_day_start(Local::now().timestamp(), day_start_hour), 0).unwrap();
+         (data_grouped, now.duration_trunc(Duration::days(1)).unwrap())
+    }
+    "weekly"  => {
+         let mut data_grouped: Vec<(DateTime<Local>, u64)> = Vec::new();
+         for(key, tree) in &stats.iter().group_by(|tree| Local.timestamp(tree.timestamp, 0).duration_trunc(Duration::weeks(1)).unwrap()) {
+            data_grouped.push((key, tree.map(|tree| tree.duration).sum()));
+         }
+
+         (data_grouped, Local::now().duration_trunc(Duration::weeks(1)).unwrap())
+    }
+    "monthly" => {
+         let mut data_grouped: Vec<(DateTime<Local>, u64)> = Vec::new();
+         for(key, tree) in &stats.iter().group_by(|tree| Local.ymd(Local.timestamp(tree.timestamp, 0).year(), Local.timestamp(tree.timestamp, 0).month(), 1)) {
+            data_grouped.push((key.and_hms(0, 0, 0), tree.map(|tree| tree.duration).sum()));
+         }
+
+         (data_grouped, Local.ymd(Local::now().year(), Local::now().month(), 1).and_hms(0, 0, 0))
+    }
+    "yearly"  => {
+         let mut data_grouped: Vec<(DateTime<Local>, u64)> = Vec::new();
+         for(key, tree) in &stats.iter().group_by(|tree| Local.ymd(Local.timestamp(tree.timestamp, 0).year(), 1, 1)) {
+            data_grouped.push((key.and_hms(0, 0, 0), tree.map(|tree| tree.duration).sum()));
+         }
+
+         (data_grouped, Local.ymd(Local::now().year(), 1, 1).and_hms(0, 0, 0))
+    }
+    _ => {
+        return Err(invalid_input("Unknown time frame option"));
+    }
+    };
+
+    let mut strips_final: Vec<(String, u64)> = Vec::new();
+
+    for _ in 0..cnt_strips {
+        let date_format = match time_option {
+        "daily" | "weekly" => { format!("{}", last_time.format("%d-%m")) }
+        "monthly" => { format!("{}", last_time.format("%m")) }
+        "yearly" => { format!("{}", last_time.format("%y")) }
+        _ => { panic!("Unexpected case"); }
+        };
+
+        match strips.pop() {
+        Some(x) => {
+            if last_time == x.0 {
+                strips_final.push((date_format, x.1));
+            } else {
+                strips_final.push((date_format, 0));
+                strips.push(x);
+            }
+        }
+        None => {
+            strips_final.push((date_format, 0));
+        }
+        }
+
+        last_time = last_time - Duration::days(1);
+        last_time = match time_option {
+        "daily" => { last_time }
+        "weekly" => { last_time.duration_trunc(Duration::weeks(1)).unwrap() }
+        "monthly" => { Local.ymd(last_time.year(), last_time.month(), 1).and_hms(0, 0, 0) }
+        "yearly" => { Local.ymd(last_time.year(), 1, 1).and_hms(0, 0, 0) }
+        _ => { panic!("Unexpected case"); }
+        }
+    }
+
+    strips_final.reverse();
+    let mut max_time = 1;
+    for stat in &strips_final {
+        max_time = cmp::max(max_time, stat.1);
+    }
+
+    let max_width = width - 1 - strips_final[0].0.len();
+
+    // Without colors, the bars would be invisible, so they are drawn with a symbol
+    let bar = if color::mode() == ColorMode::None { '#' } else { ' ' };
+
+    for stat in &strips_final {
+        write!(out, "\n{}|", stat.0)?;
+        write!(out, "{}", color::Bg((0, 0, 0)))?;
+        let ammount = (max_width as u64) * stat.1 / max_time;
+        for _ in 0..ammount {
+            write!(out, "{}", bar)?;
+        }
+        write!(out, "{}", color::Reset)?;
+        write!(out, "\n\n")?;
+    }
+
+    Ok(())
+}
+
+/// Get the number of minutes grown in the given time period.
+pub fn period_total(stats: &[GrownTree], period: &str, day_start_hour: u32) -> Result<u64, String> {
+    let mut stats = stats.to_vec();
+    filter_period(&mut stats, period, day_start_hour)?;
+
+    Ok(stats.iter().map(|tree| tree.duration).sum())
+}
+
+/// Get the progress towards a goal, as a fraction between 0 and 1.
+pub fn goal_progress(total: u64, goal: u64) -> f64 {
+    if goal == 0 {
+        1.0
+    } else {
+        (total as f64 / goal as f64).min(1.0)
+    }
+}
+
+/// Write a bar for each goal, showing how much was grown in its time period.
+pub fn render_goals<W: Write>(out: &mut W, stats: &[GrownTree], goals: &[Goal], day_start_hour: u32) -> io::Result<()> {
+    if goals.is_empty() {
+        writeln!(out, "No goals are set. Add daily_goal_minutes, weekly_goal_minutes or monthly_goal_minutes to the config file.")?;
+        return Ok(());
+    }
+
+    let bar_width = 30;
+
+    for goal in goals {
+        let total = match period_total(stats, &goal.period, day_start_hour) {
+        Ok(x) => { x }
+        Err(x) => { return Err(invalid_input(x)); }
+        };
+
+        let progress = goal_progress(total, goal.minutes);
+        let filled = (progress * bar_width as f64).floor() as usize;
+
+        writeln!(out, "{:<10} [{}{}] {:02}:{:02} / {:02}:{:02} ({:.0}%)", goal.period, "#".repeat(filled), ".".repeat(bar_width - filled),
+                 total / 60, total % 60, goal.minutes / 60, goal.minutes % 60, progress * 100.0)?;
+    }
+
+    Ok(())
+}
+
+/// Get the number of minutes grown from start (inclusive) until end (exclusive).
+pub fn range_total(stats: &[GrownTree], start: NaiveDateTime, end: NaiveDateTime) -> u64 {
+    stats.iter()
+        .filter(|tree| {
+            let date = Local.timestamp_opt(tree.timestamp, 0).unwrap().naive_local();
+            start <= date && date < end
+        })
+        .map(|tree| tree.duration)
+        .sum()
+}
+
+/// Get the first day of the period (week or month) that contains the given day, and the
+/// first day of the period before it.
+pub fn period_starts(day: NaiveDate, unit: &str) -> Result<(NaiveDate, NaiveDate), String> {
+    match unit {
+    "week" => {
+        let current = day - Duration::days(day.weekday().num_days_from_monday() as i64);
+        Ok((current, current - Duration::weeks(1)))
+    }
+    "month" => {
+        let current = day.with_day(1).unwrap();
+        let previous = (current - Duration::days(1)).with_day(1).unwrap();
+        Ok((current, previous))
+    }
+    _ => { Err("Unknown comparison period; it should be week or month".to_string()) }
+    }
+}
+
+/// Write the time grown in the current period (week or month) next to the time grown in
+/// the previous one, with the difference between them.
+pub fn render_compare<W: Write>(out: &mut W, stats: &[GrownTree], unit: &str) -> io::Result<()> {
+    render_compare_at(out, stats, unit, Local::now().naive_local())
+}
+
+/// Like render_compare, but the current period is the one that contains now.
+fn render_compare_at<W: Write>(out: &mut W, stats: &[GrownTree], unit: &str, now: NaiveDateTime) -> io::Result<()> {
+    let (current_start, previous_start) = match period_starts(now.date(), unit) {
+    Ok(x) => { x }
+    Err(x) => { return Err(invalid_input(x)); }
+    };
+    let current_start = current_start.and_hms_opt(0, 0, 0).unwrap();
+    let previous_start = previous_start.and_hms_opt(0, 0, 0).unwrap();
+
+    let current = range_total(stats, current_start, now + Duration::seconds(1));
+    let previous = range_total(stats, previous_start, current_start);
+
+    let bar_width: u64 = 30;
+    let max_time = cmp::max(1, cmp::max(current, previous));
+    let bar = |x: u64| { "#".repeat((bar_width * x / max_time) as usize) };
+
+    let delta = current as i64 - previous as i64;
+    let sign = if delta < 0 { "-" } else { "+" };
+    let percent = if previous == 0 {
+        if current == 0 { "+0%".to_string() } else { "new".to_string() }
+    } else {
+        format!("{}{:.0}%", sign, delta.abs() as f64 * 100.0 / previous as f64)
+    };
+
+    writeln!(out, "this {:<5} {:02}:{:02} {} ({}{:02}:{:02}, {})", unit, current / 60, current % 60, bar(current),
+             sign, delta.abs() / 60, delta.abs() % 60, percent)?;
+    writeln!(out, "last {:<5} {:02}:{:02} {}", unit, previous / 60, previous % 60, bar(previous))?;
+
+    Ok(())
+}
+
+/// Convert the grown trees to tab-separated values, for spreadsheets. The first line is a
+/// header, followed by a line for each tree with its label, the date in ISO 8601 format
+/// (in the given time zone), the minutes grown, the name of the tree and a note. The grown
+/// trees don't have notes yet, so the note is empty, but the column is kept for them.
+pub fn to_tsv<Tz: TimeZone>(stats: &[GrownTree], tz: &Tz) -> String where Tz::Offset: std::fmt::Display {
+    let mut tsv = String::from("label\ttimestamp\tminutes\ttree\tnote\n");
+
+    for tree in stats {
+        let date = tz.timestamp_opt(tree.timestamp, 0).unwrap();
+        tsv += &format!("{}\t{}\t{}\t{}\t\n", tree.label, date.to_rfc3339(), tree.duration, tree.tree.name);
+    }
+
+    tsv
+}
+
+/// Convert the grown trees to comma-separated values. The first line is a header, followed
+/// by a line for each tree with its timestamp, the date in RFC 3339 format (in the given
+/// time zone), the label, the minutes grown and the name of the tree. Labels and tree
+/// names can't have commas, so the values are never quoted.
+pub fn to_csv<Tz: TimeZone>(stats: &[GrownTree], tz: &Tz) -> String where Tz::Offset: std::fmt::Display {
+    let mut csv = String::from("timestamp,iso_date,label,duration_minutes,tree_name\n");
+
+    for tree in stats {
+        let date = tz.timestamp_opt(tree.timestamp, 0).unwrap();
+        csv += &format!("{},{},{},{},{}\n", tree.timestamp, date.to_rfc3339(), tree.label, tree.duration, tree.tree.name);
+    }
+
+    csv
+}
+
+/// Add the incoming grown trees to the existing ones, skipping the ones that are already
+/// there. The result is sorted by the time the trees were grown. Returns the merged trees,
+/// the number of added trees and the number of skipped duplicates.
+pub fn merge_stats(existing: Vec<GrownTree>, incoming: Vec<GrownTree>) -> (Vec<GrownTree>, usize, usize) {
+    let mut seen: HashSet<String> = existing.iter().map(|tree| tree.to_string()).collect();
+    let mut merged = existing;
+    let (mut added, mut duplicates) = (0, 0);
+
+    for tree in incoming {
+        if seen.insert(tree.to_string()) {
+            merged.push(tree);
+            added += 1;
+        } else {
+            duplicates += 1;
+        }
+    }
+
+    merged.sort_by_key(|tree| tree.timestamp);
+
+    (merged, added, duplicates)
+}
+
+/// Remove the grown trees that are exact duplicates of earlier ones, keeping the order of
+/// the others. Returns the remaining trees and the number of removed duplicates.
+pub fn dedupe_stats(stats: Vec<GrownTree>) -> (Vec<GrownTree>, usize) {
+    let mut seen: HashSet<String> = HashSet::new();
+    let total = stats.len();
+
+    let unique: Vec<GrownTree> = stats.into_iter()
+        .filter(|tree| seen.insert(tree.to_string()))
+        .collect();
+    let removed = total - unique.len();
+
+    (unique, removed)
+}
+
+/// Display the chosen view in the alternate screen and refresh it every `refresh` seconds,
+/// re-reading the grown trees (from the given file, if any) each time. The user can exit by pressing `q` or CTRL+c.
+pub fn watch(filter: &StatsFilter, view: &StatsView, refresh: u64, store: &dyn StatsStore, file: Option<&str>) -> Result<(), String> {
+    let mut display = Display::new();
+    let mut stdin = async_stdin().bytes();
+    let refresh = StdDuration::from_secs(refresh);
+
+    let mut last_refresh: Option<Instant> = None;
+    let mut exit_program = false;
+
+    while !exit_program {
+        let mut returned_none = false;
+        while !returned_none {
+            let key = stdin.next();
+            match key {
+            Some(k) => {
+                let e = termion::event::parse_event(k.unwrap(), &mut stdin);
+                match e {
+                Ok(Event::Key(Key::Ctrl('c'))) | Ok(Event::Key(Key::Char('q'))) => { exit_program = true; }
+                _ => {}
+                }
+            }
+            None => { returned_none = true; }
+            }
+        }
+
+        let should_refresh = match last_refresh {
+        Some(x) => { x.elapsed() >= refresh }
+        None => { true }
+        };
+
+        if should_refresh && !exit_program {
+            // The trees may fail to load only for a moment, so the error is shown instead
+            let text = match load_watched(store, file) {
+            Ok(mut stats) => {
+                filter.apply(&mut stats)?;
+
+                let mut buffer: Vec<u8> = Vec::new();
+                if let Err(x) = render(&mut buffer, &stats, view) {
+                    return Err(x.to_string());
+                }
+                String::from_utf8_lossy(&buffer).into_owned()
+            }
+            Err(x) => { format!("{}\n", x) }
+            };
+
+            display.display_text(&text);
+            last_refresh = Some(Instant::now());
+        }
+
+        std::thread::sleep(StdDuration::from_millis(50));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{color_mode, grown, temp_dir, MemoryStats};
+    use crate::tree::append_stats_to;
+
+    #[test]
+    fn writing_the_grid_to_a_closed_pipe_fails_with_broken_pipe() {
+        let (reader, mut writer) = io::pipe().unwrap();
+        drop(reader);
+
+        let stats: Vec<GrownTree> = (0..4).map(|x| grown("work", 25, x)).collect();
+        let err = render_grid(&mut writer, &stats, "2x2", 1).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::BrokenPipe);
+
+        let err = render(&mut writer, &stats, &StatsView::List("%Y-%m-%d".to_string())).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::BrokenPipe);
+    }
+
+    /// Get a grown tree of the given minutes, grown at the given local time.
+    fn grown_at(minutes: u64, date: &str) -> GrownTree {
+        let date = NaiveDateTime::parse_from_str(date, "%Y-%m-%d %H:%M").unwrap();
+        grown("work", minutes, Local.from_local_datetime(&date).unwrap().timestamp())
+    }
+
+    fn date(s: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M").unwrap()
+    }
+
+    #[test]
+    fn periods_start_on_monday_and_on_the_first_day() {
+        let day = NaiveDate::from_ymd_opt(2024, 3, 14).unwrap();
+        assert_eq!(period_starts(day, "week").unwrap(), (NaiveDate::from_ymd_opt(2024, 3, 11).unwrap(), NaiveDate::from_ymd_opt(2024, 3, 4).unwrap()));
+        assert_eq!(period_starts(day, "month").unwrap(), (NaiveDate::from_ymd_opt(2024, 3, 1).unwrap(), NaiveDate::from_ymd_opt(2024, 2, 1).unwrap()));
+
+        let day = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+        assert_eq!(period_starts(day, "month").unwrap().1, NaiveDate::from_ymd_opt(2023, 12, 1).unwrap());
+        assert!(period_starts(day, "year").is_err());
+    }
+
+    #[test]
+    fn compare_splits_the_time_between_the_two_periods() {
+        let stats = vec![
+            grown_at(30, "2024-02-20 10:00"),
+            grown_at(60, "2024-02-29 23:30"),
+            grown_at(45, "2024-03-01 00:00"),
+            grown_at(75, "2024-03-14 09:00"),
+            // Grown after now, so not counted yet
+            grown_at(20, "2024-03-14 18:00"),
+            grown_at(10, "2024-01-31 12:00"),
+        ];
+
+        assert_eq!(range_total(&stats, date("2024-02-01 00:00"), date("2024-03-01 00:00")), 90);
+
+        let mut out = Vec::new();
+        render_compare_at(&mut out, &stats, "month", date("2024-03-14 12:00")).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with("this month 02:00 "), "{}", lines[0]);
+        assert!(lines[0].ends_with("(+00:30, +33%)"), "{}", lines[0]);
+        assert!(lines[1].starts_with("last month 01:30 "), "{}", lines[1]);
+        assert_eq!(lines[0].matches('#').count(), 30);
+        assert_eq!(lines[1].matches('#').count(), 22);
+    }
+
+    #[test]
+    fn compare_with_an_empty_previous_period() {
+        let stats = vec![grown_at(25, "2024-03-12 10:00")];
+
+        let mut out = Vec::new();
+        render_compare_at(&mut out, &stats, "week", date("2024-03-14 12:00")).unwrap();
+        assert!(String::from_utf8(out).unwrap().lines().next().unwrap().ends_with("(+00:25, new)"));
+
+        let mut out = Vec::new();
+        render_compare_at(&mut out, &[], "week", date("2024-03-14 12:00")).unwrap();
+        assert!(String::from_utf8(out).unwrap().lines().next().unwrap().ends_with("(+00:00, +0%)"));
+    }
+
+    #[test]
+    fn tsv_can_be_read_back() {
+        let stats = vec![grown("work", 25, 1700000000), grown("deep focus", 90, 1700003600)];
+        let tz = chrono::FixedOffset::east_opt(2 * 3600).unwrap();
+        let tsv = to_tsv(&stats, &tz);
+        let lines: Vec<&str> = tsv.lines().collect();
+
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0], "label\ttimestamp\tminutes\ttree\tnote");
+        assert_eq!(lines[1], "work\t2023-11-15T00:13:20+02:00\t25\tmine\t");
+
+        for (line, tree) in lines[1..].iter().zip(&stats) {
+            let fields: Vec<&str> = line.split('\t').collect();
+            assert_eq!(fields.len(), 5);
+            assert_eq!(fields[0], tree.label);
+            assert_eq!(DateTime::parse_from_rfc3339(fields[1]).unwrap().timestamp(), tree.timestamp);
+            assert_eq!(fields[2].parse::<u64>().unwrap(), tree.duration);
+            assert_eq!(fields[3], tree.tree.name);
+            assert_eq!(fields[4], "");
+        }
+    }
+
+    /// A store that fails to load, to check it's not used.
+    struct UnusedStore;
+
+    impl StatsStore for UnusedStore {
+        fn append(&self, _tree: &GrownTree) -> Result<(), String> { Err("the store was used".to_string()) }
+        fn load(&self) -> Result<Vec<GrownTree>, String> { Err("the store was used".to_string()) }
+        fn save(&self, _stats: &[GrownTree]) -> Result<(), String> { Err("the store was used".to_string()) }
+    }
+
+    #[test]
+    fn stats_are_loaded_from_another_file() {
+        let path = std::path::Path::new(&temp_dir("stats-file")).join("archive.conf").to_string_lossy().into_owned();
+        let content = format!("{}\n{}\nbroken line\n{}\n", crate::tree::format_header(), grown("work", 25, 100), grown("read", 40, 200));
+        std::fs::write(&path, content).unwrap();
+
+        let mut stats = load_stats(&UnusedStore, Some(&path)).unwrap();
+        assert_eq!(stats.len(), 2);
+        assert_eq!((stats[0].label.as_str(), stats[0].duration), ("work", 25));
+        assert_eq!((stats[1].label.as_str(), stats[1].timestamp), ("read", 200));
+
+        // The usual filters apply to the trees from the file
+        let filter = StatsFilter { label: Some("read".to_string()), period: None, count: None, day_start_hour: 0, killed: false };
+        filter.apply(&mut stats).unwrap();
+        assert_eq!(stats.len(), 1);
+
+        assert!(load_stats(&UnusedStore, None).is_err());
+        assert!(load_stats(&UnusedStore, Some(&(path + ".missing"))).unwrap_err().starts_with("Failed to read"));
+    }
+
+    #[test]
+    fn stats_are_loaded_from_the_store() {
+        let store = MemoryStats::default();
+        store.append(&grown("work", 25, 100)).unwrap();
+        store.append(&grown("read", 40, 200)).unwrap();
+        store.append(&grown("work", 50, 300)).unwrap();
+
+        let mut stats = load_stats(&store, None).unwrap();
+        let labels: Vec<&str> = stats.iter().map(|x| x.label.as_str()).collect();
+        assert_eq!(labels, ["work", "read", "work"]);
+
+        let filter = StatsFilter { label: Some("work".to_string()), period: None, count: Some(1), day_start_hour: 0, killed: false };
+        filter.apply(&mut stats).unwrap();
+        assert_eq!(stats.len(), 1);
+        assert_eq!((stats[0].duration, stats[0].timestamp), (50, 300));
+
+        // Loading doesn't change what is in the store
+        assert_eq!(store.stats.borrow().len(), 3);
+    }
+
+    #[test]
+    fn watched_errors_are_not_hidden() {
+        let dir = temp_dir("stats-watch");
+        let path = format!("{}/stats.conf", dir);
+
+        // A removed file has no trees, but a broken store or file is an error
+        assert!(load_watched(&MemoryStats::default(), Some(&path)).unwrap().is_empty());
+        assert!(load_watched(&UnusedStore, None).is_err());
+        assert!(load_watched(&MemoryStats::default(), Some(&dir)).unwrap_err().starts_with("Failed to read"));
+
+        append_stats_to(&path, &grown("work", 25, 100)).unwrap();
+        assert_eq!(load_watched(&UnusedStore, Some(&path)).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn merging_overlapping_files_keeps_each_tree_once() {
+        let dir = temp_dir("stats-merge");
+        let path = |name: &str| { std::path::Path::new(&dir).join(name).to_string_lossy().into_owned() };
+
+        let (a, b, c, d) = (grown("work", 25, 100), grown("read", 40, 300), grown("work", 30, 200), grown("gym", 60, 50));
+        let write = |name: &str, trees: &[&GrownTree]| {
+            let content: String = trees.iter().map(|x| x.to_string() + "\n").collect();
+            std::fs::write(path(name), content).unwrap();
+        };
+        write("laptop.conf", &[&a, &b]);
+        write("desktop.conf", &[&c, &a, &b, &d, &d]);
+
+        let existing = get_stats_from(&path("laptop.conf")).unwrap();
+        let incoming = get_stats_from(&path("desktop.conf")).unwrap();
+        let (merged, added, duplicates) = merge_stats(existing, incoming);
+
+        assert_eq!((added, duplicates), (2, 3));
+        let timestamps: Vec<i64> = merged.iter().map(|x| x.timestamp).collect();
+        assert_eq!(timestamps, vec![50, 100, 200, 300]);
+
+        // Merging the same file again adds nothing
+        crate::tree::write_stats(&path("stats.conf"), &merged).unwrap();
+        let (again, added, duplicates) = merge_stats(get_stats_from(&path("stats.conf")).unwrap(), get_stats_from(&path("desktop.conf")).unwrap());
+        assert_eq!((again.len(), added, duplicates), (4, 0, 5));
+    }
+
+    #[test]
+    fn dedupe_removes_only_exact_duplicates() {
+        let mut killed = grown("work", 25, 100);
+        killed.killed = true;
+        let stats = vec![
+            grown("work", 25, 100), grown("read", 25, 100), grown("work", 25, 100),
+            grown("work", 26, 100), killed.clone(), grown("work", 25, 101), killed, grown("read", 25, 100),
+        ];
+
+        let (unique, removed) = dedupe_stats(stats);
+        assert_eq!(removed, 3);
+
+        let lines: Vec<String> = unique.iter().map(|x| x.to_string()).collect();
+        let expected: Vec<String> = [grown("work", 25, 100), grown("read", 25, 100), grown("work", 26, 100)].iter()
+            .map(|x| x.to_string())
+            .collect();
+        assert_eq!(lines.len(), 5);
+        assert_eq!(&lines[..3], &expected[..]);
+        assert!(lines[3].ends_with("/killed"));
+        assert_eq!(lines[4], grown("work", 25, 101).to_string());
+
+        assert_eq!(dedupe_stats(Vec::new()).1, 0);
+    }
+
+    #[test]
+    fn days_start_at_the_chosen_hour() {
+        assert_eq!(shift_day_start(100000, 0), 100000);
+        assert_eq!(shift_day_start(100000, 2), 100000 - 7200);
+
+        let stats = vec![
+            grown_at(10, "2024-03-14 23:00"),
+            grown_at(20, "2024-03-15 01:59"),
+            grown_at(30, "2024-03-15 02:00"),
+            grown_at(40, "2024-03-15 23:59"),
+            grown_at(50, "2024-03-16 00:30"),
+        ];
+
+        let totals: Vec<(usize, u64)> = daily_totals(&stats, 0).iter().map(|x| (x.1, x.2)).collect();
+        assert_eq!(totals, vec![(1, 10), (3, 90), (1, 50)]);
+
+        // The trees grown before 2am count for the day before
+        let totals: Vec<(usize, u64)> = daily_totals(&stats, 2).iter().map(|x| (x.1, x.2)).collect();
+        assert_eq!(totals, vec![(2, 30), (3, 120)]);
+    }
+
+    #[test]
+    fn today_includes_the_night_before_the_start_hour() {
+        let now = Local::now().timestamp();
+        let mut stats = vec![grown("now", 25, now), grown("long ago", 25, now - 3 * 86400)];
+        filter_period(&mut stats, "today", 23).unwrap();
+        assert_eq!(stats.len(), 1);
+        assert_eq!(stats[0].label, "now");
+
+        let mut stats = vec![grown("now", 25, now), grown("a day ago", 25, now - 86400)];
+        filter_period(&mut stats, "yesterday", 5).unwrap();
+        assert_eq!(stats.len(), 1);
+        assert_eq!(stats[0].label, "a day ago");
+    }
+
+    #[test]
+    fn this_month_keeps_only_the_current_month() {
+        let mut stats = vec![
+            grown_at(10, "2024-02-29 23:59"),
+            grown_at(20, "2024-03-01 00:00"),
+            grown_at(30, "2024-03-14 09:00"),
+            grown_at(40, "2024-03-31 23:59"),
+            grown_at(50, "2024-04-01 00:00"),
+            grown_at(60, "2023-03-14 09:00"),
+        ];
+        let now = Local.from_local_datetime(&date("2024-03-14 12:00")).unwrap();
+
+        filter_period_at(&mut stats, "this-month", 0, now).unwrap();
+        let minutes: Vec<u64> = stats.iter().map(|x| x.duration).collect();
+        assert_eq!(minutes, vec![20, 30, 40]);
+    }
+
+    #[test]
+    fn killed_trees_are_recorded_and_listed_apart() {
+        let path = format!("{}/stats.conf", temp_dir("stats-kills"));
+        let mut killed = grown("work", 7, 1700000000);
+        killed.killed = true;
+        append_stats_to(&path, &grown("work", 25, 1699990000)).unwrap();
+        append_stats_to(&path, &killed).unwrap();
+        append_stats_to(&path, &grown("reading", 50, 1700010000)).unwrap();
+
+        // The killed tree keeps its flag in the file
+        let content = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(content.lines().filter(|x| x.ends_with("/killed")).count(), 1);
+
+        let mut filter = StatsFilter { label: None, period: None, count: None, day_start_hour: 0, killed: true };
+        let mut stats = get_stats_from(&path).unwrap();
+        filter.apply(&mut stats).unwrap();
+        assert_eq!(stats.len(), 1);
+        assert_eq!((stats[0].duration, stats[0].timestamp), (7, 1700000000));
+
+        let mut out = Vec::new();
+        render_kills(&mut out, &stats, "%Y").unwrap();
+        let text = String::from_utf8(out).unwrap();
+        assert!(text.starts_with("1 trees killed, after growing for 00:07 in total\n"));
+
+        // The usual stats don't include the killed tree
+        filter.killed = false;
+        let mut stats = get_stats_from(&path).unwrap();
+        filter.apply(&mut stats).unwrap();
+        let minutes: Vec<u64> = stats.iter().map(|x| x.duration).collect();
+        assert_eq!(minutes, vec![25, 50]);
+    }
+
+    #[test]
+    fn period_and_grid_are_not_case_sensitive() {
+        let stats = vec![grown_at(10, "2024-03-14 09:00"), grown_at(20, "2024-02-14 09:00")];
+        let now = Local.from_local_datetime(&date("2024-03-14 12:00")).unwrap();
+
+        for period in ["This-Month", "THIS-MONTH", "Today"] {
+            let mut kept = stats.clone();
+            filter_period_at(&mut kept, period, 0, now).unwrap();
+            assert_eq!(kept.len(), 1);
+            assert_eq!(kept[0].duration, 10);
+        }
+        assert_eq!(filter_period_at(&mut stats.clone(), "Last-Month", 0, now), Err("Unknown time period".to_string()));
+
+        let _guard = color_mode(ColorMode::None);
+        let mut lower = Vec::new();
+        let mut upper = Vec::new();
+        render_grid(&mut lower, &[], "2x3", 1).unwrap();
+        render_grid(&mut upper, &[], "2X3", 1).unwrap();
+        assert_eq!(lower, upper);
+        assert!(!upper.is_empty());
+
+        for unit in ["Daily", "WEEKLY", "Monthly", "YEARLY"] {
+            let mut lower = Vec::new();
+            let mut mixed = Vec::new();
+            render_graph_sized(&mut lower, &stats, &unit.to_lowercase(), 0, 40, 10).unwrap();
+            render_graph_sized(&mut mixed, &stats, unit, 0, 40, 10).unwrap();
+            assert_eq!(lower, mixed);
+            assert!(!mixed.is_empty());
+        }
+    }
+
+    #[test]
+    fn top_ranks_by_total_time() {
+        let mut stats = vec![
+            grown("work", 25, 1),
+            grown("reading", 50, 2),
+            grown("work", 30, 3),
+            grown("music", 55, 4),
+            grown("chores", 5, 5),
+        ];
+        stats[1].tree.name = "oak".to_string();
+        stats[3].tree.name = "oak".to_string();
+
+        let mut out = Vec::new();
+        render_top(&mut out, &stats, 3, "label").unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "1) music | 00:55\n2) work | 00:55\n3) reading | 00:50\n");
+
+        assert_eq!(totals_by(&stats, "tree").unwrap(), vec![("oak".to_string(), 105), ("mine".to_string(), 60)]);
+        assert!(totals_by(&stats, "day").is_err());
+
+        let mut out = Vec::new();
+        render_top(&mut out, &stats, 10, "label").unwrap();
+        assert_eq!(String::from_utf8(out).unwrap().lines().count(), 4);
+    }
+
+    #[test]
+    fn session_stats_of_a_known_distribution() {
+        let odd = session_stats(&[50, 10, 30, 20, 90]);
+        assert_eq!((odd.count, odd.min, odd.max), (5, 10, 90));
+        assert_eq!(odd.mean, 40.0);
+        assert_eq!(odd.median, 30.0);
+
+        let even = session_stats(&[25, 25, 60, 10]);
+        assert_eq!(even.mean, 30.0);
+        assert_eq!(even.median, 25.0);
+
+        let empty = session_stats(&[]);
+        assert_eq!((empty.count, empty.min, empty.max), (0, 0, 0));
+        assert_eq!((empty.mean, empty.median), (0.0, 0.0));
+    }
+
+    #[test]
+    fn average_is_written_in_hours_and_minutes() {
+        let stats: Vec<GrownTree> = [20, 100, 45].iter().map(|x| grown("work", *x, 0)).collect();
+        let mut out = Vec::new();
+        render_average(&mut out, &stats).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(),
+                   "sessions 3\nmean     00:55\nmedian   00:45\nshortest 00:20\nlongest  01:40\n");
+
+        let mut out = Vec::new();
+        render_average(&mut out, &[]).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "No trees were grown\n");
+    }
+
+    fn daily_table(stats: &[GrownTree], output: &str) -> String {
+        let mut out = Vec::new();
+        render_daily_table(&mut out, stats, 0, output).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn daily_table_has_a_row_for_each_day_in_order() {
+        // Not sorted, like a merged stats file
+        let stats = vec![
+            grown_at(30, "2024-03-15 10:00"),
+            grown_at(25, "2024-03-14 09:00"),
+            grown_at(50, "2024-03-15 18:00"),
+            grown_at(20, "2024-03-14 22:00"),
+            grown_at(45, "2024-03-17 08:00"),
+        ];
+
+        assert_eq!(daily_table(&stats, "text"),
+                   "date       | sessions | minutes\n\
+                    2024-03-14 |        2 |      45\n\
+                    2024-03-15 |        2 |      80\n\
+                    2024-03-17 |        1 |      45\n");
+        assert_eq!(daily_table(&stats, "csv"),
+                   "date,sessions,minutes\n2024-03-14,2,45\n2024-03-15,2,80\n2024-03-17,1,45\n");
+
+        let json: serde_json::Value = serde_json::from_str(&daily_table(&stats, "json")).unwrap();
+        assert_eq!(json[1], json!({ "date": "2024-03-15", "sessions": 2, "minutes": 80 }));
+        assert_eq!(json.as_array().unwrap().len(), 3);
+
+        assert_eq!(daily_table(&[], "csv"), "date,sessions,minutes\n");
+        assert!(render_daily_table(&mut Vec::new(), &stats, 0, "xml").is_err());
+    }
+}
@@ -0,0 +1,182 @@
+//! Handle the status file of the tree that is currently growing. While growing, the tree
+//! session writes its status in `~/.rusty-forest/current.json`, so other commands can
+//! query it. The file is removed when the session ends.
+//!
+//! The file is a JSON object with the fields `pid`, `tree`, `label`, `remaining` (in
+//! seconds), `paused`, `stopwatch` and `updated` (the timestamp of the last write). For a
+//! stopwatch, `remaining` holds the seconds that passed instead.
+//!
+//! The session can also write the status in `~/.rusty-forest/current.env`, as
+//! `KEY='value'` lines that can be sourced by a shell, for instance to display the
+//! remaining time in the prompt.
+
+use std::fs;
+use serde_json::{json, Value};
+use crate::tree::{data_file, write_atomic};
+
+/// Name of the status file inside the data directory.
+const STATUS_FILE: &str = "current.json";
+
+/// Name of the status file that can be sourced by a shell.
+const STATUS_ENV_FILE: &str = "current.env";
+
+/// After how many seconds without an update the status file is considered stale.
+const STALE_AFTER: i64 = 10;
+
+/// The status of the tree that is currently growing.
+#[derive(Debug, Clone)]
+pub struct GrowStatus {
+    pub pid: u32,
+    pub tree: String,
+    pub label: String,
+    /// The seconds left, or the seconds that passed for a stopwatch.
+    pub remaining: u64,
+    pub paused: bool,
+    pub stopwatch: bool,
+    pub updated: i64,
+}
+
+impl GrowStatus {
+    /// Convert the status to its JSON representation.
+    pub fn to_json(&self) -> String {
+        json!({
+            "pid": self.pid,
+            "tree": self.tree,
+            "label": self.label,
+            "remaining": self.remaining,
+            "paused": self.paused,
+            "stopwatch": self.stopwatch,
+            "updated": self.updated,
+        }).to_string()
+    }
+
+    /// Parse the status from its JSON representation.
+    pub fn from_json(s: &str) -> Result<GrowStatus, String> {
+        let value: Value = match serde_json::from_str(s) {
+        Ok(x) => { x }
+        Err(x) => { return Err(format!("Failed to parse status: {}", x)); }
+        };
+
+        let missing = |field: &str| { format!("Failed to parse status: missing field {}", field) };
+
+        Ok(GrowStatus {
+            pid: value["pid"].as_u64().ok_or_else(|| missing("pid"))? as u32,
+            tree: value["tree"].as_str().ok_or_else(|| missing("tree"))?.to_string(),
+            label: value["label"].as_str().ok_or_else(|| missing("label"))?.to_string(),
+            remaining: value["remaining"].as_u64().ok_or_else(|| missing("remaining"))?,
+            paused: value["paused"].as_bool().ok_or_else(|| missing("paused"))?,
+            // Older versions didn't have a stopwatch
+            stopwatch: value["stopwatch"].as_bool().unwrap_or(false),
+            updated: value["updated"].as_i64().ok_or_else(|| missing("updated"))?,
+        })
+    }
+
+    /// Write the status file. The file is first written under a temporary name and then
+    /// renamed, so readers never see a partially written status.
+    pub fn write(&self) -> Result<(), String> {
+        let path = data_file(STATUS_FILE)?;
+        write_atomic(&path, &self.to_json())
+    }
+
+    /// Convert the status to `KEY='value'` lines, with the variables `RUSTY_FOREST_TREE`,
+    /// `RUSTY_FOREST_LABEL` and `RUSTY_FOREST_REMAINING` (in seconds).
+    pub fn to_env(&self) -> String {
+        format!("RUSTY_FOREST_TREE={}\nRUSTY_FOREST_LABEL={}\nRUSTY_FOREST_REMAINING={}\n",
+                shell_quote(&self.tree), shell_quote(&self.label), self.remaining)
+    }
+
+    /// Write the status file that can be sourced by a shell.
+    pub fn write_env(&self) -> Result<(), String> {
+        let path = data_file(STATUS_ENV_FILE)?;
+        write_atomic(&path, &self.to_env())
+    }
+
+    /// Load the status file. Returns None if no tree is growing.
+    pub fn load() -> Result<Option<GrowStatus>, String> {
+        let path = data_file(STATUS_FILE)?;
+
+        match fs::read_to_string(path) {
+        Ok(x) => { Ok(Some(GrowStatus::from_json(&x)?)) }
+        Err(_) => { Ok(None) }
+        }
+    }
+
+    /// Remove the status files.
+    pub fn remove() {
+        for name in [STATUS_FILE, STATUS_ENV_FILE] {
+            if let Ok(path) = data_file(name) {
+                let _ = fs::remove_file(path);
+            }
+        }
+    }
+
+    /// Returns true if the status was left behind by a session that is not running anymore,
+    /// either because its process is gone or because it hasn't been updated for a while.
+    pub fn is_stale(&self, now: i64) -> bool {
+        now - self.updated > STALE_AFTER || !process_alive(self.pid)
+    }
+}
+
+/// Get the export statements of the variables in `to_env`, or the statement that unsets
+/// them if no tree is growing.
+pub fn shell_exports(status: Option<&GrowStatus>) -> String {
+    match status {
+    Some(status) => { status.to_env().lines().map(|x| format!("export {}\n", x)).collect() }
+    None => { "unset RUSTY_FOREST_TREE RUSTY_FOREST_LABEL RUSTY_FOREST_REMAINING\n".to_string() }
+    }
+}
+
+/// Quote a value so the shell reads it as it is.
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+/// Returns true if a process with the given pid is running.
+pub fn process_alive(pid: u32) -> bool {
+    // Signal 0 doesn't send anything, it only checks if the process exists
+    let res = unsafe { libc::kill(pid as libc::pid_t, 0) };
+
+    res == 0 || std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn status(label: &str) -> GrowStatus {
+        GrowStatus {
+            pid: 1,
+            tree: "oak".to_string(),
+            label: label.to_string(),
+            remaining: 720,
+            paused: false,
+            stopwatch: false,
+            updated: 0,
+        }
+    }
+
+    #[test]
+    fn shell_exports_have_one_line_per_variable() {
+        assert_eq!(shell_exports(Some(&status("work"))),
+                   "export RUSTY_FOREST_TREE='oak'\nexport RUSTY_FOREST_LABEL='work'\nexport RUSTY_FOREST_REMAINING=720\n");
+        assert_eq!(shell_exports(None), "unset RUSTY_FOREST_TREE RUSTY_FOREST_LABEL RUSTY_FOREST_REMAINING\n");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn shell_exports_are_read_back_by_the_shell() {
+        let script = format!("{}printf %s \"$RUSTY_FOREST_LABEL\"", shell_exports(Some(&status("it's $HOME"))));
+        let output = std::process::Command::new("sh").arg("-c").arg(script).output().unwrap();
+
+        assert_eq!(String::from_utf8(output.stdout).unwrap(), "it's $HOME");
+    }
+
+    #[test]
+    fn status_json_round_trips() {
+        let json = status("work").to_json();
+        let read = GrowStatus::from_json(&json).unwrap();
+
+        assert_eq!(read.to_json(), json);
+        assert!(GrowStatus::from_json("{}").unwrap_err().contains("missing field"));
+    }
+}
@@ -0,0 +1,120 @@
+//! Where the grown trees and the tree collection are kept. Everything that reads or
+//! records grown trees goes through a `StatsStore`, and the tree collection is loaded and
+//! saved through a `TreeStore`, so the storage can be changed without touching the callers.
+
+use std::str::FromStr;
+use std::sync::OnceLock;
+use crate::tree::{Tree, GrownTree, append_stats, get_stats, save_stats, get_trees, save_trees};
+
+/// A place where the grown trees are kept.
+pub trait StatsStore {
+    /// Add a grown tree after the others.
+    fn append(&self, tree: &GrownTree) -> Result<(), String>;
+
+    /// Load all the grown trees, in the order they were added.
+    fn load(&self) -> Result<Vec<GrownTree>, String>;
+
+    /// Replace all the grown trees.
+    fn save(&self, stats: &[GrownTree]) -> Result<(), String>;
+}
+
+/// The grown trees kept in `~/.rusty-forest/stats.conf`, one on each line.
+pub struct FileStats;
+
+impl StatsStore for FileStats {
+    fn append(&self, tree: &GrownTree) -> Result<(), String> {
+        append_stats(tree)
+    }
+
+    fn load(&self) -> Result<Vec<GrownTree>, String> {
+        get_stats()
+    }
+
+    fn save(&self, stats: &[GrownTree]) -> Result<(), String> {
+        save_stats(stats)
+    }
+}
+
+/// The grown trees kept in the SQLite database `~/.rusty-forest/stats.db`.
+#[cfg(feature = "sqlite")]
+pub struct SqliteStats;
+
+#[cfg(feature = "sqlite")]
+impl StatsStore for SqliteStats {
+    fn append(&self, tree: &GrownTree) -> Result<(), String> {
+        crate::sqlite::append_stats(tree)
+    }
+
+    fn load(&self) -> Result<Vec<GrownTree>, String> {
+        crate::sqlite::get_stats()
+    }
+
+    fn save(&self, stats: &[GrownTree]) -> Result<(), String> {
+        crate::sqlite::save_stats(stats)
+    }
+}
+
+/// A place where the trees of the collection are kept. The built-in trees are not kept.
+pub trait TreeStore {
+    /// Load all the trees, in the order they were saved.
+    fn load(&self) -> Result<Vec<Tree>, String>;
+
+    /// Replace all the trees.
+    fn save(&self, trees: &[Tree]) -> Result<(), String>;
+}
+
+/// The trees kept in `~/.rusty-forest/trees.conf`, one on each line.
+pub struct FileTrees;
+
+impl TreeStore for FileTrees {
+    fn load(&self) -> Result<Vec<Tree>, String> {
+        get_trees()
+    }
+
+    fn save(&self, trees: &[Tree]) -> Result<(), String> {
+        save_trees(trees)
+    }
+}
+
+/// Where the grown trees are kept.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatsBackend {
+    /// One line for each grown tree, in `stats.conf`.
+    File,
+    /// A SQLite database, `stats.db`.
+    #[cfg(feature = "sqlite")]
+    Sqlite,
+}
+
+impl FromStr for StatsBackend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+        "file" => { Ok(StatsBackend::File) }
+        #[cfg(feature = "sqlite")]
+        "sqlite" => { Ok(StatsBackend::Sqlite) }
+        #[cfg(not(feature = "sqlite"))]
+        "sqlite" => { Err("rusty-forest was built without the sqlite feature".to_string()) }
+        _ => { Err(format!("Invalid stats backend: {}, it should be file or sqlite", s)) }
+        }
+    }
+}
+
+/// Where the grown trees are kept, if it was changed from the default.
+static STATS_BACKEND: OnceLock<StatsBackend> = OnceLock::new();
+
+/// Keep the grown trees somewhere else than `stats.conf`. This should be called before
+/// anything is loaded.
+pub fn set_stats_backend(backend: StatsBackend) {
+    let _ = STATS_BACKEND.set(backend);
+}
+
+/// Get the store of the grown trees chosen by the configuration.
+pub fn stats_store() -> Box<dyn StatsStore> {
+    match STATS_BACKEND.get() {
+    #[cfg(feature = "sqlite")]
+    Some(StatsBackend::Sqlite) => { Box::new(SqliteStats) }
+    _ => { Box::new(FileStats) }
+    }
+}
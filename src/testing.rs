@@ -0,0 +1,91 @@
+//! Helpers shared by the tests.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::fs;
+use std::sync::{Mutex, MutexGuard};
+use crate::color::{self, ColorMode};
+use crate::store::{StatsStore, TreeStore};
+use crate::tree::{Tree, GrownTree, BUILTIN_TREES};
+
+/// Held by the tests that depend on the color mode, since it's used by the whole program.
+static COLOR_MODE: Mutex<()> = Mutex::new(());
+
+/// Make an empty directory for a test and get its path. Each test uses its own name, so
+/// the tests can run at the same time.
+pub fn temp_dir(name: &str) -> String {
+    let dir = std::env::temp_dir().join(format!("rusty-forest-test-{}-{}", std::process::id(), name));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir.to_string_lossy().into_owned()
+}
+
+/// Get the shareable format of a tree that looks like the first built-in tree.
+pub fn tree_line(name: &str) -> String {
+    format!("{}:{}", BUILTIN_TREES[0].1, name)
+}
+
+/// Get a tree that looks like the first built-in tree.
+pub fn tree(name: &str) -> Tree {
+    Tree::import_tree(tree_line(name)).unwrap()
+}
+
+/// Get a grown tree with the given label, duration in minutes and timestamp.
+pub fn grown(label: &str, duration: u64, timestamp: i64) -> GrownTree {
+    GrownTree {
+        duration,
+        tree: tree("mine"),
+        label: label.to_string(),
+        timestamp,
+        killed: false,
+    }
+}
+
+/// Use the given color mode until the returned guard is dropped. The other tests that
+/// call this wait until then.
+pub fn color_mode(mode: ColorMode) -> MutexGuard<'static, ()> {
+    let guard = COLOR_MODE.lock().unwrap_or_else(|x| x.into_inner());
+    color::set_mode(mode);
+    color::set_fill(' ');
+    guard
+}
+
+/// Trees kept in memory instead of a file.
+#[derive(Default)]
+pub struct MemoryTrees {
+    pub trees: RefCell<Vec<Tree>>,
+}
+
+impl TreeStore for MemoryTrees {
+    fn load(&self) -> Result<Vec<Tree>, String> {
+        Ok(self.trees.borrow().clone())
+    }
+
+    fn save(&self, trees: &[Tree]) -> Result<(), String> {
+        *self.trees.borrow_mut() = trees.to_vec();
+        Ok(())
+    }
+}
+
+/// Grown trees kept in memory instead of a file. The clones share the same trees, so a
+/// clone can be checked after the store itself was given away.
+#[derive(Default, Clone)]
+pub struct MemoryStats {
+    pub stats: Rc<RefCell<Vec<GrownTree>>>,
+}
+
+impl StatsStore for MemoryStats {
+    fn append(&self, tree: &GrownTree) -> Result<(), String> {
+        self.stats.borrow_mut().push(tree.clone());
+        Ok(())
+    }
+
+    fn load(&self) -> Result<Vec<GrownTree>, String> {
+        Ok(self.stats.borrow().clone())
+    }
+
+    fn save(&self, stats: &[GrownTree]) -> Result<(), String> {
+        *self.stats.borrow_mut() = stats.to_vec();
+        Ok(())
+    }
+}
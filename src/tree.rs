@@ -1,12 +1,21 @@
 use termion::{color};
 use std::str::FromStr;
-use std::io::{Write, stdout};
+use std::io::{self, Write, stdout};
 use std::string::ToString;
-use std::fs::{self, File, DirBuilder};
+use std::fs::{self, DirBuilder, OpenOptions};
 use regex::Regex;
 use std::default::Default;
 use std::cmp;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::sync::OnceLock;
+use std::path::Path;
+use std::convert::TryFrom;
+use serde_json::{json, Value};
 use crate::grow::GrowthTime;
+use crate::display::Display;
+use crate::log;
+use crate::store::{TreeStore, FileTrees};
 
 /// Handle all things about trees, which could be useful also for the GUI,
 /// or for the time management.
@@ -21,7 +30,25 @@ use crate::grow::GrowthTime;
 /// The cells in order are taken from the first line, from the top to the bottom, and 
 /// for each line, they're taken in order from left to right.
 ///
-/// The name should consist only of alphabet letters, digits, empty space, hyphens or underlines.
+/// Symbols that don't fit in a byte, like `█` or `♦`, need the second version of the
+/// format, `2:<hex-string>:<name>`. There, each cell has 10 bytes, and the symbol takes
+/// the last 4 bytes, as its Unicode code point in big-endian order. The trees that only
+/// have single byte symbols are still written in the first version.
+///
+/// The name should consist only of alphabet letters, digits, empty space, hyphens or underlines,
+/// and have at most `MAX_NAME_LEN` characters.
+
+/// The maximum number of characters in the name of a tree.
+pub const MAX_NAME_LEN: usize = 64;
+
+/// Check that the name of a tree is not too long. The trees already saved are loaded
+/// even if their name is longer, but new trees can't be added with such names.
+pub fn check_name_len(name: &str) -> Result<(), String> {
+    if name.len() > MAX_NAME_LEN {
+        return Err(format!("The name is too long; it should have at most {} characters", MAX_NAME_LEN));
+    }
+    Ok(())
+}
 
 /// A cell containing the RGB-value of the background, foreground, and the character.
 #[derive(PartialEq, Clone, Copy, Debug)]
@@ -102,15 +129,32 @@ impl Tree {
     }
 
     /// Display the cell from the l'th line and c'th column at the position of the cursor.
-    pub fn display_symbol(&self, l: usize, c: usize) {
-        write!(stdout(), "{}{}{}", color::Bg(self.get_bg_color(l, c)), 
-                                   color::Fg(self.get_fg_color(l, c)), 
-                                   self.cells[l][c].symbol).expect("Failed to write");
+    pub fn display_symbol(&self, l: usize, c: usize) -> io::Result<()> {
+        self.write_symbol(&mut stdout(), l, c)
+    }
+
+    /// Write the cell from the l'th line and c'th column to the given output.
+    pub fn write_symbol<W: Write>(&self, out: &mut W, l: usize, c: usize) -> io::Result<()> {
+        write!(out, "{}{}{}", crate::color::Bg(self.cells[l][c].bg), 
+                              crate::color::Fg(self.cells[l][c].fg), 
+                              crate::color::visible_symbol(self.cells[l][c].symbol))
+    }
+
+    /// Draw the tree on the display, with its top left corner at the l'th line and c'th
+    /// column. Each cell of the tree is drawn as a block of scale x scale characters.
+    pub fn draw_at(&self, display: &mut Display, l: usize, c: usize, scale: usize) {
+        for i in 0..5 * scale {
+            for j in 0..5 * scale {
+                display.draw_pixel(l + i, c + j, self.cells[i / scale][j / scale]);
+            }
+        }
     }
 
-    /// Create a new tree from a hex string and name.
-    fn new(bytes: Vec<u8>, name: String) -> Result<Tree, String> {
-        if bytes.len() != 25 * 7 {
+    /// Create a new tree from a hex string and name. The cells have 7 bytes in the first
+    /// version of the format and 10 bytes in the second one.
+    fn new(bytes: Vec<u8>, name: String, version: u8) -> Result<Tree, String> {
+        let cell_len = if version == 1 { 7 } else { 10 };
+        if bytes.len() != 25 * cell_len {
             return Err("Wrong number of bytes".to_string());
         }
 
@@ -120,12 +164,23 @@ impl Tree {
         for _ in 0..5 {
             let mut line: Vec<Cell> = Vec::new();
             for _ in 0..5 {
+                let symbol = if version == 1 {
+                    bytes[last_byte + 6] as char
+                } else {
+                    let code = u32::from_be_bytes([bytes[last_byte + 6], bytes[last_byte + 7],
+                                                   bytes[last_byte + 8], bytes[last_byte + 9]]);
+                    match char::from_u32(code) {
+                    Some(x) => { x }
+                    None => { return Err(format!("Invalid symbol {:#x}", code)); }
+                    }
+                };
+
                 line.push(Cell {
                     bg: (bytes[last_byte], bytes[last_byte + 1], bytes[last_byte + 2]),
                     fg: (bytes[last_byte + 3], bytes[last_byte + 4], bytes[last_byte + 5]),
-                    symbol: bytes[last_byte + 6] as char,
+                    symbol,
                 });
-                last_byte += 7;
+                last_byte += cell_len;
             }
 
             arr.push(line);
@@ -143,6 +198,12 @@ impl Tree {
             return Err("The tree does not respect the format".to_string());
         }
 
+        // The first version has no prefix
+        let (version, tree) = match tree.strip_prefix("2:") {
+        Some(x) => { (2, x) }
+        None => { (1, tree.as_str()) }
+        };
+
         let parts: Vec<&str> = tree.split(":").collect();
 
         if parts.len() != 2 {
@@ -156,12 +217,104 @@ impl Tree {
 
         let tree_name = parts[1];
 
-        Tree::new(tree_data, tree_name.to_string())
+        Tree::new(tree_data, tree_name.to_string(), version)
+    }
+
+    /// Convert the tree to a JSON object on a single line, with its name and its cells,
+    /// line by line. Each cell has the bg and fg colors as [r, g, b] and the symbol.
+    pub fn to_json(&self) -> String {
+        let cells: Vec<Vec<Value>> = self.cells.iter()
+            .map(|line| line.iter()
+                .map(|cell| json!({
+                    "bg": [cell.bg.0, cell.bg.1, cell.bg.2],
+                    "fg": [cell.fg.0, cell.fg.1, cell.fg.2],
+                    "symbol": cell.symbol.to_string(),
+                }))
+                .collect())
+            .collect();
+
+        json!({
+            "name": self.name,
+            "cells": cells,
+        }).to_string()
+    }
+
+    /// Import a tree from the JSON object written by to_json.
+    pub fn from_json(s: &str) -> Result<Tree, String> {
+        let value: Value = match serde_json::from_str(s) {
+        Ok(x) => { x }
+        Err(x) => { return Err(format!("Failed to parse tree: {}", x)); }
+        };
+
+        let name = match value["name"].as_str() {
+        Some(x) if Tree::is_legit_name(x) => { x.to_string() }
+        Some(_) => { return Err("Failed to parse tree: invalid name".to_string()); }
+        None => { return Err("Failed to parse tree: missing field name".to_string()); }
+        };
+
+        let lines = match value["cells"].as_array() {
+        Some(x) if x.len() == 5 => { x }
+        _ => { return Err("Failed to parse tree: cells should have 5 lines".to_string()); }
+        };
+
+        let color = |value: &Value| -> Option<(u8, u8, u8)> {
+            let rgb: Vec<u8> = value.as_array()?.iter()
+                .map(|x| x.as_u64().and_then(|x| u8::try_from(x).ok()))
+                .collect::<Option<Vec<u8>>>()?;
+            if rgb.len() == 3 { Some((rgb[0], rgb[1], rgb[2])) } else { None }
+        };
+
+        let mut cells: Vec<Vec<Cell>> = Vec::new();
+        for line in lines {
+            let line = match line.as_array() {
+            Some(x) if x.len() == 5 => { x }
+            _ => { return Err("Failed to parse tree: each line should have 5 cells".to_string()); }
+            };
+
+            let mut row: Vec<Cell> = Vec::new();
+            for cell in line {
+                let (bg, fg) = match (color(&cell["bg"]), color(&cell["fg"])) {
+                (Some(bg), Some(fg)) => { (bg, fg) }
+                _ => { return Err("Failed to parse tree: colors should be [r, g, b], with values up to 255".to_string()); }
+                };
+
+                let mut symbol = cell["symbol"].as_str().unwrap_or("").chars();
+                let symbol = match (symbol.next(), symbol.next()) {
+                (Some(x), None) => { x }
+                _ => { return Err("Failed to parse tree: each symbol should be a single character".to_string()); }
+                };
+
+                row.push(Cell { bg, fg, symbol });
+            }
+            cells.push(row);
+        }
+
+        Ok(Tree {
+            cells,
+            name,
+        })
+    }
+
+    /// Import a tree written either in the tree format or as JSON.
+    pub fn read_tree(tree: String) -> Result<Tree, String> {
+        if tree.trim_start().starts_with('{') {
+            Tree::from_json(&tree)
+        } else {
+            Tree::import_tree(tree)
+        }
+    }
+
+    /// Returns true if the name only has the characters allowed in the tree format.
+    pub fn is_legit_name(name: &str) -> bool {
+        !name.is_empty() && name.chars().all(|x| x.is_ascii_alphanumeric() || x == '-' || x == '_' || x == ' ')
     }
 
     /// Returns true if the given tree string is correct.
     pub fn is_legit(tree: &String) -> bool {
-        Regex::new("^[A-Fa-f0-9]{350}:[-_ a-zA-Z0-9]+$").unwrap().is_match(tree)
+        // Compiling the regex takes longer than matching it, so it is compiled only once
+        static TREE_REGEX: OnceLock<Regex> = OnceLock::new();
+
+        TREE_REGEX.get_or_init(|| Regex::new("^([A-Fa-f0-9]{350}|2:[A-Fa-f0-9]{500}):[-_ a-zA-Z0-9]+$").unwrap()).is_match(tree)
     }
     
     /// Calculates the time cost of a tree. In particular, a "greener" tree will be cheaper, 
@@ -185,11 +338,19 @@ impl Tree {
 
         base_cost + bg_cost + fg_cost
     }
+
+    /// Returns true if both trees look the same, whatever their names are.
+    pub fn same_appearance(&self, other: &Tree) -> bool {
+        self.cells == other.cells
+    }
 }
 
 impl ToString for Tree {
-    /// Convert a tree to a string that respects the tree format
+    /// Convert a tree to a string that respects the tree format. The second version of the
+    /// format is used only if a symbol doesn't fit in a byte.
     fn to_string(&self) -> String {
+        let wide = self.cells.iter().flatten().any(|cell| cell.symbol as u32 > 0xff);
+
         let mut res: Vec<u8> = Vec::new();
         for l in 0..5 {
             for c in 0..5 {
@@ -199,126 +360,363 @@ impl ToString for Tree {
                 res.push(self.cells[l][c].fg.0);
                 res.push(self.cells[l][c].fg.1);
                 res.push(self.cells[l][c].fg.2);
-                res.push(self.cells[l][c].symbol as u8);
+                if wide {
+                    res.extend((self.cells[l][c].symbol as u32).to_be_bytes());
+                } else {
+                    res.push(self.cells[l][c].symbol as u8);
+                }
             }
         }
 
-        hex::encode(res) + &":" + &self.name
+        let prefix = if wide { "2:" } else { "" };
+        prefix.to_string() + &hex::encode(res) + &":" + &self.name
+    }
+}
+
+/// Get the distinct symbols drawn on the tree, in the order they first appear. Blank
+/// cells are not counted as symbols.
+pub fn distinct_symbols(tree: &Tree) -> Vec<char> {
+    let mut symbols: Vec<char> = Vec::new();
+
+    for line in &tree.cells {
+        for cell in line {
+            if cell.symbol != ' ' && !symbols.contains(&cell.symbol) {
+                symbols.push(cell.symbol);
+            }
+        }
+    }
+
+    symbols
+}
+
+/// The trees that are always in the collection, as their names and their hex strings.
+/// They are added when loading the collection, and never saved.
+pub const BUILTIN_TREES: [(&str, &str); 3] = [
+    ("default-1", "0000000000002000000000000020000000000000200000000000002000000000000020000000000000201e6e00000000201e6e00000000201e6e0000000020000000000000201e6e00000000201e6e00000000201e6e00000000201e6e00000000201e6e00000000200000000000002000000000000020321e000000002000000000000020000000000000200000000000002000000000000020321e00000000200000000000002000000000000020"),
+    ("default-2", "00000000000020000000000000201e6e00000000200000000000002000000000000020000000000000201e6e00000000201e6e00000000201e6e000000002000000000000020000000000000201e6e00000000201e6e00000000201e6e0000000020000000000000201e6e00000000201e6e00000000201e6e00000000201e6e00000000201e6e00000000200000000000002000000000000020321e00000000200000000000002000000000000020"),
+    ("default-3", "00000000000020000000000000201e6e00000000200000000000002000000000000020000000000000201e6e00000000201e6e00000000201e6e00ff00006f000000000000201e6e00ff00006f1e6e00ff00006f1e6e00000000201e6e00000000201e6e00ff00006f0000000000002000000000000020321e000000002000000000000020000000000000200000000000002000000000000020321e00000000200000000000002000000000000020"),
+];
+
+/// Returns true if the tree with the given name is always in the collection.
+pub fn is_builtin(name: &str) -> bool {
+    BUILTIN_TREES.iter().any(|x| { x.0 == name })
+}
+
+/// Returns true if the name matches the pattern. In the pattern, `*` matches any
+/// sequence of characters and `?` matches a single character. A pattern without any of
+/// them matches the names that contain it.
+pub fn name_matches(pattern: &str, name: &str) -> bool {
+    if !pattern.contains(['*', '?']) {
+        return name.contains(pattern);
     }
+
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+
+    // matched[j] is true if the pattern read so far matches the first j characters
+    let mut matched = vec![false; name.len() + 1];
+    matched[0] = true;
+
+    for chr in pattern {
+        let mut next = vec![false; name.len() + 1];
+        for j in 0..name.len() + 1 {
+            next[j] = match chr {
+            '*' => { matched[j] || (j > 0 && next[j - 1]) }
+            '?' => { j > 0 && matched[j - 1] }
+            _ => { j > 0 && matched[j - 1] && name[j - 1] == chr }
+            };
+        }
+        matched = next;
+    }
+
+    matched[name.len()]
+}
+
+/// How a tree that would be imported compares to the trees from the collection.
+#[derive(Debug, PartialEq, Eq)]
+pub enum TreeDiff {
+    /// There is no tree with the same name or appearance.
+    New,
+    /// There is a tree with the same name, but it looks different.
+    NameCollision,
+    /// There is a tree that looks the same, with the given name.
+    Duplicate(String),
 }
 
 pub struct TreeCollection {
-    pub collection: Vec<Tree>
+    pub collection: Vec<Tree>,
+    /// The names of all the trees in the collection, to find name collisions quickly.
+    names: HashSet<String>,
+    /// The last suffix given to each renamed tree, so the next rename doesn't have to
+    /// try all the suffixes again.
+    suffixes: HashMap<String, usize>,
 }
 
 impl TreeCollection {
     /// Load all trees from `~/.rusty-forest/trees.conf`.
     pub fn load() -> Self {
-        check_directories().expect("Failed to check directories");
-        
-        let home = std::env::var("HOME");
-
-        let home = match home {
-        Ok(x) => { x }
-        Err(_) => { return TreeCollection { collection: Vec::new() }; }
-        };
-        
-        let mut trees: Vec<Tree> = Vec::new();
-        
-        trees.push(Tree::import_tree("0000000000002000000000000020000000000000200000000000002000000000000020000000000000201e6e00000000201e6e00000000201e6e0000000020000000000000201e6e00000000201e6e00000000201e6e00000000201e6e00000000201e6e00000000200000000000002000000000000020321e000000002000000000000020000000000000200000000000002000000000000020321e00000000200000000000002000000000000020:default-1".to_string()).unwrap());
-        trees.push(Tree::import_tree("00000000000020000000000000201e6e00000000200000000000002000000000000020000000000000201e6e00000000201e6e00000000201e6e000000002000000000000020000000000000201e6e00000000201e6e00000000201e6e0000000020000000000000201e6e00000000201e6e00000000201e6e00000000201e6e00000000201e6e00000000200000000000002000000000000020321e00000000200000000000002000000000000020:default-2".to_string()).unwrap());
-        trees.push(Tree::import_tree("00000000000020000000000000201e6e00000000200000000000002000000000000020000000000000201e6e00000000201e6e00000000201e6e00ff00006f000000000000201e6e00ff00006f1e6e00ff00006f1e6e00000000201e6e00000000201e6e00ff00006f0000000000002000000000000020321e000000002000000000000020000000000000200000000000002000000000000020321e00000000200000000000002000000000000020:default-3".to_string()).unwrap());
-
-        let fs = fs::read_to_string(home + &"/.rusty-forest/trees.conf");
-        let fs = match fs {
-        Err(_) => { String::new() }
-        Ok(x)  => { x }
-        };
-    
-        for tree_str in fs.lines() {
-            let tree = Tree::import_tree(tree_str.to_string());
-            match tree {
-            Ok(x) => { trees.push(x); }
-            Err(x) => { println!("Failed to load tree: {}", x); }
-            };
+        // The other data files are saved in the same directory, so it's made now
+        if let Err(x) = check_directories() {
+            log::warn(&format!("Failed to check directories: {}", x));
         }
-        
+
+        TreeCollection::load_from(&FileTrees)
+    }
+
+    /// Get a collection with only the built-in trees, without reading anything.
+    pub fn builtin() -> Self {
+        let trees: Vec<Tree> = BUILTIN_TREES.iter()
+            .map(|(name, hex)| Tree::import_tree(format!("{}:{}", hex, name)).unwrap())
+            .collect();
+        let names = trees.iter().map(|x| x.name.clone()).collect();
+
         TreeCollection {
-            collection: trees
+            collection: trees,
+            names,
+            suffixes: HashMap::new(),
+        }
+    }
+
+    /// Load the built-in trees and the trees from the store.
+    pub fn load_from(store: &dyn TreeStore) -> Self {
+        let mut collection = TreeCollection::builtin();
+
+        // Without the saved trees, only the built-in trees are loaded
+        match store.load() {
+        Ok(x) => {
+            for tree in x {
+                collection.names.insert(tree.name.clone());
+                collection.collection.push(tree);
+            }
+        }
+        Err(x) => { log::warn(&format!("Failed to load trees: {}", x)); }
         }
+
+        collection
     }
 
     /// Add a tree to the tree collection, and be careful to not add a duplicate tree.
     /// If duped is true, the tree will be renamed to not collide with other trees.
     pub fn add_tree(&mut self, tree: String, duped: bool) -> Result<Tree, String> {
-        let mut tree = Tree::import_tree(tree)?;
-
-        let mut cnt = 0;
-        let mut failed = true;
-        while failed {
-            failed = false;
+        let mut tree = Tree::read_tree(tree)?;
+        check_name_len(&tree.name)?;
 
-            let new_name = if cnt == 0 {
-                tree.name.clone()
-            } else {
-                tree.name.clone() + &format!("-{}", cnt)
-            };
-            
-            for other_tree in &self.collection {
-                if other_tree.name == new_name {
-                    if !duped {
-                        return Err("Duplicate name tree exists".to_string());
-                    } else {
-                        failed = true;
-                    }
-                }
+        if self.names.contains(&tree.name) {
+            if !duped {
+                return Err("Duplicate name tree exists".to_string());
             }
-            if failed {
-                cnt = cnt + 1;
+
+            // Take the next free suffix, so the trees that already have a suffix
+            // (even the ones named by hand, like oak-1) are skipped
+            let mut cnt = self.suffixes.get(&tree.name).map_or(1, |x| x + 1);
+            while self.names.contains(&format!("{}-{}", tree.name, cnt)) {
+                cnt += 1;
             }
-        }
 
-        if cnt != 0 {
-            tree.name = tree.name + &format!("-{}", cnt);
+            self.suffixes.insert(tree.name.clone(), cnt);
+            tree.name = format!("{}-{}", tree.name, cnt);
+            check_name_len(&tree.name)?;
         }
 
+        self.names.insert(tree.name.clone());
         self.collection.push(tree.clone());
         Ok(tree)
     }
 
-    /// Save all trees in `~/.rusty-forest/trees.conf`.
-    pub fn save(&self) -> Result<(), String> {
-        let home = std::env::var("HOME");
+    /// Get the tree that is grown when no tree is chosen: the first built-in tree, or the
+    /// first tree of the collection if it's missing. There is none only if the collection
+    /// is empty.
+    pub fn default_tree(&self) -> Option<&Tree> {
+        self.collection.iter()
+            .find(|x| { x.name == BUILTIN_TREES[0].0 })
+            .or_else(|| self.collection.first())
+    }
 
-        let home = match home {
-        Ok(x) => { x }
-        Err(x) => { return Err(format!("{}", x)); }
-        };
-        
-        let mut file = File::create(home + &"/.rusty-forest/trees.conf").unwrap();
+    /// Compare a tree with the trees from the collection, without adding it. A tree that
+    /// looks the same as another one is a duplicate, even if the name is different.
+    pub fn diff(&self, tree: &Tree) -> TreeDiff {
+        if let Some(x) = self.collection.iter().find(|x| { x.same_appearance(tree) }) {
+            TreeDiff::Duplicate(x.name.clone())
+        } else if self.names.contains(&tree.name) {
+            TreeDiff::NameCollision
+        } else {
+            TreeDiff::New
+        }
+    }
+
+    /// Get the names of the trees that match the pattern (see name_matches), each once.
+    /// The built-in trees can't be erased, so they are returned separately.
+    pub fn matching_names(&self, pattern: &str) -> (Vec<String>, Vec<String>) {
+        let mut matched: Vec<String> = Vec::new();
+        let mut builtin: Vec<String> = Vec::new();
 
         for tree in &self.collection {
-            if tree.name != "default" && tree.name != "default-2" && tree.name != "default-3" {
-                file.write_all((tree.to_string() + &"\n").as_bytes()).unwrap();
+            if !name_matches(pattern, &tree.name) || matched.contains(&tree.name) || builtin.contains(&tree.name) {
+                continue;
+            }
+
+            if is_builtin(&tree.name) {
+                builtin.push(tree.name.clone());
+            } else {
+                matched.push(tree.name.clone());
             }
         }
-        
+
+        (matched, builtin)
+    }
+
+    /// Erase all the trees with the given name from the tree collection. Returns whether
+    /// any tree was erased.
+    pub fn erase_tree(&mut self, name: &str) -> bool {
+        if !self.names.remove(name) {
+            return false;
+        }
+
+        self.collection.retain(|tree| { tree.name != name });
+        true
+    }
+
+    /// Replace the tree with the given name by another tree, which may have a different
+    /// name. The new tree takes the place of the old one in the collection.
+    pub fn replace_tree(&mut self, name: &str, tree: Tree) -> Result<(), String> {
+        check_name_len(&tree.name)?;
+
+        if tree.name != name && self.names.contains(&tree.name) {
+            return Err("Duplicate name tree exists".to_string());
+        }
+
+        let idx = match self.collection.iter().position(|x| { x.name == name }) {
+        Some(x) => { x }
+        None => { return Err(format!("There is no tree named {}", name)); }
+        };
+
+        // Any other tree with the old name goes away, like when erasing it
+        self.erase_tree(name);
+        self.names.insert(tree.name.clone());
+        self.collection.insert(idx, tree);
         Ok(())
     }
+
+    /// Add the trees saved in the store since this collection was loaded, for instance by
+    /// another process, so saving the collection doesn't lose them. The trees are matched
+    /// by name, and the ones from this collection are kept. Returns how many were added.
+    pub fn merge_from(&mut self, store: &dyn TreeStore) -> Result<usize, String> {
+        let mut added = 0;
+
+        for tree in store.load()? {
+            if !self.names.contains(&tree.name) {
+                self.names.insert(tree.name.clone());
+                self.collection.push(tree);
+                added += 1;
+            }
+        }
+
+        Ok(added)
+    }
+
+    /// Save all trees in `~/.rusty-forest/trees.conf`.
+    pub fn save(&self) -> Result<(), String> {
+        self.save_to(&FileTrees)
+    }
+
+    /// Save the trees in the store, except the built-in ones.
+    pub fn save_to(&self, store: &dyn TreeStore) -> Result<(), String> {
+        let trees: Vec<Tree> = self.collection.iter()
+            .filter(|tree| !is_builtin(&tree.name))
+            .cloned()
+            .collect();
+        store.save(&trees)
+    }
 }
 
-/// Check the directories that hold the saved data. Create them if they do not exist.
-fn check_directories() -> Result<(), String> {
-    let home = std::env::var("HOME");
+/// Load the trees from `~/.rusty-forest/trees.conf`, without the built-in ones.
+pub fn get_trees() -> Result<Vec<Tree>, String> {
+    Ok(read_trees(&data_file("trees.conf")?))
+}
 
-    let home = match home {
-    Ok(x) => { x }
-    Err(x) => { return Err(format!("{}", x)); }
+/// Read the trees from a file in the format of `trees.conf`, without the built-in ones. A
+/// missing file means no trees were saved yet. The file is never changed, even if it was
+/// saved by an older version.
+pub fn read_trees(path: &str) -> Vec<Tree> {
+    let fs = fs::read_to_string(path);
+    let fs = match fs {
+    Err(_) => { String::new() }
+    Ok(x)  => { x }
     };
 
+    let mut trees: Vec<Tree> = Vec::new();
+
+    for tree_str in data_lines(&fs) {
+        let tree = Tree::import_tree(tree_str.to_string());
+        match tree {
+        // Older versions saved a copy of a built-in tree, which is always loaded
+        Ok(x) if is_builtin(&x.name) => {}
+        Ok(x) => { trees.push(x); }
+        Err(x) => { log::warn(&format!("Failed to load tree: {}", x)); }
+        };
+    }
+
+    log::info(&format!("Loaded {} trees from {}", trees.len(), path));
+    trees
+}
+
+/// Replace the trees from `~/.rusty-forest/trees.conf`.
+pub fn save_trees(trees: &[Tree]) -> Result<(), String> {
+    check_directories()?;
+    write_trees(&data_file("trees.conf")?, trees)
+}
+
+/// Write the trees in a file, in the format of `trees.conf`.
+pub fn write_trees(path: &str, trees: &[Tree]) -> Result<(), String> {
+    let content: String = trees.iter().map(|tree| tree.to_string() + "\n").collect();
+    write_atomic(path, &(format_header() + "\n" + &content))
+}
+
+/// The version of the format of `trees.conf` and `stats.conf`. Since the second version,
+/// the files start with a header line, `#rusty-forest v<version>`.
+pub const FORMAT_VERSION: u32 = 2;
+
+/// Get the header line of `trees.conf` and `stats.conf`.
+pub fn format_header() -> String {
+    format!("#rusty-forest v{}", FORMAT_VERSION)
+}
+
+/// Get the version of the format of the content of a data file. The files without a header
+/// were written by older versions, in the first version of the format.
+pub fn format_version(content: &str) -> u32 {
+    content.lines().next()
+        .and_then(|x| x.strip_prefix("#rusty-forest v"))
+        .and_then(|x| x.trim().parse().ok())
+        .unwrap_or(1)
+}
+
+/// Returns true if the content of a data file is in an older version of the format, so it
+/// should get the header of the current one.
+fn needs_migration(content: &str) -> bool {
+    !content.is_empty() && format_version(content) < FORMAT_VERSION
+}
+
+/// Add the header of the current version above the content of a data file saved by an
+/// older version. All the lines are kept, even the ones that can't be read.
+pub fn migrate_content(content: &str) -> String {
+    let mut migrated = format_header() + "\n" + content;
+    if !migrated.ends_with('\n') {
+        migrated.push('\n');
+    }
+    migrated
+}
+
+/// Get the lines of a data file that hold data. The header and the other lines starting
+/// with `#` are skipped.
+pub fn data_lines(content: &str) -> impl Iterator<Item = &str> {
+    content.lines().filter(|x| !x.starts_with('#'))
+}
+
+/// Check the directories that hold the saved data. Create them if they do not exist.
+pub fn check_directories() -> Result<(), String> {
     let res = DirBuilder::new()
         .recursive(true)
-        .create(home + &"/.rusty-forest");
+        .create(data_dir()?);
     
     if let Err(x) = res {
         return Err(format!("{}", x).to_string());
@@ -327,17 +725,95 @@ fn check_directories() -> Result<(), String> {
     Ok(())
 }
 
+/// The directory that holds the saved data, if it was changed from the default.
+static DATA_DIR: OnceLock<String> = OnceLock::new();
+
+/// Use another directory to hold the saved data, instead of the default one. This should
+/// be called before anything is loaded.
+pub fn set_data_dir(path: String) {
+    let _ = DATA_DIR.set(path);
+}
+
+/// Get the path of the directory that holds the saved data.
+pub fn data_dir() -> Result<String, String> {
+    match DATA_DIR.get() {
+    Some(x) => { Ok(x.clone()) }
+    None => { default_data_dir() }
+    }
+}
+
+/// Get the default data directory on Windows, `%APPDATA%\rusty-forest`.
+#[cfg(windows)]
+fn default_data_dir() -> Result<String, String> {
+    match std::env::var("APPDATA") {
+    Ok(x) => { Ok(Path::new(&x).join("rusty-forest").to_string_lossy().into_owned()) }
+    Err(x) => { Err(format!("Failed to find the data directory: APPDATA: {}", x)) }
+    }
+}
+
+/// Get the default data directory. This is `~/.rusty-forest` if it already exists, so the
+/// data saved by older versions is still found. Otherwise, it's `rusty-forest` inside
+/// `$XDG_DATA_HOME`, or inside `~/.local/share` if that is not set.
+#[cfg(not(windows))]
+fn default_data_dir() -> Result<String, String> {
+    let home = std::env::var("HOME");
+
+    if let Ok(x) = &home {
+        let legacy = x.clone() + "/.rusty-forest";
+        if Path::new(&legacy).is_dir() {
+            return Ok(legacy);
+        }
+    }
+
+    // The specification says that relative paths should be ignored
+    match std::env::var("XDG_DATA_HOME") {
+    Ok(x) if x.starts_with('/') => { return Ok(x + "/rusty-forest"); }
+    _ => {}
+    }
+
+    match home {
+    Ok(x) => { Ok(x + "/.local/share/rusty-forest") }
+    Err(x) => { Err(format!("Failed to find the data directory: HOME: {}", x)) }
+    }
+}
+
+/// Get the path of a file from the directory that holds the saved data.
+pub fn data_file(name: &str) -> Result<String, String> {
+    Ok(Path::new(&data_dir()?).join(name).to_string_lossy().into_owned())
+}
+
+/// Write the content to the file at the given path. The file is first written under a
+/// temporary name and then renamed, so it is never left partially written.
+pub fn write_atomic(path: &str, content: &str) -> Result<(), String> {
+    let tmp_path = path.to_string() + ".tmp";
+
+    if let Err(x) = fs::write(&tmp_path, content) {
+        return Err(format!("Failed to write {}: {}", path, x));
+    }
+    if let Err(x) = fs::rename(&tmp_path, path) {
+        return Err(format!("Failed to write {}: {}", path, x));
+    }
+
+    Ok(())
+}
+
 /// This holds all the data of an already grown tree. The data refers to 
 /// how much the tree has grown, the tree itself, its label and the date 
 /// it was grown.
 ///
 /// The format is `<duration as hh:mm>/<label>/<timstamp>/<formatted-tree>`.
-#[derive(Debug)]
+///
+/// The trees that were cancelled before they grew are recorded too, with the time they
+/// grew before that. Their lines have a fifth token, `killed`, which older versions don't
+/// load.
+#[derive(Debug, Clone)]
 pub struct GrownTree {
     pub duration: u64,
     pub tree: Tree,
     pub label: String,
     pub timestamp: i64,
+    /// True if the tree was cancelled before it grew.
+    pub killed: bool,
 }
 
 impl FromStr for GrownTree {
@@ -348,11 +824,14 @@ impl FromStr for GrownTree {
         let tokens: Vec<&str> = s.split('/').collect();
         
 
-        if tokens.len() != 4 {
-            return Err("Failed to parse stats: wrong number of tokens".to_string());
-        }
+        let killed = match tokens.len() {
+        4 => { false }
+        5 if tokens[4] == "killed" => { true }
+        5 => { return Err(format!("Failed to parse stats: unknown flag {}", tokens[4])); }
+        _ => { return Err("Failed to parse stats: wrong number of tokens".to_string()); }
+        };
 
-        let duration = GrowthTime::from_str(tokens[0])?;        
+        let duration = GrowthTime::from_record(tokens[0])?;        
         let duration: u64 = duration.h * 60 + duration.m;
         let label = tokens[1].to_string();
         let timestamp = match i64::from_str(tokens[2]) {
@@ -366,37 +845,385 @@ impl FromStr for GrownTree {
             duration,
             tree,
             label,
-            timestamp
+            timestamp,
+            killed,
         } )
     }
 }
 
-/// Load all the grown trees from `~/.rusty-forest/stats.conf`.
-pub fn get_stats() -> Result<Vec<GrownTree>, String> {
-    check_directories().expect("Failed to check directories");
-    
-    let home = std::env::var("HOME");
+impl fmt::Display for GrownTree {
+    /// Convert the grown tree to a line of the stats file.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let duration = GrowthTime { h: self.duration / 60, m: self.duration % 60 };
+        write!(f, "{}/{}/{}/{}", duration.to_string(), self.label, self.timestamp, self.tree.to_string())?;
+        if self.killed {
+            write!(f, "/killed")?;
+        }
+        Ok(())
+    }
+}
+
+/// Add a grown tree at the end of `~/.rusty-forest/stats.conf`.
+pub fn append_stats(tree: &GrownTree) -> Result<(), String> {
+    append_stats_to(&data_file("stats.conf")?, tree)
+}
 
-    let home = match home {
+/// Add a grown tree at the end of a file in the format of `stats.conf`. A file saved by an
+/// older version first gets the header of the current one, above all its lines.
+pub fn append_stats_to(path: &str, tree: &GrownTree) -> Result<(), String> {
+    let content = fs::read_to_string(path).unwrap_or_default();
+    if needs_migration(&content) {
+        write_atomic(path, &migrate_content(&content))?;
+        log::info(&format!("Migrated {} to version {}", path, FORMAT_VERSION));
+    }
+
+    let mut file = match OpenOptions::new().append(true).create(true).open(path) {
     Ok(x) => { x }
-    Err(_) => { return Ok(Vec::new()); }
+    Err(x) => { return Err(format!("Failed to open stats file: {}", x)); }
     };
+
+    // A new file starts with the header
+    let mut line = tree.to_string() + "\n";
+    if file.metadata().map(|x| x.len() == 0).unwrap_or(false) {
+        line = format_header() + "\n" + &line;
+    }
+
+    match file.write_all(line.as_bytes()) {
+    Ok(_) => { Ok(()) }
+    Err(x) => { Err(format!("Failed to write stats file: {}", x)) }
+    }
+}
+
+/// Replace all the grown trees from `~/.rusty-forest/stats.conf`.
+pub fn save_stats(stats: &[GrownTree]) -> Result<(), String> {
+    check_directories()?;
+    write_stats(&data_file("stats.conf")?, stats)
+}
+
+/// Write the grown trees in a file, in the format of `stats.conf`.
+pub fn write_stats(path: &str, stats: &[GrownTree]) -> Result<(), String> {
+    let content: String = stats.iter().map(|tree| tree.to_string() + "\n").collect();
+    write_atomic(path, &(format_header() + "\n" + &content))
+}
+
+/// Load all the grown trees from `~/.rusty-forest/stats.conf`. The file is never changed,
+/// even if it was saved by an older version.
+pub fn get_stats() -> Result<Vec<GrownTree>, String> {
+    check_directories()?;
     
-    let mut trees: Vec<GrownTree> = Vec::new();
-    let fs = fs::read_to_string(home + &"/.rusty-forest/stats.conf");
+    let path = data_file("stats.conf")?;
+    
+    let fs = fs::read_to_string(&path);
     let fs = match fs {
     Err(_) => { String::new() }
     Ok(x)  => { x }
     };
     
-    for line in fs.lines() {
+    let stats = parse_stats(&fs);
+    log::info(&format!("Loaded {} grown trees from {}", stats.len(), path));
+
+    Ok(stats)
+}
+
+/// Load the grown trees from another file, in the same format as `stats.conf`.
+pub fn get_stats_from(path: &str) -> Result<Vec<GrownTree>, String> {
+    match fs::read_to_string(path) {
+    Ok(x) => { Ok(parse_stats(&x)) }
+    Err(x) => { Err(format!("Failed to read {}: {}", path, x)) }
+    }
+}
+
+/// Parse the grown trees, one on each line. The lines that can't be parsed are skipped.
+fn parse_stats(content: &str) -> Vec<GrownTree> {
+    let mut trees: Vec<GrownTree> = Vec::new();
+
+    for line in data_lines(content) {
         let tree = GrownTree::from_str(line);
         match tree {
         Ok(x) => { trees.push(x); }
-        Err(x) => { println!("Failed to load tree: {}", x); }
+        Err(x) => { log::warn(&format!("Failed to load grown tree: {}", x)); }
         }
     }
 
-    Ok(trees)
+    trees
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{MemoryTrees, grown, temp_dir, tree_line};
+
+    #[test]
+    fn distinct_symbols_are_listed_once_in_order() {
+        let mut tree = Tree::default();
+        tree.cells[0][1].symbol = '*';
+        tree.cells[1][0].symbol = 'o';
+        tree.cells[2][2].symbol = '*';
+        tree.cells[3][3].symbol = '🍎';
+        tree.cells[4][4].symbol = 'o';
+
+        assert_eq!(distinct_symbols(&tree), vec!['*', 'o', '🍎']);
+        assert_eq!(distinct_symbols(&Tree::default()), Vec::<char>::new());
+    }
+
+    fn names(trees: &TreeCollection) -> Vec<&str> {
+        trees.collection.iter().map(|x| x.name.as_str()).collect()
+    }
+
+    #[test]
+    fn renamed_trees_skip_the_existing_suffixes() {
+        let mut trees = TreeCollection::builtin();
+        for name in ["oak", "oak-1", "oak-3"] {
+            trees.add_tree(tree_line(name), false).unwrap();
+        }
+
+        assert_eq!(trees.add_tree(tree_line("oak"), true).unwrap().name, "oak-2");
+        assert_eq!(trees.add_tree(tree_line("oak"), true).unwrap().name, "oak-4");
+        assert_eq!(trees.add_tree(tree_line("oak-1"), true).unwrap().name, "oak-1-1");
+        assert!(trees.add_tree(tree_line("oak"), false).is_err());
+        assert_eq!(&names(&trees)[3..], &["oak", "oak-1", "oak-3", "oak-2", "oak-4", "oak-1-1"]);
+    }
+
+    #[test]
+    fn bulk_import_gives_each_tree_its_own_name() {
+        let mut trees = TreeCollection::builtin();
+        trees.add_tree(tree_line("pine-5"), false).unwrap();
+        for _ in 0..1000 {
+            trees.add_tree(tree_line("pine"), true).unwrap();
+        }
+
+        assert_eq!(trees.collection.len(), 3 + 1001);
+        let unique: HashSet<&str> = names(&trees).into_iter().collect();
+        assert_eq!(unique.len(), trees.collection.len());
+        assert_eq!(trees.collection[4].name, "pine");
+        assert_eq!(trees.collection[5].name, "pine-1");
+        assert_eq!(trees.collection[9].name, "pine-6");
+        assert_eq!(trees.collection.last().unwrap().name, "pine-1000");
+    }
+
+    #[test]
+    fn name_index_follows_adds_erases_and_replacements() {
+        let mut trees = TreeCollection::builtin();
+        for idx in 0..5000 {
+            trees.add_tree(tree_line(&format!("tree{}", idx)), false).unwrap();
+        }
+        assert_eq!(trees.collection.len(), 5003);
+        assert!(trees.add_tree(tree_line("tree4999"), false).is_err());
+
+        // An erased name can be used again
+        assert!(trees.erase_tree("tree10"));
+        assert!(!trees.erase_tree("tree10"));
+        assert_eq!(trees.add_tree(tree_line("tree10"), false).unwrap().name, "tree10");
+
+        // A replaced tree frees its old name and takes the new one
+        let mut renamed = crate::testing::tree("renamed");
+        trees.replace_tree("tree20", renamed.clone()).unwrap();
+        assert!(trees.add_tree(tree_line("tree20"), false).is_ok());
+        assert!(trees.add_tree(tree_line("renamed"), false).is_err());
+        assert_eq!(trees.collection[22].name, "renamed");
+
+        renamed.name = "tree30".to_string();
+        assert!(trees.replace_tree("tree31", renamed).is_err());
+        assert_eq!(trees.diff(&Tree::default()), TreeDiff::New);
+    }
+
+    #[test]
+    fn patterns_match_names() {
+        assert!(name_matches("oak", "old-oak-2"));
+        assert!(!name_matches("oak", "pine"));
+        assert!(name_matches("oak*", "oak-2"));
+        assert!(!name_matches("oak*", "old-oak"));
+        assert!(name_matches("*-?", "oak-2"));
+        assert!(!name_matches("*-?", "oak-12"));
+        assert!(name_matches("*", ""));
+        assert!(name_matches("\u{e9}t?", "\u{e9}t\u{e9}"));
+    }
+
+    #[test]
+    fn pattern_erases_the_matching_trees_but_no_defaults() {
+        let mut trees = TreeCollection::builtin();
+        for name in ["oak", "oak-1", "pine", "red-oak"] {
+            trees.add_tree(tree_line(name), false).unwrap();
+        }
+
+        let (matched, builtin) = trees.matching_names("oak*");
+        assert_eq!(matched, vec!["oak", "oak-1"]);
+        assert!(builtin.is_empty());
+
+        let (matched, builtin) = trees.matching_names("*-?");
+        assert_eq!(matched, vec!["oak-1"]);
+        assert_eq!(builtin, vec!["default-1", "default-2", "default-3"]);
+
+        for name in trees.matching_names("oak").0 {
+            trees.erase_tree(&name);
+        }
+        assert_eq!(names(&trees), vec!["default-1", "default-2", "default-3", "pine"]);
+    }
+
+    #[test]
+    fn erased_defaults_come_back_after_reloading() {
+        assert!(is_builtin("default-2"));
+        assert!(!is_builtin("default-4"));
+
+        let store = MemoryTrees::default();
+        let mut trees = TreeCollection::builtin();
+        trees.add_tree(tree_line("oak"), false).unwrap();
+        assert!(trees.erase_tree("default-1"));
+        trees.save_to(&store).unwrap();
+
+        // The built-in trees are never saved
+        assert_eq!(store.trees.borrow().iter().map(|x| x.name.as_str()).collect::<Vec<_>>(), vec!["oak"]);
+
+        let trees = TreeCollection::load_from(&store);
+        assert_eq!(names(&trees), vec!["default-1", "default-2", "default-3", "oak"]);
+        assert_eq!(trees.default_tree().unwrap().name, "default-1");
+    }
+
+    /// A store that can't be read, like a missing `trees.conf`.
+    struct BrokenTrees;
+
+    impl TreeStore for BrokenTrees {
+        fn load(&self) -> Result<Vec<Tree>, String> { Err("no trees".to_string()) }
+        fn save(&self, _trees: &[Tree]) -> Result<(), String> { Err("no trees".to_string()) }
+    }
+
+    #[test]
+    fn trees_go_through_the_store() {
+        let store = MemoryTrees::default();
+        let mut trees = TreeCollection::load_from(&store);
+        assert_eq!(names(&trees), vec!["default-1", "default-2", "default-3"]);
+
+        trees.add_tree(tree_line("oak"), false).unwrap();
+        trees.add_tree(tree_line("pine"), false).unwrap();
+        trees.replace_tree("pine", Tree::read_tree(tree_line("birch")).unwrap()).unwrap();
+        trees.save_to(&store).unwrap();
+
+        let saved: Vec<String> = store.trees.borrow().iter().map(|x| x.to_string()).collect();
+        let loaded = TreeCollection::load_from(&store);
+        assert_eq!(names(&loaded), vec!["default-1", "default-2", "default-3", "oak", "birch"]);
+        let reloaded: Vec<String> = loaded.collection.iter()
+            .filter(|x| !is_builtin(&x.name))
+            .map(|x| x.to_string())
+            .collect();
+        assert_eq!(reloaded, saved);
+
+        // Without a readable store, only the built-in trees are there
+        let trees = TreeCollection::load_from(&BrokenTrees);
+        assert_eq!(names(&trees), vec!["default-1", "default-2", "default-3"]);
+        assert!(trees.save_to(&BrokenTrees).is_err());
+    }
+
+    #[test]
+    fn imported_names_are_limited() {
+        let mut trees = TreeCollection::builtin();
+        let below = "b".repeat(MAX_NAME_LEN - 1);
+        let at = "a".repeat(MAX_NAME_LEN);
+        let above = "c".repeat(MAX_NAME_LEN + 1);
+
+        assert!(check_name_len(&at).is_ok());
+        assert!(check_name_len(&above).is_err());
+        assert_eq!(trees.add_tree(tree_line(&below), false).unwrap().name, below);
+        assert_eq!(trees.add_tree(tree_line(&at), false).unwrap().name, at);
+        assert!(trees.add_tree(tree_line(&above), false).unwrap_err().starts_with("The name is too long"));
+        assert!(!trees.collection.iter().any(|x| x.name == above));
+
+        // The suffix of a renamed tree can't make the name too long either
+        assert!(trees.add_tree(tree_line(&at), true).is_err());
+        assert_eq!(trees.add_tree(tree_line(&below), true).unwrap_err(), check_name_len(&format!("{}-1", below)).unwrap_err());
+    }
+
+    /// Trees kept in a file of the given path, like `trees.conf`.
+    struct PathTrees(String);
+
+    impl TreeStore for PathTrees {
+        fn load(&self) -> Result<Vec<Tree>, String> {
+            Ok(read_trees(&self.0))
+        }
+
+        fn save(&self, trees: &[Tree]) -> Result<(), String> {
+            write_trees(&self.0, trees)
+        }
+    }
+
+    #[test]
+    fn saving_and_reloading_keeps_the_file_the_same() {
+        let store = PathTrees(format!("{}/trees.conf", temp_dir("tree-round-trip")));
+        let size = || fs::metadata(&store.0).unwrap().len();
+
+        // Only the built-in trees
+        TreeCollection::builtin().save_to(&store).unwrap();
+        assert_eq!(size() as usize, format_header().len() + 1);
+
+        let mut trees = TreeCollection::load_from(&store);
+        trees.add_tree(tree_line("oak"), false).unwrap();
+        trees.save_to(&store).unwrap();
+        let saved = size();
+
+        for _ in 0..3 {
+            let trees = TreeCollection::load_from(&store);
+            assert_eq!(names(&trees), vec!["default-1", "default-2", "default-3", "oak"]);
+            trees.save_to(&store).unwrap();
+            assert_eq!(size(), saved);
+        }
+
+        // A built-in tree saved by an older version is dropped
+        let mut content = fs::read_to_string(&store.0).unwrap();
+        content += &format!("{}:{}\n", BUILTIN_TREES[0].1, BUILTIN_TREES[0].0);
+        fs::write(&store.0, content).unwrap();
+        TreeCollection::load_from(&store).save_to(&store).unwrap();
+        assert_eq!(size(), saved);
+    }
+
+    #[test]
+    fn default_tree_falls_back_to_any_tree() {
+        let store = MemoryTrees::default();
+        let mut trees = TreeCollection::load_from(&store);
+        for (name, _) in BUILTIN_TREES {
+            assert!(trees.erase_tree(name));
+        }
+        assert!(trees.collection.is_empty());
+        assert!(trees.default_tree().is_none());
+
+        trees.add_tree(tree_line("oak"), false).unwrap();
+        trees.add_tree(tree_line("pine"), false).unwrap();
+        assert_eq!(trees.default_tree().unwrap().name, "oak");
+    }
+
+    #[test]
+    fn v1_stats_get_the_header_and_keep_their_lines() {
+        let path = format!("{}/stats.conf", temp_dir("tree-migrate-stats"));
+        let v1 = format!("{}\n{}", grown("work", 25, 1700000000), grown("study", 50, 1700000100));
+        fs::write(&path, &v1).unwrap();
+        assert_eq!(format_version(&v1), 1);
+
+        // Reading doesn't change the file
+        assert_eq!(get_stats_from(&path).unwrap().len(), 2);
+        assert_eq!(fs::read_to_string(&path).unwrap(), v1);
+
+        append_stats_to(&path, &grown("music", 15, 1700000200)).unwrap();
+        let content = fs::read_to_string(&path).unwrap();
+        assert_eq!(content, format!("{}\n{}\n{}\n", format_header(), v1, grown("music", 15, 1700000200)));
+        assert_eq!(format_version(&content), FORMAT_VERSION);
+
+        // The header is added only once
+        append_stats_to(&path, &grown("music", 15, 1700000300)).unwrap();
+        let content = fs::read_to_string(&path).unwrap();
+        assert_eq!(content.lines().filter(|x| x.starts_with('#')).count(), 1);
+        assert_eq!(get_stats_from(&path).unwrap().len(), 4);
+    }
+
+    #[test]
+    fn v1_trees_are_saved_with_the_header() {
+        let path = format!("{}/trees.conf", temp_dir("tree-migrate-trees"));
+        let v1 = format!("{}\n{}\n", tree_line("oak"), tree_line("pine"));
+        fs::write(&path, &v1).unwrap();
+
+        let trees = read_trees(&path);
+        assert_eq!(trees.len(), 2);
+        write_trees(&path, &trees).unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), format!("{}\n{}", format_header(), v1));
+        assert_eq!(migrate_content("a\nb"), format!("{}\na\nb\n", format_header()));
+    }
+}